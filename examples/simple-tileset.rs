@@ -1,10 +1,9 @@
 use std::io::stdin;
-use std::marker::PhantomData;
 
 use rand::thread_rng;
 use termion::color::{Fg, Green, Magenta, Red, Reset};
 use wave_collapse::tile2d::*;
-use wave_collapse::wave_function::{WaveShape, WaveSolver};
+use wave_collapse::wave_function::ReadShape;
 use wave_collapse::*;
 
 fn main() {
@@ -13,7 +12,7 @@ fn main() {
     let wait_for_user = false;
     let color = true;
     let tile_size = Size2D::new(50, 16); // 50, 16
-    let cutoff_behaviour = CutoffBehaviour::Ignored;
+    let closed_edges = [Direction::Bottom]; // only used with wrapping_mode::Cutoff
     type WrappingMode = wrapping_mode::Wrapping;
     let tiles = tiles_all();
     // *************************** Settings *********************************
@@ -25,10 +24,11 @@ fn main() {
         print_tile_map(&shape, false, color);
     }
 
-    let solver = TileSolver::<WrappingMode>::new(cutoff_behaviour);
+    let solver = tile_solver(&tiles, &closed_edges);
 
     let mut rng = thread_rng();
-    let mut result_iter = collapse_wave(shape, &solver, &mut rng);
+    let mut result_iter =
+        collapse_wave::<_, _, _, Kernel2D<WrappingMode, Tile2D>, _>(shape, &solver, &mut rng);
 
     if log_steps {
         for (n, shape) in &mut result_iter.enumerate() {
@@ -44,7 +44,7 @@ fn main() {
 
     println!("Result: ");
     match result_iter.calc_result() {
-        Ok(shape) => print_tile_map(&shape, false, color),
+        Ok(outcome) => print_tile_map(&outcome.shape, false, color),
         Err(error) => eprintln!("Failed to collapse wave: {error:?}"),
     }
 }
@@ -58,6 +58,16 @@ pub struct Tile2D {
 }
 
 impl Tile2D {
+    fn passage(&self, direction: Direction) -> bool {
+        match direction {
+            Direction::Left => self.left,
+            Direction::Right => self.right,
+            Direction::Top => self.top,
+            Direction::Bottom => self.bot,
+            _ => false,
+        }
+    }
+
     fn get_char_at(&self, x: u32, y: u32) -> char {
         match (x, y) {
             (1, 1) => {
@@ -100,93 +110,29 @@ impl Tile2D {
     }
 }
 
-#[allow(dead_code)]
-enum CutoffBehaviour {
-    Wall,
-    Passage,
-    Ignored,
-}
-
-impl CutoffBehaviour {
-    fn cutoff(&self, passage: bool) -> bool {
-        match self {
-            CutoffBehaviour::Wall => !passage,
-            CutoffBehaviour::Passage => passage,
-            CutoffBehaviour::Ignored => true,
-        }
-    }
-}
-
-pub struct TileSolver<WrappingMode> {
-    cutoff_behaviour: CutoffBehaviour,
-    _wrapping_mode: PhantomData<WrappingMode>,
-}
-
-impl<WrappingMode> Default for TileSolver<WrappingMode> {
-    fn default() -> Self {
-        Self {
-            cutoff_behaviour: CutoffBehaviour::Ignored,
-            _wrapping_mode: Default::default(),
-        }
-    }
-}
-
-impl<WrappingMode> TileSolver<WrappingMode> {
-    fn new(cutoff_behaviour: CutoffBehaviour) -> Self {
-        Self {
-            cutoff_behaviour,
-            _wrapping_mode: Default::default(),
+/// Connects tiles with matching passages and closes the map edges in `closed_edges`.
+fn tile_solver(tiles: &[Tile2D], closed_edges: &[Direction]) -> AdjacencySolver2D<Tile2D> {
+    let mut solver = AdjacencySolver2D::new();
+    for tile in tiles {
+        for other_tile in tiles {
+            if tile.right == other_tile.left {
+                solver = solver.with_rule(tile.clone(), other_tile.clone(), Direction::Right);
+            }
+            if tile.bot == other_tile.top {
+                solver = solver.with_rule(tile.clone(), other_tile.clone(), Direction::Bottom);
+            }
         }
     }
 
-    fn is_tile_valid(&self, tile: &Tile2D, kernel: &Kernel2D<WrappingMode, Tile2D>) -> bool {
-        assert!(kernel.radius_x == 1 && kernel.radius_y == 1);
-
-        let left_node = kernel.get(-1, 0);
-        let right_node = kernel.get(1, 0);
-        let top_node = kernel.get(0, -1);
-        let bot_node = kernel.get(0, 1);
-
-        let left_valid = left_node
-            .map(|node| {
-                node.possible_values()
-                    .iter()
-                    .any(|other_tile| tile.left == other_tile.right)
-            })
-            .unwrap_or(self.cutoff_behaviour.cutoff(tile.left));
-
-        let right_valid = right_node
-            .map(|node| {
-                node.possible_values()
-                    .iter()
-                    .any(|other_tile| tile.right == other_tile.left)
-            })
-            .unwrap_or(self.cutoff_behaviour.cutoff(tile.right));
-
-        let top_valid = top_node
-            .map(|node| {
-                node.possible_values()
-                    .iter()
-                    .any(|other_tile| tile.top == other_tile.bot)
-            })
-            .unwrap_or(self.cutoff_behaviour.cutoff(tile.top));
-
-        let bot_valid = bot_node
-            .map(|node| {
-                node.possible_values()
-                    .iter()
-                    .any(|other_tile| tile.bot == other_tile.top)
-            })
-            .unwrap_or(self.cutoff_behaviour.cutoff(tile.bot));
-
-        left_valid && right_valid && top_valid && bot_valid
-    }
-}
-
-impl<WrappingMode> WaveSolver<Tile2D, Kernel2D<WrappingMode, Tile2D>> for TileSolver<WrappingMode> {
-    fn is_valid(&self, tile: &Tile2D, kernel: &Kernel2D<WrappingMode, Tile2D>) -> bool {
-        self.is_tile_valid(tile, kernel)
+    for &direction in closed_edges {
+        let closed = tiles
+            .iter()
+            .filter(|tile| !tile.passage(direction))
+            .cloned()
+            .collect();
+        solver = solver.with_cutoff(direction, CutoffBehaviour::Only(closed));
     }
+    solver
 }
 
 fn tiles() -> Vec<Tile2D> {
@@ -295,7 +241,7 @@ fn print_tile_map(tile_map: &TileMap2D<Tile2D>, user_step: bool, use_color: bool
     for y in 0..size.height {
         for sub_y in 0..3 {
             for x in 0..size.width {
-                let node = tile_map.get_node(&(x, y)).unwrap();
+                let node = &tile_map[(x, y)];
                 for sub_x in 0..3 {
                     // TODO add color
                     if node.is_overspecified() {
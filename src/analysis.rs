@@ -0,0 +1,465 @@
+//! Static analysis of tilesets, that can be run before collapsing a wave function.
+
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet, VecDeque},
+    hash::Hash,
+};
+
+use crate::tile2d::wrapping_mode::Cutoff;
+use crate::tile2d::{
+    probe_neighbor, probe_solver, AdjacencySolver2D, CutoffBehaviour, Direction, Kernel2D, Size2D,
+};
+use crate::wave_function::WaveSolver;
+
+/// The result of [analyze_coverage].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoverageReport<NodeValue> {
+    /// (value, offset) slots for which no value is a valid neighbor.
+    pub dead_slots: Vec<(NodeValue, (i64, i64))>,
+    /// (value, offset) pairs where the value can never be placed so that `offset` is outside
+    /// of the map.
+    pub border_excluded: Vec<(NodeValue, (i64, i64))>,
+    /// values that can not be reached from the seed values.
+    pub unreachable: Vec<NodeValue>,
+}
+
+impl<NodeValue> CoverageReport<NodeValue> {
+    /// returns `true` if the analysis found no problems.
+    pub fn is_empty(&self) -> bool {
+        self.dead_slots.is_empty() && self.border_excluded.is_empty() && self.unreachable.is_empty()
+    }
+}
+
+/// Analyzes how well `tiles` cover each other according to `solver`.
+///
+/// Each offset within `kernel_size` is treated as a separate direction. The border behaviour
+/// is defined by `solver`, which is asked whether a value is valid if the node at an offset
+/// is outside of the map, so the cutoff policy under test should be configured in the solver.
+/// A value is reachable if it is one of the `seeds` or a valid neighbor of a reachable value.
+pub fn analyze_coverage<NodeValue, Solver>(
+    solver: &Solver,
+    tiles: &[NodeValue],
+    kernel_size: Size2D,
+    seeds: &[NodeValue],
+) -> CoverageReport<NodeValue>
+where
+    NodeValue: Clone + PartialEq,
+    Solver: WaveSolver<NodeValue, Kernel2D<Cutoff, NodeValue>>,
+{
+    let radius_x = ((kernel_size.width - 1) / 2) as i64;
+    let radius_y = ((kernel_size.height - 1) / 2) as i64;
+
+    let mut offsets = Vec::new();
+    for dy in -radius_y..=radius_y {
+        for dx in -radius_x..=radius_x {
+            if dx != 0 || dy != 0 {
+                offsets.push((dx, dy));
+            }
+        }
+    }
+
+    let mut dead_slots = Vec::new();
+    let mut border_excluded = Vec::new();
+    let mut neighbors = vec![Vec::new(); tiles.len()];
+
+    for (index, value) in tiles.iter().enumerate() {
+        for &offset in &offsets {
+            let mut any_valid = false;
+            for (neighbor_index, neighbor) in tiles.iter().enumerate() {
+                if probe_neighbor(solver, tiles, kernel_size, value, neighbor, offset) {
+                    any_valid = true;
+                    if !neighbors[index].contains(&neighbor_index) {
+                        neighbors[index].push(neighbor_index);
+                    }
+                }
+            }
+            if !any_valid {
+                dead_slots.push((value.clone(), offset));
+            }
+
+            if !is_valid_at_border(solver, tiles, kernel_size, value, offset) {
+                border_excluded.push((value.clone(), offset));
+            }
+        }
+    }
+
+    let mut reachable = vec![false; tiles.len()];
+    let mut open_list = VecDeque::new();
+    for (index, value) in tiles.iter().enumerate() {
+        if seeds.contains(value) {
+            reachable[index] = true;
+            open_list.push_back(index);
+        }
+    }
+    while let Some(index) = open_list.pop_front() {
+        for &neighbor_index in &neighbors[index] {
+            if !reachable[neighbor_index] {
+                reachable[neighbor_index] = true;
+                open_list.push_back(neighbor_index);
+            }
+        }
+    }
+
+    let unreachable = tiles
+        .iter()
+        .zip(reachable)
+        .filter(|(_, reachable)| !reachable)
+        .map(|(value, _)| value.clone())
+        .collect();
+
+    CoverageReport {
+        dead_slots,
+        border_excluded,
+        unreachable,
+    }
+}
+
+/// checks if `value` is valid when the node at `offset` is outside of the map and all other
+/// nodes in the kernel are unconstrained.
+fn is_valid_at_border<NodeValue, Solver>(
+    solver: &Solver,
+    tiles: &[NodeValue],
+    kernel_size: Size2D,
+    value: &NodeValue,
+    offset: (i64, i64),
+) -> bool
+where
+    NodeValue: Clone,
+    Solver: WaveSolver<NodeValue, Kernel2D<Cutoff, NodeValue>>,
+{
+    let place = |delta: i64, size: u32| -> u32 {
+        match delta.cmp(&0) {
+            Ordering::Greater => (size as i64 - delta) as u32,
+            Ordering::Less => (-delta - 1) as u32,
+            Ordering::Equal => (size - 1) / 2,
+        }
+    };
+    let center = (
+        place(offset.0, kernel_size.width),
+        place(offset.1, kernel_size.height),
+    );
+
+    probe_solver(solver, tiles, kernel_size, kernel_size, center, value, &[])
+}
+
+/// A rule of an [AdjacencySolver2D]: the second value is allowed next to the first value in the
+/// [Direction], see [AdjacencySolver2D::with_rule].
+pub type AdjacencyRule<NodeValue> = (NodeValue, NodeValue, Direction);
+
+/// The result of [minimize_rules].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleReport<NodeValue> {
+    /// the rules that are neither redundant nor dead, in their original order.
+    pub rules: Vec<AdjacencyRule<NodeValue>>,
+    /// rules that are duplicates of an earlier rule, either directly or mirrored in the
+    /// opposite direction.
+    pub redundant: Vec<AdjacencyRule<NodeValue>>,
+    /// rules that can never fire, because one of their values is not one of the tiles or is a
+    /// dead value.
+    pub dead: Vec<AdjacencyRule<NodeValue>>,
+    /// values that can never be placed, because no placeable value is allowed next to them in
+    /// a constrained direction and the cutoff of that direction does not allow them either.
+    pub dead_values: Vec<NodeValue>,
+    /// values that can only be placed at the edge of a map, because no placeable value is
+    /// allowed next to them in a constrained direction, but the cutoff of that direction allows
+    /// them. Their rules are kept.
+    pub edge_only: Vec<NodeValue>,
+}
+
+impl<NodeValue> RuleReport<NodeValue> {
+    /// returns `true` if no rule was removed.
+    pub fn is_empty(&self) -> bool {
+        self.redundant.is_empty() && self.dead.is_empty()
+    }
+
+    /// returns an [AdjacencySolver2D] with the remaining rules. Cutoffs are not part of the
+    /// rules and need to be added again.
+    pub fn to_solver(&self) -> AdjacencySolver2D<NodeValue>
+    where
+        NodeValue: Clone + Eq + Hash,
+    {
+        self.rules.iter().cloned().fold(
+            AdjacencySolver2D::new(),
+            |solver, (value, neighbor, direction)| solver.with_rule(value, neighbor, direction),
+        )
+    }
+}
+
+/// Removes redundant and dead rules from the rule table of an [AdjacencySolver2D] for `tiles`.
+/// `cutoffs` are the [CutoffBehaviour]s the solver is used with, see
+/// [AdjacencySolver2D::with_cutoff]. Directions without a cutoff allow any value at the edge.
+///
+/// A rule is redundant if an earlier rule already allows the same pair, because
+/// [AdjacencySolver2D::with_rule] also adds the mirrored rule. A direction is constrained if any
+/// rule uses it or its opposite. A value is dead if there is a constrained direction in which
+/// no placeable value is allowed next to it and the cutoff of that direction does not allow it
+/// at the edge either. Removing a dead value can make other values dead, so this is repeated
+/// until no more values are removed. Values that are only placeable at the edge are reported,
+/// but keep their rules.
+///
+/// # Example
+/// ```no_run
+/// use wave_collapse::analysis::minimize_rules;
+/// use wave_collapse::tile2d::Direction;
+///
+/// let rules = [
+///     ("grass", "grass", Direction::Right),
+///     ("grass", "grass", Direction::Left),
+///     ("grass", "grass", Direction::Bottom),
+///     ("grass", "lava", Direction::Right),
+/// ];
+/// let report = minimize_rules(&rules, &["grass", "water"], &[]);
+/// // the mirrored grass rule is redundant and lava is not a tile
+/// assert_eq!(report.rules.len(), 2);
+/// // water has no neighbors, so it can only be placed at the edge
+/// assert_eq!(report.edge_only, ["water"]);
+/// let solver = report.to_solver();
+/// ```
+pub fn minimize_rules<NodeValue>(
+    rules: &[AdjacencyRule<NodeValue>],
+    tiles: &[NodeValue],
+    cutoffs: &[(Direction, CutoffBehaviour<NodeValue>)],
+) -> RuleReport<NodeValue>
+where
+    NodeValue: Clone + Eq + Hash,
+{
+    let mut unique = Vec::new();
+    let mut redundant = Vec::new();
+    let mut seen = HashSet::new();
+    for (value, neighbor, direction) in rules {
+        let mirrored = (neighbor, value, direction.opposite());
+        if seen.contains(&(value, neighbor, *direction)) || seen.contains(&mirrored) {
+            redundant.push((value.clone(), neighbor.clone(), *direction));
+        } else {
+            seen.insert((value, neighbor, *direction));
+            unique.push((value.clone(), neighbor.clone(), *direction));
+        }
+    }
+
+    // the values allowed next to each value in a direction, in both directions of each rule
+    let mut allowed: HashMap<(&NodeValue, Direction), HashSet<&NodeValue>> = HashMap::new();
+    let mut constrained = HashSet::new();
+    for (value, neighbor, direction) in &unique {
+        allowed
+            .entry((value, *direction))
+            .or_default()
+            .insert(neighbor);
+        allowed
+            .entry((neighbor, direction.opposite()))
+            .or_default()
+            .insert(value);
+        constrained.insert(*direction);
+        constrained.insert(direction.opposite());
+    }
+
+    // the last cutoff of a direction wins, like in AdjacencySolver2D::with_cutoff
+    let cutoffs: HashMap<Direction, &CutoffBehaviour<NodeValue>> = cutoffs
+        .iter()
+        .map(|(direction, cutoff)| (*direction, cutoff))
+        .collect();
+    let allowed_at_edge = |value: &NodeValue, direction: Direction| {
+        let Some(cutoff) = cutoffs.get(&direction) else {
+            return true;
+        };
+        cutoff.allows(value)
+    };
+    // the constrained directions in which no live value is allowed next to `value`
+    let missing_neighbors = |value: &NodeValue, live: &HashSet<&NodeValue>| {
+        constrained
+            .iter()
+            .copied()
+            .filter(|&direction| {
+                !allowed
+                    .get(&(value, direction))
+                    .is_some_and(|neighbors| neighbors.iter().any(|n| live.contains(n)))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut live: HashSet<&NodeValue> = tiles.iter().collect();
+    let mut dead_values = Vec::new();
+    loop {
+        let dead: Vec<&NodeValue> = tiles
+            .iter()
+            .filter(|value| live.contains(value))
+            .filter(|&value| {
+                missing_neighbors(value, &live)
+                    .into_iter()
+                    .any(|direction| !allowed_at_edge(value, direction))
+            })
+            .collect();
+        if dead.is_empty() {
+            break;
+        }
+        for value in dead {
+            live.remove(&value);
+            dead_values.push(value.clone());
+        }
+    }
+
+    let edge_only = tiles
+        .iter()
+        .filter(|value| live.contains(value) && !missing_neighbors(*value, &live).is_empty())
+        .cloned()
+        .collect();
+
+    let (rules, dead): (Vec<_>, Vec<_>) = unique
+        .into_iter()
+        .partition(|(value, neighbor, _)| live.contains(&value) && live.contains(&neighbor));
+
+    RuleReport {
+        rules,
+        redundant,
+        dead,
+        dead_values,
+        edge_only,
+    }
+}
+
+/// The result of [dedup_variants].
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantReport<NodeValue> {
+    /// the distinct variants in the order they first appear, each with the sum of the weights
+    /// of its duplicates.
+    pub variants: Vec<(NodeValue, f32)>,
+    /// the index into `variants` of each input variant, e.g. to remap rules or metadata that
+    /// were generated for each input variant.
+    pub mapping: Vec<usize>,
+}
+
+impl<NodeValue> VariantReport<NodeValue> {
+    /// returns the number of input variants that were merged into an earlier variant.
+    pub fn duplicates(&self) -> usize {
+        self.mapping.len() - self.variants.len()
+    }
+
+    /// returns the distinct variants without their weights.
+    pub fn values(&self) -> Vec<NodeValue>
+    where
+        NodeValue: Clone,
+    {
+        self.variants
+            .iter()
+            .map(|(value, _)| value.clone())
+            .collect()
+    }
+}
+
+/// Merges equal variants of weighted tiles, e.g. rotations or reflections of a symmetric tile
+/// that map onto the tile itself. The weights of equal variants are summed, so each distinct
+/// tile keeps the total weight of its variants, but the duplicates no longer skew the entropy
+/// or the number of candidates.
+///
+/// # Example
+/// ```no_run
+/// use wave_collapse::analysis::dedup_variants;
+/// use wave_collapse::tile2d::{Size2D, TileMap2D};
+///
+/// let tiles = [(0b0000_0011u8, 1.0), (0b0101_0101, 2.0)];
+/// // the second tile is symmetric, so all its rotations are equal
+/// let rotations = tiles
+///     .iter()
+///     .flat_map(|&(tile, weight)| (0..4).map(move |r| (tile.rotate_left(2 * r), weight)));
+/// let report = dedup_variants(rotations);
+/// assert_eq!(report.duplicates(), 3);
+/// let shape = TileMap2D::new_weighted(Size2D::square(10), Size2D::square(3), &report.variants);
+/// ```
+pub fn dedup_variants<NodeValue>(
+    variants: impl IntoIterator<Item = (NodeValue, f32)>,
+) -> VariantReport<NodeValue>
+where
+    NodeValue: Clone + Eq + Hash,
+{
+    let mut indices = HashMap::new();
+    let mut merged: Vec<(NodeValue, f32)> = Vec::new();
+    let mut mapping = Vec::new();
+    for (value, weight) in variants {
+        let index = *indices.entry(value.clone()).or_insert_with(|| {
+            merged.push((value, 0.0));
+            merged.len() - 1
+        });
+        merged[index].1 += weight;
+        mapping.push(index);
+    }
+
+    VariantReport {
+        variants: merged,
+        mapping,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::minimize_rules;
+    use crate::tile2d::{CutoffBehaviour, Direction};
+
+    const TILES: [&str; 3] = ["grass", "water", "shore"];
+
+    /// water is never above or below anything and shore is only right of water.
+    fn rules() -> Vec<(&'static str, &'static str, Direction)> {
+        vec![
+            ("grass", "grass", Direction::Right),
+            ("grass", "grass", Direction::Bottom),
+            ("grass", "water", Direction::Right),
+            ("water", "shore", Direction::Right),
+            ("shore", "shore", Direction::Bottom),
+            ("shore", "grass", Direction::Right),
+        ]
+    }
+
+    #[test]
+    fn removes_redundant_rules() {
+        let rules = [
+            ("grass", "grass", Direction::Right),
+            ("grass", "grass", Direction::Left),
+            ("grass", "grass", Direction::Right),
+        ];
+        let report = minimize_rules(&rules, &["grass"], &[]);
+
+        assert_eq!(report.rules, [("grass", "grass", Direction::Right)]);
+        assert_eq!(report.redundant, rules[1..]);
+        assert!(report.dead.is_empty());
+        assert!(report.dead_values.is_empty());
+        assert!(report.edge_only.is_empty());
+    }
+
+    #[test]
+    fn removes_rules_of_dead_values() {
+        let grass_only = || CutoffBehaviour::Only(HashSet::from(["grass"]));
+        let cutoffs = Direction::ALL.map(|direction| (direction, grass_only()));
+        let report = minimize_rules(&rules(), &TILES, &cutoffs);
+
+        // water has no neighbors above it, shore is only placeable right of water
+        assert_eq!(report.dead_values, ["water", "shore"]);
+        assert_eq!(report.rules, rules()[..2]);
+        assert_eq!(report.dead, rules()[2..]);
+        assert!(report.redundant.is_empty());
+        assert!(report.edge_only.is_empty());
+        assert!(!report.is_empty());
+    }
+
+    #[test]
+    fn keeps_rules_of_values_that_are_placeable_at_the_edge() {
+        let only_water = || CutoffBehaviour::Only(HashSet::from(["water"]));
+        for cutoffs in [
+            vec![],
+            vec![
+                (Direction::Top, only_water()),
+                (Direction::Bottom, only_water()),
+            ],
+        ] {
+            let report = minimize_rules(&rules(), &TILES, &cutoffs);
+
+            assert_eq!(report.edge_only, ["water"]);
+            assert!(report.dead_values.is_empty());
+            assert_eq!(report.rules, rules());
+            assert!(report.is_empty());
+            assert!(report
+                .to_solver()
+                .allows(&"grass", &"water", Direction::Right));
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! An async entry point, that lets other tasks run between collapse steps.
+//!
+//! This does not depend on a specific async runtime. The returned future is not [Send], because
+//! the shape is shared with [std::rc::Rc], so it has to be run on a single thread, e.g. with
+//! `tokio::task::spawn_local`.
+
+use std::{
+    fmt::Debug,
+    future::Future,
+    hash::Hash,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::error::{EngineError, Result};
+use crate::outcome::CollapseOutcome;
+use crate::rng::EntropySource;
+use crate::wave_function::{collapse_wave, WaveKernel, WaveShape, WaveSolver};
+
+/// A future that is pending exactly once, which gives the executor a chance to run other tasks.
+#[derive(Debug, Default)]
+pub struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// returns a future that yields to the executor once.
+pub fn yield_now() -> YieldNow {
+    YieldNow::default()
+}
+
+/// like [collapse_wave], but awaits a [yield_now] after each step, so long running collapses do
+/// not block other tasks on the same executor. Dropping the future cancels the collapse.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::wave_function::WaveSolver;
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Wrapping};
+/// use wave_collapse::async_collapse::collapse_wave_async;
+/// # struct TestSolver;
+/// # impl WaveSolver<u32, Kernel2D<Wrapping, u32>> for TestSolver {
+/// #     fn is_valid(&self, _value: &u32, _kernel: &Kernel2D<Wrapping, u32>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// # async fn generate() {
+/// let tiles: Vec<u32> = vec![0, 1, 2];
+/// let shape = TileMap2D::new(Size2D::square(100), Size2D::square(3), &tiles);
+/// let mut rng = rand::thread_rng();
+/// let result =
+///     collapse_wave_async::<_, _, _, Kernel2D<Wrapping, u32>, _>(shape, &TestSolver, &mut rng)
+///         .await;
+/// # }
+/// ```
+pub async fn collapse_wave_async<Shape, NodeId, NodeValue, Kernel, Solver>(
+    shape: Shape,
+    solver: &Solver,
+    rng: &mut impl EntropySource,
+) -> Result<CollapseOutcome<Shape>>
+where
+    NodeId: Copy + Eq + Hash + Debug,
+    NodeValue: Clone + PartialEq + Debug,
+    Shape: WaveShape<NodeId, NodeValue>,
+    Kernel: WaveKernel<NodeId, NodeValue, Shape>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+{
+    let mut result_iter = collapse_wave::<_, _, _, Kernel, _>(shape, solver, rng);
+
+    while Iterator::next(&mut &mut result_iter).is_some() {
+        yield_now().await;
+    }
+
+    match result_iter.return_or_self() {
+        Ok(result) => result,
+        Err(_) => Err(EngineError::IterationError.into()),
+    }
+}
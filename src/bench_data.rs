@@ -0,0 +1,118 @@
+//! Canonical tilesets, so that benchmarks use comparable workloads.
+//!
+//! All tilesets use [SocketTile]s and can be collapsed with the [SocketSolver].
+
+use crate::tile2d::Kernel2D;
+use crate::wave_function::WaveSolver;
+
+/// The offsets of the neighbors matching the socket indices of a [SocketTile].
+const OFFSETS: [(i64, i64); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+/// A tile with a socket on each edge. Two tiles can be placed next to each other if the sockets
+/// on the shared edge are the same.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SocketTile {
+    /// the sockets in the order right, bottom, left, top.
+    pub sockets: [u8; 4],
+}
+
+impl SocketTile {
+    pub const RIGHT: usize = 0;
+    pub const BOTTOM: usize = 1;
+    pub const LEFT: usize = 2;
+    pub const TOP: usize = 3;
+
+    pub fn new(right: u8, bottom: u8, left: u8, top: u8) -> Self {
+        SocketTile {
+            sockets: [right, bottom, left, top],
+        }
+    }
+}
+
+/// A [WaveSolver] for [SocketTile]s. Missing neighbors do not constrain a tile.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SocketSolver;
+
+impl<WrappingMode> WaveSolver<SocketTile, Kernel2D<WrappingMode, SocketTile>> for SocketSolver {
+    fn is_valid(&self, tile: &SocketTile, kernel: &Kernel2D<WrappingMode, SocketTile>) -> bool {
+        OFFSETS.iter().enumerate().all(|(side, offset)| {
+            match kernel.get(offset.0, offset.1).node() {
+                Some(node) => {
+                    node.any_value(|other| tile.sockets[side] == other.sockets[(side + 2) % 4])
+                }
+                None => true,
+            }
+        })
+    }
+}
+
+/// A pipe tileset with all 16 combinations of connected and unconnected edges.
+pub fn pipes() -> Vec<SocketTile> {
+    let mut tiles = Vec::new();
+    for right in 0..2 {
+        for bottom in 0..2 {
+            for left in 0..2 {
+                for top in 0..2 {
+                    tiles.push(SocketTile::new(right, bottom, left, top));
+                }
+            }
+        }
+    }
+    tiles
+}
+
+/// A 2-color Wang corner tileset with all 16 combinations of corner colors.
+///
+/// The socket of an edge encodes the colors of both of its corners.
+pub fn wang_2_color() -> Vec<SocketTile> {
+    let edge = |first: u8, second: u8| first * 2 + second;
+
+    let mut tiles = Vec::new();
+    for top_left in 0..2 {
+        for top_right in 0..2 {
+            for bottom_left in 0..2 {
+                for bottom_right in 0..2 {
+                    tiles.push(SocketTile::new(
+                        edge(top_right, bottom_right),
+                        edge(bottom_left, bottom_right),
+                        edge(top_left, bottom_left),
+                        edge(top_left, top_right),
+                    ));
+                }
+            }
+        }
+    }
+    tiles
+}
+
+/// A castle tileset using the sockets grass (0), wall (1) and road (2).
+///
+/// It contains grass, straight walls, wall corners, towers, roads, crossings and gates.
+pub fn castle() -> Vec<SocketTile> {
+    const GRASS: u8 = 0;
+    const WALL: u8 = 1;
+    const ROAD: u8 = 2;
+
+    vec![
+        // grass
+        SocketTile::new(GRASS, GRASS, GRASS, GRASS),
+        // straight walls
+        SocketTile::new(WALL, GRASS, WALL, GRASS),
+        SocketTile::new(GRASS, WALL, GRASS, WALL),
+        // wall corners
+        SocketTile::new(WALL, WALL, GRASS, GRASS),
+        SocketTile::new(GRASS, WALL, WALL, GRASS),
+        SocketTile::new(GRASS, GRASS, WALL, WALL),
+        SocketTile::new(WALL, GRASS, GRASS, WALL),
+        // tower
+        SocketTile::new(WALL, WALL, WALL, WALL),
+        // straight roads
+        SocketTile::new(ROAD, GRASS, ROAD, GRASS),
+        SocketTile::new(GRASS, ROAD, GRASS, ROAD),
+        // crossing
+        SocketTile::new(ROAD, ROAD, ROAD, ROAD),
+        // gates
+        SocketTile::new(WALL, ROAD, WALL, ROAD),
+        SocketTile::new(ROAD, WALL, ROAD, WALL),
+    ]
+}
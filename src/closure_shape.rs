@@ -0,0 +1,198 @@
+//! A [WaveShape] defined by closures, for one-off experiments with unusual topologies, e.g. rings,
+//! graphs or hexagonal grids, without implementing [WaveShape] and [WaveKernel] by hand.
+//!
+//! # Example
+//! ```no_run
+//! use wave_collapse::closure_shape::{ClosureKernel, ClosureShape};
+//! use wave_collapse::collapse_wave;
+//! use wave_collapse::wave_function::{WaveKernel, WaveSolver};
+//!
+//! /// neighbors on the ring must differ
+//! struct RingSolver;
+//! impl WaveSolver<char, ClosureKernel<u32, char>> for RingSolver {
+//!     fn is_valid(&self, value: &char, kernel: &ClosureKernel<u32, char>) -> bool {
+//!         kernel
+//!             .iter_directed()
+//!             .all(|(_, node)| node.any_value(|other| other != value))
+//!     }
+//! }
+//!
+//! // a ring of 12 nodes
+//! let shape = ClosureShape::new(0..12u32, &['a', 'b', 'c'], |id: &u32| {
+//!     vec![(id + 11) % 12, (id + 1) % 12]
+//! });
+//! let mut rng = rand::thread_rng();
+//! let result = collapse_wave::<_, _, _, ClosureKernel<u32, char>, _>(shape, &RingSolver, &mut rng);
+//! ```
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::rc::Rc;
+
+use crate::domain::Domain;
+use crate::node::{DirectedNodeIter, Node, NodeIdIter};
+use crate::wave_function::{ReadShape, WaveKernel, WaveShape};
+
+/// A shape made from a list of node ids and a closure returning the neighbors of a node. The
+/// nodes are visited in the order of the list.
+pub struct ClosureShape<NodeId, NodeValue: Clone> {
+    nodes: Vec<Node<NodeId, NodeValue>>,
+    indices: HashMap<NodeId, usize>,
+    neighbors: Box<dyn Fn(&NodeId) -> Vec<NodeId> + Send>,
+    last_collapsed: RefCell<Option<NodeId>>,
+}
+
+impl<NodeId, NodeValue> ClosureShape<NodeId, NodeValue>
+where
+    NodeId: Copy + Eq + Hash,
+    NodeValue: Clone,
+{
+    /// Creates a shape with a node for each id in `node_ids`. `neighbors` returns the ids of the
+    /// neighbors of a node, which are part of its [ClosureKernel]. Ids that are not part of the
+    /// shape are ignored. `possible_values` must not be empty and `node_ids` must not contain
+    /// duplicates. `neighbors` must be [Send], so the shape can be collapsed on another thread.
+    pub fn new(
+        node_ids: impl IntoIterator<Item = NodeId>,
+        possible_values: &[NodeValue],
+        neighbors: impl Fn(&NodeId) -> Vec<NodeId> + Send + 'static,
+    ) -> Self {
+        assert!(!possible_values.is_empty(), "At least one value required!");
+
+        let mut nodes = Vec::new();
+        let mut indices = HashMap::new();
+        for id in node_ids {
+            let previous = indices.insert(id, nodes.len());
+            assert!(previous.is_none(), "node ids must be unique");
+            nodes.push(Node::new(id, possible_values));
+        }
+
+        ClosureShape {
+            nodes,
+            indices,
+            neighbors: Box::new(neighbors),
+            last_collapsed: RefCell::new(None),
+        }
+    }
+
+    /// returns the ids of the neighbors of `id` that are part of this shape, in the order of the
+    /// neighbor closure.
+    pub fn neighbors(&self, id: &NodeId) -> Vec<NodeId> {
+        (self.neighbors)(id)
+            .into_iter()
+            .filter(|neighbor| self.indices.contains_key(neighbor))
+            .collect()
+    }
+}
+
+impl<NodeId, NodeValue> ReadShape<NodeId, NodeValue> for ClosureShape<NodeId, NodeValue>
+where
+    NodeId: Copy + Eq + Hash,
+    NodeValue: Clone,
+{
+    fn get_node(&self, id: &NodeId) -> Option<&Node<NodeId, NodeValue>> {
+        self.indices.get(id).map(|index| &self.nodes[*index])
+    }
+
+    fn iter_node_ids(&self) -> NodeIdIter<NodeId> {
+        let ids: Vec<_> = self.nodes.iter().map(|node| node.id).collect();
+        ids.into_iter()
+    }
+
+    fn get_last_collapsed_id(&self) -> Option<NodeId> {
+        *self.last_collapsed.borrow()
+    }
+}
+
+impl<NodeId, NodeValue> WaveShape<NodeId, NodeValue> for ClosureShape<NodeId, NodeValue>
+where
+    NodeId: Copy + Eq + Hash,
+    NodeValue: Clone,
+{
+    fn set_last_collapsed_id(&self, node_id: NodeId) {
+        let _ = self.last_collapsed.borrow_mut().insert(node_id);
+    }
+}
+
+/// A kernel containing a node of a [ClosureShape] and its neighbors. The
+/// [WaveKernel::Direction] of a neighbor is its index in the list returned by
+/// [ClosureShape::neighbors].
+pub struct ClosureKernel<NodeId, NodeValueDescription: Clone> {
+    shape: Rc<ClosureShape<NodeId, NodeValueDescription>>,
+    node_id: NodeId,
+    neighbors: Vec<NodeId>,
+}
+
+impl<NodeId, NodeValueDescription> ClosureKernel<NodeId, NodeValueDescription>
+where
+    NodeId: Copy + Eq + Hash,
+    NodeValueDescription: Clone,
+{
+    /// returns the ids of the neighbors of the center.
+    pub fn neighbor_ids(&self) -> &[NodeId] {
+        &self.neighbors
+    }
+
+    /// returns the neighbor at `index` in [ClosureKernel::neighbor_ids].
+    pub fn get(&self, index: usize) -> Option<&Node<NodeId, NodeValueDescription>> {
+        self.neighbors
+            .get(index)
+            .and_then(|id| self.shape.get_node(id))
+    }
+}
+
+impl<NodeId, NodeValueDescription>
+    WaveKernel<NodeId, NodeValueDescription, ClosureShape<NodeId, NodeValueDescription>>
+    for ClosureKernel<NodeId, NodeValueDescription>
+where
+    NodeId: Copy + Eq + Hash,
+    NodeValueDescription: Clone,
+{
+    type Direction = usize;
+
+    fn new(
+        shape: Rc<ClosureShape<NodeId, NodeValueDescription>>,
+        node: &Node<NodeId, NodeValueDescription>,
+    ) -> Self {
+        let neighbors = shape
+            .neighbors(&node.id)
+            .into_iter()
+            .filter(|id| *id != node.id)
+            .collect();
+        ClosureKernel {
+            shape,
+            node_id: node.id,
+            neighbors,
+        }
+    }
+
+    fn center_id(&self) -> NodeId {
+        self.node_id
+    }
+
+    fn center(&self) -> &Node<NodeId, NodeValueDescription> {
+        self.shape
+            .get_node(&self.node_id)
+            .expect("the kernel is created from a valid node")
+    }
+
+    fn iter_node_ids(&self) -> NodeIdIter<NodeId> {
+        let mut ids = vec![self.node_id];
+        ids.extend(self.neighbors.iter().copied());
+        ids.into_iter()
+    }
+
+    fn iter_node_ids_without_center(&self) -> NodeIdIter<NodeId> {
+        self.neighbors.clone().into_iter()
+    }
+
+    fn iter_directed(
+        &self,
+    ) -> DirectedNodeIter<'_, usize, NodeId, NodeValueDescription, Domain<NodeValueDescription>>
+    {
+        let nodes: Vec<_> = (0..self.neighbors.len())
+            .filter_map(|index| self.get(index).map(|node| (index, node)))
+            .collect();
+        nodes.into_iter()
+    }
+}
@@ -0,0 +1,8 @@
+//! Constraints for [crate::tile2d::TileMap2D]s, that go beyond the direct neighbors of a node.
+//!
+//! The constraints wrap a [crate::wave_function::WaveSolver] for [crate::tile2d::Kernel2D], so
+//! they can be combined with any existing solver.
+
+pub mod biome;
+pub mod counting;
+pub mod distance;
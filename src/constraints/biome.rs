@@ -0,0 +1,210 @@
+//! Drives the collapse with a coarse map of labels, e.g. biomes, that restrict the values of the
+//! nodes of each label and the transitions between labels.
+
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+use crate::tile2d::{Direction, Index2D, Kernel2D, Size2D, TileMap2D};
+use crate::wave_function::{ReadShape, WaveKernel, WaveSolver};
+
+/// A [WaveSolver] that enforces a map of labels on top of a solver for [Kernel2D]s.
+///
+/// Each node has a label. The values of a node are limited to the values allowed for its
+/// label, see [BiomeSolver::with_allowed]. Where nodes with different labels are orthogonal
+/// neighbors, only the transitions added with [BiomeSolver::with_transition] are allowed. Labels
+/// without allowed values and pairs of labels without transitions are not constrained.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Cutoff};
+/// # use wave_collapse::wave_function::WaveSolver;
+/// use wave_collapse::constraints::biome::BiomeSolver;
+/// # struct TileSolver;
+/// # impl WaveSolver<&'static str, Kernel2D<Cutoff, &'static str>> for TileSolver {
+/// #     fn is_valid(&self, _value: &&str, _kernel: &Kernel2D<Cutoff, &str>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+/// enum Biome {
+///     Forest,
+///     Desert,
+/// }
+///
+/// let size = Size2D::new(2, 1);
+/// let tiles = ["tree", "grass", "sand", "cactus"];
+/// let shape = TileMap2D::new(size, Size2D::square(3), &tiles);
+/// let solver = BiomeSolver::new(TileSolver, size, vec![Biome::Forest, Biome::Desert])
+///     .with_allowed(Biome::Forest, ["tree", "grass"])
+///     .with_allowed(Biome::Desert, ["sand", "cactus"])
+///     .with_transition(Biome::Forest, "grass", Biome::Desert, "sand");
+/// solver.restrict(&shape);
+/// ```
+#[derive(Debug, Clone)]
+pub struct BiomeSolver<Solver, Label, NodeValue> {
+    solver: Solver,
+    size: Size2D,
+    /// the label of each node, row by row.
+    labels: Vec<Label>,
+    allowed: HashMap<Label, HashSet<NodeValue>>,
+    /// the values allowed next to a value of the first label in the second label.
+    transitions: HashMap<(Label, Label), HashMap<NodeValue, HashSet<NodeValue>>>,
+}
+
+impl<Solver, Label, NodeValue> BiomeSolver<Solver, Label, NodeValue>
+where
+    Label: Copy + Eq + Hash,
+    NodeValue: Clone + Eq + Hash,
+{
+    /// Creates a [BiomeSolver] for a [TileMap2D] of `size`. `labels` contains the label of each
+    /// node, row by row starting at the top left.
+    pub fn new(solver: Solver, size: Size2D, labels: Vec<Label>) -> Self {
+        assert_eq!(
+            labels.len(),
+            (size.width * size.height) as usize,
+            "there must be a label for each node"
+        );
+
+        BiomeSolver {
+            solver,
+            size,
+            labels,
+            allowed: HashMap::new(),
+            transitions: HashMap::new(),
+        }
+    }
+
+    /// Limits the values of nodes with `label` to `values`.
+    pub fn with_allowed(
+        mut self,
+        label: Label,
+        values: impl IntoIterator<Item = NodeValue>,
+    ) -> Self {
+        self.allowed.entry(label).or_default().extend(values);
+        self
+    }
+
+    /// Allows `value` with `label` next to `neighbor` with `neighbor_label`, in any direction.
+    pub fn with_transition(
+        mut self,
+        label: Label,
+        value: NodeValue,
+        neighbor_label: Label,
+        neighbor: NodeValue,
+    ) -> Self {
+        self.transitions
+            .entry((label, neighbor_label))
+            .or_default()
+            .entry(value.clone())
+            .or_default()
+            .insert(neighbor.clone());
+        self.transitions
+            .entry((neighbor_label, label))
+            .or_default()
+            .entry(neighbor)
+            .or_default()
+            .insert(value);
+        self
+    }
+
+    /// returns the wrapped solver.
+    pub fn inner(&self) -> &Solver {
+        &self.solver
+    }
+
+    /// returns the label of the node `id` or [None] if `id` is outside of the map.
+    pub fn label(&self, id: Index2D) -> Option<Label> {
+        if id.0 >= self.size.width || id.1 >= self.size.height {
+            return None;
+        }
+        Some(self.labels[(id.1 * self.size.width + id.0) as usize])
+    }
+
+    /// returns `true` if `value` is allowed for nodes with `label`.
+    pub fn is_allowed(&self, label: Label, value: &NodeValue) -> bool {
+        match self.allowed.get(&label) {
+            Some(allowed) => allowed.contains(value),
+            None => true,
+        }
+    }
+
+    /// Removes all values that are not allowed for the label of each node of `shape`, so
+    /// that the collapse starts with the restricted domains. `shape` must not be collapsed yet.
+    pub fn restrict(&self, shape: &TileMap2D<NodeValue>) {
+        assert_eq!(*shape.size(), self.size, "shape must match the label map");
+
+        for node in shape.iter_nodes() {
+            let label = self
+                .label(node.id)
+                .expect("shape size matches the label map");
+            node.with_values_mut(|values| values.retain(0, |value| self.is_allowed(label, value)));
+        }
+    }
+
+    /// returns `true` if `value` with `label` is allowed next to one of the possible values of
+    /// the neighbor at `offset`.
+    fn is_transition_valid<WrappingMode>(
+        &self,
+        value: &NodeValue,
+        label: Label,
+        kernel: &Kernel2D<WrappingMode, NodeValue>,
+        (x, y): (i64, i64),
+    ) -> bool {
+        let Some(neighbor) = kernel.get(x, y).node() else {
+            return true;
+        };
+        let Some(neighbor_label) = self.label(neighbor.id) else {
+            return true;
+        };
+        if neighbor_label == label {
+            return true;
+        }
+        let Some(transitions) = self.transitions.get(&(label, neighbor_label)) else {
+            return true;
+        };
+
+        let allowed = transitions.get(value);
+        neighbor.any_value(|other| allowed.is_some_and(|allowed| allowed.contains(other)))
+    }
+}
+
+impl<Solver, WrappingMode, Label, NodeValue>
+    WaveSolver<NodeValue, Kernel2D<WrappingMode, NodeValue>>
+    for BiomeSolver<Solver, Label, NodeValue>
+where
+    Solver: WaveSolver<NodeValue, Kernel2D<WrappingMode, NodeValue>>,
+    Kernel2D<WrappingMode, NodeValue>: WaveKernel<Index2D, NodeValue, TileMap2D<NodeValue>>,
+    Label: Copy + Eq + Hash,
+    NodeValue: Clone + Eq + Hash,
+{
+    fn is_valid(&self, value: &NodeValue, kernel: &Kernel2D<WrappingMode, NodeValue>) -> bool {
+        let Some(label) = self.label(kernel.center_id()) else {
+            return self.solver.is_valid(value, kernel);
+        };
+
+        self.is_allowed(label, value)
+            && self.solver.is_valid(value, kernel)
+            && Direction::ORTHOGONAL.iter().all(|direction| {
+                let (x, y) = direction.offset();
+                !kernel.is_changed(x, y) || self.is_transition_valid(value, label, kernel, (x, y))
+            })
+    }
+
+    fn kernel_radius(&self) -> Option<u32> {
+        self.solver.kernel_radius()
+    }
+
+    fn kernel_radii(&self) -> Option<Vec<u32>> {
+        self.solver.kernel_radii()
+    }
+
+    fn prepare<NodeId, Shape>(&mut self, shape: &Shape)
+    where
+        NodeValue: Clone,
+        Shape: ReadShape<NodeId, NodeValue> + ?Sized,
+    {
+        self.solver.prepare::<NodeId, Shape>(shape);
+    }
+}
@@ -0,0 +1,281 @@
+//! Constraints on the number of times a value appears in each row or column, e.g. "each row
+//! contains at most 2 doors" or "each column contains at least one tree".
+
+use std::{collections::HashSet, rc::Rc};
+
+use crate::domain::Domain;
+use crate::node::{DirectedNodeIter, Node, NodeIdIter};
+use crate::tile2d::{Index2D, Kernel2D, TileMap2D};
+use crate::wave_function::{ReadShape, WaveKernel, WaveSolver};
+
+/// A row or column of a [TileMap2D].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Line {
+    Row,
+    Column,
+}
+
+/// Limits how often `value` appears in each [Line] of a kind.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CountConstraint<NodeValue> {
+    pub line: Line,
+    pub value: NodeValue,
+    /// the minimum number of times `value` appears in each line.
+    pub min: usize,
+    /// the maximum number of times `value` appears in each line.
+    pub max: usize,
+}
+
+impl<NodeValue: PartialEq> CountConstraint<NodeValue> {
+    /// each `line` contains `value` at most `max` times.
+    pub fn at_most(line: Line, value: NodeValue, max: usize) -> Self {
+        CountConstraint {
+            line,
+            value,
+            min: 0,
+            max,
+        }
+    }
+
+    /// each `line` contains `value` at least `min` times.
+    pub fn at_least(line: Line, value: NodeValue, min: usize) -> Self {
+        CountConstraint {
+            line,
+            value,
+            min,
+            max: usize::MAX,
+        }
+    }
+
+    /// returns `true` if the center of `kernel` can be `value` without violating this
+    /// constraint in its line.
+    fn allows<WrappingMode>(
+        &self,
+        value: &NodeValue,
+        kernel: &LineKernel2D<WrappingMode, NodeValue>,
+    ) -> bool
+    where
+        NodeValue: Clone,
+    {
+        if *value == self.value {
+            // nodes that can only be `value`
+            let certain = kernel
+                .line(self.line)
+                .filter(|node| {
+                    let values = node.possible_values();
+                    values.len() == 1 && values[0] == *value
+                })
+                .count();
+            certain < self.max
+        } else {
+            // nodes that can still be `value`
+            let possible = kernel
+                .line(self.line)
+                .filter(|node| node.contains(&self.value))
+                .count();
+            possible >= self.min
+        }
+    }
+}
+
+/// A [WaveKernel] that contains the row and column of its center on top of the nodes of a
+/// [Kernel2D], so that changes are propagated along the lines for [CountingSolver]s.
+pub struct LineKernel2D<WrappingMode, NodeValue: Clone> {
+    kernel: Kernel2D<WrappingMode, NodeValue>,
+    tile_map: Rc<TileMap2D<NodeValue>>,
+    center: Index2D,
+}
+
+impl<WrappingMode, NodeValue: Clone> LineKernel2D<WrappingMode, NodeValue> {
+    /// returns the wrapped [Kernel2D].
+    pub fn kernel(&self) -> &Kernel2D<WrappingMode, NodeValue> {
+        &self.kernel
+    }
+
+    /// returns an [Iterator] over the nodes in `line` of the center, without the center.
+    pub fn line(&self, line: Line) -> impl Iterator<Item = &Node<Index2D, NodeValue>> {
+        self.line_ids(line)
+            .filter_map(|id| self.tile_map.get_node(&id))
+    }
+
+    fn line_ids(&self, line: Line) -> impl Iterator<Item = Index2D> {
+        let size = *self.tile_map.size();
+        let (x, y) = self.center;
+        let ids: Vec<_> = match line {
+            Line::Row => (0..size.width)
+                .filter(|other| *other != x)
+                .map(|other| (other, y))
+                .collect(),
+            Line::Column => (0..size.height)
+                .filter(|other| *other != y)
+                .map(|other| (x, other))
+                .collect(),
+        };
+        ids.into_iter()
+    }
+}
+
+impl<WrappingMode, NodeValue> WaveKernel<Index2D, NodeValue, TileMap2D<NodeValue>>
+    for LineKernel2D<WrappingMode, NodeValue>
+where
+    NodeValue: Clone,
+    Kernel2D<WrappingMode, NodeValue>:
+        WaveKernel<Index2D, NodeValue, TileMap2D<NodeValue>, Direction = (i64, i64)>,
+{
+    type Direction = (i64, i64);
+
+    fn new(shape: Rc<TileMap2D<NodeValue>>, node: &Node<Index2D, NodeValue>) -> Self {
+        LineKernel2D {
+            kernel: <Kernel2D<WrappingMode, NodeValue> as WaveKernel<_, _, _>>::new(
+                shape.clone(),
+                node,
+            ),
+            tile_map: shape,
+            center: node.id,
+        }
+    }
+
+    fn center_id(&self) -> Index2D {
+        self.center
+    }
+
+    fn center(&self) -> &Node<Index2D, NodeValue> {
+        self.tile_map
+            .get_node(&self.center)
+            .expect("the kernel is created from a valid node")
+    }
+
+    fn iter_node_ids(&self) -> NodeIdIter<Index2D> {
+        let mut ids: Vec<_> = self.kernel.iter_node_ids().collect();
+        let mut contained: HashSet<_> = ids.iter().copied().collect();
+        for id in self.line_ids(Line::Row).chain(self.line_ids(Line::Column)) {
+            if contained.insert(id) {
+                ids.push(id);
+            }
+        }
+        ids.into_iter()
+    }
+
+    fn iter_directed(
+        &self,
+    ) -> DirectedNodeIter<'_, (i64, i64), Index2D, NodeValue, Domain<NodeValue>> {
+        let mut nodes: Vec<_> = self.kernel.iter_directed().collect();
+        let mut contained: HashSet<_> = nodes.iter().map(|(_, node)| node.id).collect();
+        contained.insert(self.center);
+        for id in self.line_ids(Line::Row).chain(self.line_ids(Line::Column)) {
+            if !contained.insert(id) {
+                continue;
+            }
+            if let Some(node) = self.tile_map.get_node(&id) {
+                let offset = (
+                    id.0 as i64 - self.center.0 as i64,
+                    id.1 as i64 - self.center.1 as i64,
+                );
+                nodes.push((offset, node));
+            }
+        }
+        nodes.into_iter()
+    }
+
+    fn set_changed_ids(&mut self, changed: Option<Vec<Index2D>>) {
+        self.kernel.set_changed_ids(changed);
+    }
+
+    fn set_radius(&mut self, radius: u32) {
+        self.kernel.set_radius(radius);
+    }
+
+    fn set_radii(&mut self, radii: &[u32]) {
+        self.kernel.set_radii(radii);
+    }
+}
+
+/// A [WaveSolver] that enforces [CountConstraint]s on top of a solver for [Kernel2D]s.
+///
+/// The counts are calculated from the possible values of the nodes in the line, whenever a
+/// value is validated. A value is rejected if the line already contains `max` nodes that can
+/// only be that value, or if it would leave fewer than `min` nodes that can still be the value
+/// of a constraint.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Cutoff};
+/// # use wave_collapse::wave_function::WaveSolver;
+/// use wave_collapse::collapse_fully;
+/// use wave_collapse::constraints::counting::{CountConstraint, CountingSolver, Line, LineKernel2D};
+/// use wave_collapse::wave_function::CollapseConfig;
+/// # struct TileSolver;
+/// # impl WaveSolver<u32, Kernel2D<Cutoff, u32>> for TileSolver {
+/// #     fn is_valid(&self, _value: &u32, _kernel: &Kernel2D<Cutoff, u32>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// const DOOR: u32 = 2;
+///
+/// let tiles: Vec<u32> = vec![0, 1, DOOR];
+/// let shape = TileMap2D::new(Size2D::square(20), Size2D::square(3), &tiles);
+/// let solver = CountingSolver::new(TileSolver)
+///     .with_constraint(CountConstraint::at_most(Line::Row, DOOR, 2))
+///     .with_constraint(CountConstraint::at_least(Line::Column, DOOR, 1));
+/// let config = CollapseConfig::new(rand::thread_rng());
+/// let outcome = collapse_fully::<_, _, _, LineKernel2D<Cutoff, u32>, _, _>(shape, &solver, config);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CountingSolver<Solver, NodeValue> {
+    solver: Solver,
+    constraints: Vec<CountConstraint<NodeValue>>,
+}
+
+impl<Solver, NodeValue> CountingSolver<Solver, NodeValue> {
+    pub fn new(solver: Solver) -> Self {
+        CountingSolver {
+            solver,
+            constraints: Vec::new(),
+        }
+    }
+
+    pub fn with_constraint(mut self, constraint: CountConstraint<NodeValue>) -> Self {
+        self.constraints.push(constraint);
+        self
+    }
+
+    /// returns the wrapped solver.
+    pub fn inner(&self) -> &Solver {
+        &self.solver
+    }
+
+    pub fn constraints(&self) -> &[CountConstraint<NodeValue>] {
+        &self.constraints
+    }
+}
+
+impl<Solver, WrappingMode, NodeValue> WaveSolver<NodeValue, LineKernel2D<WrappingMode, NodeValue>>
+    for CountingSolver<Solver, NodeValue>
+where
+    Solver: WaveSolver<NodeValue, Kernel2D<WrappingMode, NodeValue>>,
+    NodeValue: Clone + PartialEq,
+{
+    fn is_valid(&self, value: &NodeValue, kernel: &LineKernel2D<WrappingMode, NodeValue>) -> bool {
+        self.solver.is_valid(value, kernel.kernel())
+            && self
+                .constraints
+                .iter()
+                .all(|constraint| constraint.allows(value, kernel))
+    }
+
+    fn kernel_radius(&self) -> Option<u32> {
+        self.solver.kernel_radius()
+    }
+
+    fn kernel_radii(&self) -> Option<Vec<u32>> {
+        self.solver.kernel_radii()
+    }
+
+    fn prepare<NodeId, Shape>(&mut self, shape: &Shape)
+    where
+        NodeValue: Clone,
+        Shape: ReadShape<NodeId, NodeValue> + ?Sized,
+    {
+        self.solver.prepare::<NodeId, Shape>(shape);
+    }
+}
@@ -0,0 +1,221 @@
+//! Constraints on the distance between values, e.g. "every node is within 3 nodes of water" or
+//! "no two villages are within 5 nodes of each other".
+
+use std::{collections::HashSet, hash::Hash};
+
+use crate::tile2d::Kernel2D;
+use crate::wave_function::{ReadShape, WaveSolver};
+
+/// How the distance between two nodes is measured.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Metric {
+    /// the maximum of the distances along each axis, so the nodes within a distance form a
+    /// square.
+    #[default]
+    Chebyshev,
+    /// the sum of the distances along each axis, so the nodes within a distance form a diamond.
+    Manhattan,
+}
+
+impl Metric {
+    /// returns the distance of the offset `(x, y)` from the center.
+    pub fn distance(&self, x: i64, y: i64) -> u32 {
+        match self {
+            Metric::Chebyshev => x.unsigned_abs().max(y.unsigned_abs()) as u32,
+            Metric::Manhattan => (x.unsigned_abs() + y.unsigned_abs()) as u32,
+        }
+    }
+}
+
+/// A constraint on the distance to nodes with a value in `class`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DistanceConstraint<NodeValue: Eq + Hash> {
+    /// every node is at most `distance` away from a node with a value in `class`.
+    Within {
+        class: HashSet<NodeValue>,
+        distance: u32,
+    },
+    /// nodes with a value in `class` are more than `distance` apart.
+    Apart {
+        class: HashSet<NodeValue>,
+        distance: u32,
+    },
+}
+
+impl<NodeValue: Clone + Eq + Hash> DistanceConstraint<NodeValue> {
+    /// returns the largest distance that is checked by this constraint.
+    pub fn distance(&self) -> u32 {
+        match self {
+            DistanceConstraint::Within { distance, .. } => *distance,
+            DistanceConstraint::Apart { distance, .. } => *distance,
+        }
+    }
+
+    /// returns `true` if the center of `kernel` can be `value` without violating this
+    /// constraint.
+    fn allows<WrappingMode>(
+        &self,
+        value: &NodeValue,
+        kernel: &Kernel2D<WrappingMode, NodeValue>,
+        metric: Metric,
+    ) -> bool {
+        match self {
+            DistanceConstraint::Within { class, distance } => {
+                // a node that can still be part of the class is close enough
+                class.contains(value)
+                    || Self::any_within(kernel, metric, *distance, |values| {
+                        values.iter().any(|other| class.contains(other))
+                    })
+            }
+            DistanceConstraint::Apart { class, distance } => {
+                // only nodes that are certainly part of the class are too close
+                !class.contains(value)
+                    || !Self::any_within(kernel, metric, *distance, |values| {
+                        !values.is_empty() && values.iter().all(|other| class.contains(other))
+                    })
+            }
+        }
+    }
+
+    /// returns `true` if `f` returns `true` for the possible values of any node within
+    /// `distance` of the center of `kernel`, not counting the center itself.
+    fn any_within<WrappingMode>(
+        kernel: &Kernel2D<WrappingMode, NodeValue>,
+        metric: Metric,
+        distance: u32,
+        f: impl Fn(&[NodeValue]) -> bool,
+    ) -> bool {
+        let radius = distance as i64;
+        (-radius..=radius)
+            .flat_map(|y| (-radius..=radius).map(move |x| (x, y)))
+            .filter(|&(x, y)| (x, y) != (0, 0) && metric.distance(x, y) <= distance)
+            .filter_map(|(x, y)| kernel.get(x, y).node())
+            .any(|node| f(&node.possible_values()))
+    }
+}
+
+/// A [WaveSolver] that enforces [DistanceConstraint]s on top of a solver for [Kernel2D]s.
+///
+/// The distances are checked against the possible values of the nodes in the kernel, so the
+/// kernel must contain all nodes within the largest distance of a constraint, e.g. a kernel
+/// size of at least 7 and [crate::tile2d::Neighborhood::Full] for a distance of 3.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Cutoff};
+/// # use wave_collapse::wave_function::WaveSolver;
+/// use std::collections::HashSet;
+/// use wave_collapse::constraints::distance::{DistanceConstraint, DistanceSolver, Metric};
+/// # struct TileSolver;
+/// # impl WaveSolver<u32, Kernel2D<Cutoff, u32>> for TileSolver {
+/// #     fn is_valid(&self, _value: &u32, _kernel: &Kernel2D<Cutoff, u32>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// const WATER: u32 = 0;
+/// const VILLAGE: u32 = 3;
+///
+/// let tiles: Vec<u32> = vec![WATER, 1, 2, VILLAGE];
+/// let shape = TileMap2D::new(Size2D::square(50), Size2D::square(11), &tiles);
+/// let solver = DistanceSolver::new(TileSolver)
+///     .with_metric(Metric::Manhattan)
+///     .with_constraint(DistanceConstraint::Within {
+///         class: HashSet::from([WATER]),
+///         distance: 4,
+///     })
+///     .with_constraint(DistanceConstraint::Apart {
+///         class: HashSet::from([VILLAGE]),
+///         distance: 5,
+///     });
+/// ```
+#[derive(Debug, Clone)]
+pub struct DistanceSolver<Solver, NodeValue: Eq + Hash> {
+    solver: Solver,
+    metric: Metric,
+    constraints: Vec<DistanceConstraint<NodeValue>>,
+}
+
+impl<Solver, NodeValue: Eq + Hash> DistanceSolver<Solver, NodeValue> {
+    pub fn new(solver: Solver) -> Self {
+        DistanceSolver {
+            solver,
+            metric: Metric::default(),
+            constraints: Vec::new(),
+        }
+    }
+
+    pub fn with_metric(mut self, metric: Metric) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    pub fn with_constraint(mut self, constraint: DistanceConstraint<NodeValue>) -> Self {
+        self.constraints.push(constraint);
+        self
+    }
+
+    /// returns the wrapped solver.
+    pub fn inner(&self) -> &Solver {
+        &self.solver
+    }
+
+    /// returns the largest distance of the constraints.
+    fn max_distance(&self) -> u32 {
+        self.constraints
+            .iter()
+            .map(|constraint| constraint.distance())
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+impl<Solver, WrappingMode, NodeValue> WaveSolver<NodeValue, Kernel2D<WrappingMode, NodeValue>>
+    for DistanceSolver<Solver, NodeValue>
+where
+    Solver: WaveSolver<NodeValue, Kernel2D<WrappingMode, NodeValue>>,
+    NodeValue: Clone + Eq + Hash,
+{
+    fn is_valid(&self, value: &NodeValue, kernel: &Kernel2D<WrappingMode, NodeValue>) -> bool {
+        debug_assert!(
+            self.constraints.iter().all(|constraint| {
+                let distance = constraint.distance() as i64;
+                kernel.radius_x >= distance && kernel.radius_y >= distance
+            }),
+            "the kernel must contain all nodes within the distance of each constraint"
+        );
+
+        self.solver.is_valid(value, kernel)
+            && self
+                .constraints
+                .iter()
+                .all(|constraint| constraint.allows(value, kernel, self.metric))
+    }
+
+    /// the radius of the wrapped solver, increased to the largest distance of the constraints.
+    /// [None] if the wrapped solver uses the kernel size of the shape.
+    fn kernel_radius(&self) -> Option<u32> {
+        let distance = self.max_distance();
+        self.solver
+            .kernel_radius()
+            .map(|radius| radius.max(distance))
+    }
+
+    /// like `kernel_radius`, but for each axis.
+    fn kernel_radii(&self) -> Option<Vec<u32>> {
+        let distance = self.max_distance();
+        self.solver.kernel_radii().map(|radii| {
+            // a single radius applies to both axes
+            let x = radii.first().copied().unwrap_or_default();
+            let y = radii.get(1).copied().unwrap_or(x);
+            vec![x.max(distance), y.max(distance)]
+        })
+    }
+
+    fn prepare<NodeId, Shape>(&mut self, shape: &Shape)
+    where
+        NodeValue: Clone,
+        Shape: ReadShape<NodeId, NodeValue> + ?Sized,
+    {
+        self.solver.prepare::<NodeId, Shape>(shape);
+    }
+}
@@ -0,0 +1,306 @@
+//! A cube-sphere shape, that maps six square grids onto the faces of a cube. This can be used to
+//! generate planet surfaces without seams or distortions at the poles.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::domain::Domain;
+use crate::node::{DirectedNodeIter, Node, NodeIdIter};
+use crate::wave_function::{ReadShape, WaveKernel, WaveShape};
+
+/// The offsets of the orthogonal neighbors within a [CubeKernel].
+const OFFSETS: [(i64, i64); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+/// The [WaveKernel::Direction] of a neighbor in a [CubeKernel].
+///
+/// The local frames of two faces are rotated against each other, so at the seams of the cube the
+/// direction back to the center is not the inverse of `offset`. Rules that are keyed by a
+/// direction should use both offsets, so the rule for the center seen from the neighbor is the
+/// one with `offset` and `back` swapped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CubeDirection {
+    /// the offset of the neighbor in the local frame of the center.
+    pub offset: (i64, i64),
+    /// the offset of the center in the local frame of the neighbor.
+    pub back: (i64, i64),
+}
+
+impl CubeDirection {
+    /// returns the direction from the neighbor back to the center.
+    pub fn reverse(&self) -> Self {
+        CubeDirection {
+            offset: self.back,
+            back: self.offset,
+        }
+    }
+}
+
+/// The id of a node in a [CubeSphere].
+///
+/// Face `f` is perpendicular to the axis `f % 3` and lies on the positive side of the cube for
+/// `f < 3`. `x` runs along the axis `(f % 3 + 1) % 3` and `y` along `(f % 3 + 2) % 3`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CubeIndex {
+    pub face: u8,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl CubeIndex {
+    pub fn new(face: u8, x: u32, y: u32) -> Self {
+        CubeIndex { face, x, y }
+    }
+
+    fn axes(&self) -> (usize, usize, usize) {
+        let normal = (self.face % 3) as usize;
+        (normal, (normal + 1) % 3, (normal + 2) % 3)
+    }
+
+    fn is_positive(&self) -> bool {
+        self.face < 3
+    }
+
+    /// The center of the node in a coordinate system where the cube spans from 0 to
+    /// `2 * face_size` along each axis.
+    fn to_doubled(self, face_size: u32) -> [i64; 3] {
+        let (normal, u, v) = self.axes();
+        let mut coords = [0; 3];
+        coords[normal] = if self.is_positive() {
+            2 * face_size as i64
+        } else {
+            0
+        };
+        coords[u] = 2 * self.x as i64 + 1;
+        coords[v] = 2 * self.y as i64 + 1;
+        coords
+    }
+
+    fn from_doubled(coords: [i64; 3], face_size: u32) -> Self {
+        let max = 2 * face_size as i64;
+        let normal = coords
+            .iter()
+            .position(|c| *c == 0 || *c == max)
+            .expect("node centers lie on exactly one face");
+        let face = if coords[normal] == max {
+            normal as u8
+        } else {
+            normal as u8 + 3
+        };
+        CubeIndex {
+            face,
+            x: ((coords[(normal + 1) % 3] - 1) / 2) as u32,
+            y: ((coords[(normal + 2) % 3] - 1) / 2) as u32,
+        }
+    }
+
+    /// returns the neighbor in the direction `(dx, dy)` of the local frame of this node and the
+    /// direction pointing back to this node in the local frame of the neighbor.
+    /// `(dx, dy)` must be an orthogonal offset of length 1.
+    fn step(self, (dx, dy): (i64, i64), face_size: u32) -> (CubeIndex, (i64, i64)) {
+        assert!(
+            OFFSETS.contains(&(dx, dy)),
+            "only orthogonal steps are supported"
+        );
+
+        let (normal, u, v) = self.axes();
+        let (axis, sign) = if dx != 0 { (u, dx) } else { (v, dy) };
+        let max = 2 * face_size as i64;
+
+        let mut coords = self.to_doubled(face_size);
+        coords[axis] += 2 * sign;
+
+        if coords[axis] > 0 && coords[axis] < max {
+            return (CubeIndex::from_doubled(coords, face_size), (-dx, -dy));
+        }
+
+        // we stepped over the edge of the face, onto the face perpendicular to `axis`
+        coords[axis] = if sign > 0 { max } else { 0 };
+        let back = if self.is_positive() { 1 } else { -1 };
+        coords[normal] -= back;
+
+        let neighbor = CubeIndex::from_doubled(coords, face_size);
+        let (_, neighbor_u, _) = neighbor.axes();
+        let back_direction = if normal == neighbor_u {
+            (back, 0)
+        } else {
+            (0, back)
+        };
+
+        (neighbor, back_direction)
+    }
+}
+
+/// A shape made from six square faces of `face_size * face_size` nodes stitched together
+/// to form a cube.
+pub struct CubeSphere<NodeValue: Clone> {
+    face_size: u32,
+
+    last_collapsed: RefCell<Option<CubeIndex>>,
+
+    nodes: Vec<Node<CubeIndex, NodeValue>>,
+}
+
+impl<NodeValue: Clone> CubeSphere<NodeValue> {
+    /// Create a new [CubeSphere]. `possible_values` must not be empty.
+    pub fn new(face_size: u32, possible_values: &[NodeValue]) -> Self {
+        assert!(face_size > 0, "Face size must not be 0");
+        assert!(!possible_values.is_empty(), "At least one value required!");
+
+        let mut nodes = Vec::new();
+        for face in 0..6 {
+            for y in 0..face_size {
+                for x in 0..face_size {
+                    nodes.push(Node::new(CubeIndex::new(face, x, y), possible_values));
+                }
+            }
+        }
+
+        CubeSphere {
+            face_size,
+            last_collapsed: RefCell::new(None),
+            nodes,
+        }
+    }
+
+    pub fn face_size(&self) -> u32 {
+        self.face_size
+    }
+
+    /// returns the neighbor of `id` in the direction `offset` of the local frame of `id` and the
+    /// direction pointing back to `id` in the local frame of the neighbor.
+    ///
+    /// The local frames of two faces are rotated against each other, so the direction back is not
+    /// always the inverse of `offset`.
+    pub fn neighbor(&self, id: CubeIndex, offset: (i64, i64)) -> (CubeIndex, (i64, i64)) {
+        id.step(offset, self.face_size)
+    }
+}
+
+impl<NodeValue> ReadShape<CubeIndex, NodeValue> for CubeSphere<NodeValue>
+where
+    NodeValue: Clone,
+{
+    fn get_node(&self, id: &CubeIndex) -> Option<&Node<CubeIndex, NodeValue>> {
+        if id.face >= 6 || id.x >= self.face_size || id.y >= self.face_size {
+            return None;
+        }
+        let face_len = (self.face_size * self.face_size) as usize;
+        let index = id.face as usize * face_len + (id.y * self.face_size) as usize + id.x as usize;
+        self.nodes.get(index)
+    }
+
+    fn iter_node_ids(&self) -> NodeIdIter<CubeIndex> {
+        let ids: Vec<_> = self.nodes.iter().map(|node| node.id).collect();
+        ids.into_iter()
+    }
+
+    fn get_last_collapsed_id(&self) -> Option<CubeIndex> {
+        *self.last_collapsed.borrow()
+    }
+}
+
+impl<NodeValue> WaveShape<CubeIndex, NodeValue> for CubeSphere<NodeValue>
+where
+    NodeValue: Clone,
+{
+    fn set_last_collapsed_id(&self, node_id: CubeIndex) {
+        let _ = self.last_collapsed.borrow_mut().insert(node_id);
+    }
+}
+
+/// A kernel containing a node of a [CubeSphere] and its 4 orthogonal neighbors, including
+/// neighbors on adjacent faces.
+pub struct CubeKernel<NodeValueDescription: Clone> {
+    shape: Rc<CubeSphere<NodeValueDescription>>,
+    node_id: CubeIndex,
+}
+
+impl<NodeValueDescription: Clone> CubeKernel<NodeValueDescription> {
+    /// returns the node at `(dx, dy)` in the local frame of the center node. Only the center and
+    /// orthogonal offsets of length 1 are part of the kernel.
+    pub fn get(&self, dx: i64, dy: i64) -> Option<&Node<CubeIndex, NodeValueDescription>> {
+        self.get_with_direction(dx, dy).map(|(node, _)| node)
+    }
+
+    /// like [CubeKernel::get], but also returns the direction pointing back to the center
+    /// in the local frame of the returned node.
+    pub fn get_with_direction(
+        &self,
+        dx: i64,
+        dy: i64,
+    ) -> Option<(&Node<CubeIndex, NodeValueDescription>, (i64, i64))> {
+        if dx == 0 && dy == 0 {
+            return self
+                .shape
+                .get_node(&self.node_id)
+                .map(|node| (node, (0, 0)));
+        }
+        if !OFFSETS.contains(&(dx, dy)) {
+            return None;
+        }
+
+        let (id, back) = self.shape.neighbor(self.node_id, (dx, dy));
+        self.shape.get_node(&id).map(|node| (node, back))
+    }
+}
+
+impl<NodeValueDescription: Clone>
+    WaveKernel<CubeIndex, NodeValueDescription, CubeSphere<NodeValueDescription>>
+    for CubeKernel<NodeValueDescription>
+{
+    /// the offset of the side in the local frame of the center node and the offset back.
+    type Direction = CubeDirection;
+
+    fn new(
+        shape: Rc<CubeSphere<NodeValueDescription>>,
+        node: &Node<CubeIndex, NodeValueDescription>,
+    ) -> Self {
+        CubeKernel {
+            shape,
+            node_id: node.id,
+        }
+    }
+
+    fn center_id(&self) -> CubeIndex {
+        self.node_id
+    }
+
+    fn center(&self) -> &Node<CubeIndex, NodeValueDescription> {
+        self.shape
+            .get_node(&self.node_id)
+            .expect("the kernel is created from a valid node")
+    }
+
+    fn iter_node_ids(&self) -> NodeIdIter<CubeIndex> {
+        let mut ids = vec![self.node_id];
+        ids.extend(self.iter_node_ids_without_center());
+        ids.into_iter()
+    }
+
+    fn iter_node_ids_without_center(&self) -> NodeIdIter<CubeIndex> {
+        let ids: Vec<_> = OFFSETS
+            .iter()
+            .map(|offset| self.shape.neighbor(self.node_id, *offset).0)
+            .collect();
+        ids.into_iter()
+    }
+
+    fn iter_directed(
+        &self,
+    ) -> DirectedNodeIter<
+        '_,
+        CubeDirection,
+        CubeIndex,
+        NodeValueDescription,
+        Domain<NodeValueDescription>,
+    > {
+        let nodes: Vec<_> = OFFSETS
+            .iter()
+            .filter_map(|&offset| {
+                self.get_with_direction(offset.0, offset.1)
+                    .map(|(node, back)| (CubeDirection { offset, back }, node))
+            })
+            .collect();
+        nodes.into_iter()
+    }
+}
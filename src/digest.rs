@@ -0,0 +1,28 @@
+use std::hash::Hasher;
+
+/// A [Hasher] implementing 64 bit FNV-1a.
+///
+/// Unlike [std::collections::hash_map::DefaultHasher] the algorithm is fixed, so digests stay
+/// the same across rust versions. Digests of values whose [Hash] implementation writes
+/// integers still depend on the endianness of the platform.
+#[derive(Debug, Clone, Copy)]
+pub struct Fnv1aHasher(u64);
+
+impl Default for Fnv1aHasher {
+    fn default() -> Self {
+        Fnv1aHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1aHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
@@ -0,0 +1,330 @@
+//! Storage for the possible values of a [crate::node::Node].
+//!
+//! [Domain] is used by default. Other representations can be used by implementing
+//! [DomainStorage] and setting [crate::wave_function::ReadShape::Storage].
+
+use std::mem::size_of;
+
+/// Stores the possible values of a node.
+///
+/// The possible values must be available as a slice, so that solvers can inspect them. How
+/// removed values are kept, if at all, is up to the implementation.
+pub trait DomainStorage<Value>: Clone {
+    /// Creates a storage where all `values` are possible.
+    fn from_values(values: Vec<Value>) -> Self;
+
+    /// returns the possible values.
+    fn as_slice(&self) -> &[Value];
+
+    /// returns the number of possible values.
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    /// returns `true` if there are no possible values left.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all values for which `f` returns `false` in `generation`.
+    /// returns the number of removed values.
+    fn retain<F: FnMut(&Value) -> bool>(&mut self, generation: u32, f: F) -> usize;
+
+    /// Removes all values except the one at `index` in `generation`.
+    fn collapse(&mut self, generation: u32, index: usize);
+
+    /// Restores all values that were removed in `generation` or later.
+    /// returns the number of restored values. Storages that do not keep removed values return 0.
+    fn restore(&mut self, generation: u32) -> usize;
+
+    /// Adds `value` to the possible values, unless it is already possible.
+    /// returns `true` if the value was added.
+    fn insert(&mut self, value: Value) -> bool
+    where
+        Value: PartialEq;
+
+    /// returns an estimate of the heap memory in bytes used by this storage. Heap memory owned
+    /// by the values themselves is not included. The default implementation only counts the
+    /// possible values.
+    fn heap_size(&self) -> usize {
+        self.len() * size_of::<Value>()
+    }
+}
+
+/// The possible values of a node.
+///
+/// Removed values are not dropped, instead they are swapped behind the possible values and
+/// stamped with the generation they were removed in. That way removing a value does not move
+/// any other values and all values removed since a generation can be restored with
+/// [Domain::restore], e.g. for backtracking.
+/// Removing values changes the order of the remaining values.
+#[derive(Clone, Debug)]
+pub struct Domain<Value> {
+    /// the possible values, followed by the removed values. More recently removed values come
+    /// first.
+    values: Vec<Value>,
+    /// the generation each value in `values` was removed in. Only valid for removed values.
+    removed_at: Vec<u32>,
+    /// the number of possible values.
+    len: usize,
+}
+
+impl<Value> Domain<Value> {
+    pub fn new(values: Vec<Value>) -> Self {
+        Domain {
+            removed_at: vec![0; values.len()],
+            len: values.len(),
+            values,
+        }
+    }
+
+    /// returns the possible values.
+    pub fn as_slice(&self) -> &[Value] {
+        &self.values[..self.len]
+    }
+
+    /// returns the number of possible values.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// returns `true` if there are no possible values left.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Removes all values for which `f` returns `false` and stamps them with `generation`.
+    /// `generation` must not be smaller than the generation of any previous removal.
+    /// returns the number of removed values.
+    pub fn retain(&mut self, generation: u32, mut f: impl FnMut(&Value) -> bool) -> usize {
+        let len_before = self.len;
+        // iterate backwards, so that the value swapped into `index` was already checked
+        for index in (0..self.len).rev() {
+            if !f(&self.values[index]) {
+                self.remove(index, generation);
+            }
+        }
+        len_before - self.len
+    }
+
+    /// Removes all values except the one at `index` and stamps them with `generation`.
+    /// The remaining value is moved to the front.
+    pub fn collapse(&mut self, generation: u32, index: usize) {
+        assert!(index < self.len, "index must be a possible value");
+
+        self.values.swap(0, index);
+        for removed in &mut self.removed_at[1..self.len] {
+            *removed = generation;
+        }
+        self.len = 1;
+    }
+
+    /// Restores all values that were removed in `generation` or later.
+    /// returns the number of restored values.
+    pub fn restore(&mut self, generation: u32) -> usize {
+        let len_before = self.len;
+        while self.len < self.values.len() && self.removed_at[self.len] >= generation {
+            self.len += 1;
+        }
+        self.len - len_before
+    }
+
+    /// Adds `value` to the possible values, unless it is already possible. A removed value that
+    /// is equal to `value` is moved back instead of adding a duplicate.
+    /// This changes the order of the removed values, so a later [Domain::restore] might restore
+    /// fewer values than were removed since its generation.
+    /// returns `true` if the value was added.
+    pub fn insert(&mut self, value: Value) -> bool
+    where
+        Value: PartialEq,
+    {
+        if self.as_slice().contains(&value) {
+            return false;
+        }
+
+        let index = match self.values[self.len..].iter().position(|v| *v == value) {
+            Some(offset) => self.len + offset,
+            None => {
+                self.values.push(value);
+                self.removed_at.push(0);
+                self.values.len() - 1
+            }
+        };
+        self.values.swap(self.len, index);
+        self.removed_at.swap(self.len, index);
+        self.len += 1;
+        true
+    }
+
+    fn remove(&mut self, index: usize, generation: u32) {
+        debug_assert!(
+            self.len == self.values.len() || self.removed_at[self.len] <= generation,
+            "generations must not decrease"
+        );
+
+        self.len -= 1;
+        self.values.swap(index, self.len);
+        self.removed_at.swap(index, self.len);
+        self.removed_at[self.len] = generation;
+    }
+}
+
+impl<Value: Clone> DomainStorage<Value> for Domain<Value> {
+    fn from_values(values: Vec<Value>) -> Self {
+        Domain::new(values)
+    }
+
+    fn as_slice(&self) -> &[Value] {
+        Domain::as_slice(self)
+    }
+
+    fn len(&self) -> usize {
+        Domain::len(self)
+    }
+
+    fn retain<F: FnMut(&Value) -> bool>(&mut self, generation: u32, f: F) -> usize {
+        Domain::retain(self, generation, f)
+    }
+
+    fn collapse(&mut self, generation: u32, index: usize) {
+        Domain::collapse(self, generation, index)
+    }
+
+    fn restore(&mut self, generation: u32) -> usize {
+        Domain::restore(self, generation)
+    }
+
+    fn insert(&mut self, value: Value) -> bool
+    where
+        Value: PartialEq,
+    {
+        Domain::insert(self, value)
+    }
+
+    /// Removed values are kept, so this does not shrink during the collapse.
+    fn heap_size(&self) -> usize {
+        self.values.capacity() * size_of::<Value>() + self.removed_at.capacity() * size_of::<u32>()
+    }
+}
+
+/// A storage that drops removed values. The order of the possible values never changes, but
+/// removed values can not be restored.
+impl<Value: Clone> DomainStorage<Value> for Vec<Value> {
+    fn from_values(values: Vec<Value>) -> Self {
+        values
+    }
+
+    fn as_slice(&self) -> &[Value] {
+        self
+    }
+
+    fn retain<F: FnMut(&Value) -> bool>(&mut self, _generation: u32, f: F) -> usize {
+        let len_before = self.len();
+        Vec::retain(self, f);
+        len_before - self.len()
+    }
+
+    fn collapse(&mut self, _generation: u32, index: usize) {
+        self.swap(0, index);
+        self.truncate(1);
+    }
+
+    fn restore(&mut self, _generation: u32) -> usize {
+        0
+    }
+
+    fn insert(&mut self, value: Value) -> bool
+    where
+        Value: PartialEq,
+    {
+        if self.contains(&value) {
+            return false;
+        }
+        self.push(value);
+        true
+    }
+
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<Value>()
+    }
+}
+
+impl<Value> From<Vec<Value>> for Domain<Value> {
+    fn from(values: Vec<Value>) -> Self {
+        Domain::new(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Domain;
+
+    fn sorted(domain: &Domain<char>) -> Vec<char> {
+        let mut values = domain.as_slice().to_vec();
+        values.sort();
+        values
+    }
+
+    #[test]
+    fn restore_restores_all_later_generations() {
+        let mut domain = Domain::new(vec!['a', 'b', 'c', 'd', 'e']);
+        assert_eq!(domain.retain(1, |value| *value != 'a'), 1);
+        assert_eq!(domain.retain(2, |value| !matches!(value, 'b' | 'c')), 2);
+        assert_eq!(domain.retain(3, |value| *value != 'd'), 1);
+        assert_eq!(sorted(&domain), ['e']);
+
+        assert_eq!(domain.restore(2), 3);
+        assert_eq!(sorted(&domain), ['b', 'c', 'd', 'e']);
+
+        assert_eq!(domain.restore(2), 0);
+        assert_eq!(domain.restore(1), 1);
+        assert_eq!(sorted(&domain), ['a', 'b', 'c', 'd', 'e']);
+    }
+
+    #[test]
+    fn restore_after_nested_removals_only_restores_the_newer_ones() {
+        let mut domain = Domain::new(vec!['a', 'b', 'c', 'd']);
+        domain.retain(1, |value| *value != 'a');
+        domain.retain(2, |value| *value != 'b');
+        domain.retain(3, |value| *value != 'c');
+
+        assert_eq!(domain.restore(3), 1);
+        assert_eq!(sorted(&domain), ['c', 'd']);
+
+        // removals after a restore can be restored again
+        domain.retain(3, |value| *value != 'd');
+        assert_eq!(sorted(&domain), ['c']);
+        assert_eq!(domain.restore(2), 2);
+        assert_eq!(sorted(&domain), ['b', 'c', 'd']);
+    }
+
+    #[test]
+    fn restore_undoes_a_collapse() {
+        let mut domain = Domain::new(vec!['a', 'b', 'c', 'd']);
+        domain.retain(1, |value| *value != 'a');
+        let index = domain
+            .as_slice()
+            .iter()
+            .position(|value| *value == 'c')
+            .unwrap();
+        domain.collapse(2, index);
+        assert_eq!(domain.as_slice(), ['c']);
+
+        assert_eq!(domain.restore(2), 2);
+        assert_eq!(sorted(&domain), ['b', 'c', 'd']);
+        assert_eq!(domain.restore(1), 1);
+        assert_eq!(sorted(&domain), ['a', 'b', 'c', 'd']);
+    }
+
+    #[test]
+    fn restore_after_collapse_of_a_restored_domain() {
+        let mut domain = Domain::new(vec!['a', 'b', 'c']);
+        domain.collapse(1, 2);
+        domain.restore(1);
+        domain.collapse(1, 0);
+        assert_eq!(domain.len(), 1);
+
+        assert_eq!(domain.restore(1), 2);
+        assert_eq!(sorted(&domain), ['a', 'b', 'c']);
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt::{self, Display, Formatter};
+
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, WaveCollapseError>;
@@ -15,10 +17,126 @@ where
     }
 }
 
+/// The error returned by the collapse. Recoverable contradictions and programming errors are
+/// kept apart, so that retry logic can match [WaveCollapseError::Propagation] without swallowing
+/// bugs. Both carry an [ErrorContext] that describes where the error occurred.
 #[derive(Error, Debug)]
 pub enum WaveCollapseError {
+    #[error("{0}{1}")]
+    Propagation(PropagationError, ErrorContext),
+    #[error("{0}{1}")]
+    Engine(EngineError, ErrorContext),
+}
+
+impl WaveCollapseError {
+    /// returns `true` if the collapse might succeed when it is restarted or backtracked.
+    pub fn is_recoverable(&self) -> bool {
+        matches!(self, WaveCollapseError::Propagation(..))
+    }
+
+    /// returns the [ErrorContext] of this error.
+    pub fn context(&self) -> &ErrorContext {
+        match self {
+            WaveCollapseError::Propagation(_, context) | WaveCollapseError::Engine(_, context) => {
+                context
+            }
+        }
+    }
+
+    /// Replaces the [ErrorContext] of this error.
+    pub fn with_context(mut self, context: ErrorContext) -> Self {
+        match &mut self {
+            WaveCollapseError::Propagation(_, old) | WaveCollapseError::Engine(_, old) => {
+                *old = context
+            }
+        }
+        self
+    }
+
+    /// Sets the step of the [ErrorContext] of this error.
+    pub fn at_step(self, step: u32) -> Self {
+        let context = ErrorContext {
+            step: Some(step),
+            ..self.context().clone()
+        };
+        self.with_context(context)
+    }
+}
+
+impl From<PropagationError> for WaveCollapseError {
+    fn from(error: PropagationError) -> Self {
+        WaveCollapseError::Propagation(error, ErrorContext::default())
+    }
+}
+
+impl From<EngineError> for WaveCollapseError {
+    fn from(error: EngineError) -> Self {
+        WaveCollapseError::Engine(error, ErrorContext::default())
+    }
+}
+
+/// Describes where a [WaveCollapseError] occurred. The collapse fills in as much as it knows.
+/// Node ids and values are formatted with [Debug], so the error does not depend on the types
+/// of the shape.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// the step of the collapse, which is the number of nodes collapsed before the error.
+    pub step: Option<u32>,
+    /// the id of the node that caused the error, e.g. the node that was left without values.
+    pub node_id: Option<String>,
+    /// for [PropagationError::InvalidSuperposition]: the neighbors of the node and their
+    /// possible values when the node was left without values.
+    pub constraints: Vec<String>,
+}
+
+impl ErrorContext {
+    /// returns `true` if nothing is known about where the error occurred.
+    pub fn is_empty(&self) -> bool {
+        self.step.is_none() && self.node_id.is_none() && self.constraints.is_empty()
+    }
+}
+
+impl Display for ErrorContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        let mut parts = Vec::new();
+        if let Some(step) = self.step {
+            parts.push(format!("step {step}"));
+        }
+        if let Some(node_id) = &self.node_id {
+            parts.push(format!("node {node_id}"));
+        }
+        if !self.constraints.is_empty() {
+            parts.push(format!("neighbors {}", self.constraints.join(", ")));
+        }
+        write!(f, " ({})", parts.join(", "))
+    }
+}
+
+/// A contradiction discovered while collapsing. This depends on the random choices, so it can be
+/// recovered from by restarting or backtracking.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropagationError {
     #[error("failed to collapse wave function")]
     InvalidSuperposition,
+}
+
+/// The pins passed to [crate::wave_function::pin_all] that can not be applied together.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("pins {pins:?} are inconsistent")]
+pub struct PinConflict {
+    /// the indices of a minimal set of inconsistent pins. Without any one of them the others
+    /// are consistent. A pin that can not be applied on its own is the only entry.
+    pub pins: Vec<usize>,
+}
+
+/// An error caused by invalid input or a bug in a shape, kernel or this crate. Restarting the
+/// collapse will fail the same way.
+#[derive(Error, Debug)]
+pub enum EngineError {
     #[error("unknown error")]
     Other,
     #[error("not implemented")]
@@ -27,4 +145,18 @@ pub enum WaveCollapseError {
     EmptyInput,
     #[error("iteration failed, this should never happen")]
     IterationError,
+    #[error("node id {0} can not be resolved by the shape")]
+    UnresolvableNodeId(String),
+    #[error("node id {0} is returned more than once by the shape")]
+    DuplicateNodeId(String),
+    #[error("kernel of node {center} contains node id {id} that does not belong to the shape")]
+    InvalidKernelNodeId { center: String, id: String },
+    #[error("node {0} is not collapsed")]
+    NotCollapsed(String),
+    #[error("shape has no nodes")]
+    EmptyShape,
+    #[error("node {0} has no possible values")]
+    EmptyDomain(String),
+    #[error("kernel of size {kernel} is larger than the shape of size {shape}")]
+    KernelLargerThanShape { kernel: String, shape: String },
 }
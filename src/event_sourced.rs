@@ -0,0 +1,232 @@
+//! Versioned change tracking of a shape, for engines that detect changes by version numbers
+//! instead of comparing the content of each node every frame, e.g. ECS change detection.
+//!
+//! An [EventSourcedShape] wraps the shape of a collapse, see [crate::collapse_wave]. Each change
+//! of the domain of a node is recorded as a [DomainEvent] with an increasing version when it
+//! happens, see [WaveShape::record_change]. Consumers remember the last version they have seen
+//! and only apply the [EventSourcedShape::events_since] that version.
+
+use std::{
+    cell::{Cell, Ref, RefCell},
+    collections::HashMap,
+    fmt::Debug,
+    hash::Hash,
+};
+
+use crate::error::Result;
+use crate::node::{Node, NodeIdIter};
+use crate::rng::EntropySource;
+use crate::wave_function::{Pruning, ReadShape, WaveShape};
+
+/// A change of the domain of a node, see [WaveShape::record_change].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DomainEvent<NodeId, NodeValue> {
+    /// the version of this change. Each event has a higher version than all previous events.
+    pub version: u64,
+    /// the step of the collapse the change was made in. Removed values are stamped with it, so
+    /// they can be restored with [Node::restore].
+    pub step: u32,
+    pub node_id: NodeId,
+    /// the possible values of the node after the change.
+    pub values: Vec<NodeValue>,
+    /// `true` if the node is collapsed after the change.
+    pub collapsed: bool,
+}
+
+/// A [WaveShape] that records the changes of the shape it wraps as [DomainEvent]s.
+///
+/// The changes are recorded by the collapse when they are made, so a node that changed
+/// multiple times in one step is recorded multiple times. Only the changed nodes are stored,
+/// besides the events.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Cutoff};
+/// # use wave_collapse::wave_function::WaveSolver;
+/// use wave_collapse::collapse_wave;
+/// use wave_collapse::event_sourced::EventSourcedShape;
+/// # struct TestSolver;
+/// # impl WaveSolver<u32, Kernel2D<Cutoff, u32>> for TestSolver {
+/// #     fn is_valid(&self, _value: &u32, _kernel: &Kernel2D<Cutoff, u32>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// let shape = TileMap2D::new(Size2D::square(50), Size2D::square(3), &[0u32, 1, 2]);
+/// let mut rng = rand::thread_rng();
+/// let mut collapse = collapse_wave::<_, _, _, Kernel2D<Cutoff, u32>, _>(
+///     EventSourcedShape::new(shape),
+///     &TestSolver,
+///     &mut rng,
+/// );
+///
+/// let mut seen = 0;
+/// while let Some(shape) = Iterator::next(&mut &mut collapse) {
+///     for event in shape.events_since(seen).iter() {
+///         println!("{:?} changed to {:?}", event.node_id, event.values);
+///     }
+///     seen = shape.version();
+/// }
+/// ```
+pub struct EventSourcedShape<NodeId, NodeValue, Shape> {
+    shape: Shape,
+    /// the version of the last change of each changed node.
+    versions: RefCell<HashMap<NodeId, u64>>,
+    events: RefCell<Vec<DomainEvent<NodeId, NodeValue>>>,
+    version: Cell<u64>,
+}
+
+impl<NodeId, NodeValue, Shape> EventSourcedShape<NodeId, NodeValue, Shape>
+where
+    NodeId: Copy + Eq + Hash,
+    NodeValue: Clone,
+    Shape: ReadShape<NodeId, NodeValue>,
+{
+    /// Creates a tracker for `shape`, at version 0. The current state of `shape` is not
+    /// recorded as events.
+    pub fn new(shape: Shape) -> Self {
+        EventSourcedShape {
+            shape,
+            versions: RefCell::new(HashMap::new()),
+            events: RefCell::new(Vec::new()),
+            version: Cell::new(0),
+        }
+    }
+
+    /// returns the wrapped shape.
+    pub fn shape(&self) -> &Shape {
+        &self.shape
+    }
+
+    /// returns the wrapped shape. The events are dropped.
+    pub fn into_inner(self) -> Shape {
+        self.shape
+    }
+
+    /// returns the version of the last recorded event, or 0 if there is none.
+    pub fn version(&self) -> u64 {
+        self.version.get()
+    }
+
+    /// returns the version of the last recorded change of the node `id`, 0 if it did not change
+    /// since the tracker was created, or [None] if the node is not part of the shape.
+    pub fn node_version(&self, id: &NodeId) -> Option<u64> {
+        self.shape.get_node(id)?;
+        Some(self.versions.borrow().get(id).copied().unwrap_or_default())
+    }
+
+    /// returns all recorded events with a version higher than `version`, oldest first.
+    ///
+    /// # Panics
+    /// if a change is recorded while the events are borrowed.
+    pub fn events_since(&self, version: u64) -> Ref<'_, [DomainEvent<NodeId, NodeValue>]> {
+        Ref::map(self.events.borrow(), |events| {
+            let start = events.partition_point(|event| event.version <= version);
+            &events[start..]
+        })
+    }
+
+    /// Drops all events up to and including `version`, e.g. once all consumers have seen them,
+    /// so the memory of a long collapse stays bounded. The versions of the nodes are kept.
+    pub fn discard_until(&self, version: u64) {
+        let mut events = self.events.borrow_mut();
+        let end = events.partition_point(|event| event.version <= version);
+        events.drain(..end);
+    }
+}
+
+impl<NodeId, NodeValue, Shape> ReadShape<NodeId, NodeValue>
+    for EventSourcedShape<NodeId, NodeValue, Shape>
+where
+    NodeValue: Clone,
+    Shape: ReadShape<NodeId, NodeValue>,
+{
+    type Storage = Shape::Storage;
+
+    fn iter_node_ids(&self) -> NodeIdIter<NodeId> {
+        self.shape.iter_node_ids()
+    }
+
+    fn get_node(&self, id: &NodeId) -> Option<&Node<NodeId, NodeValue, Self::Storage>> {
+        self.shape.get_node(id)
+    }
+
+    fn is_collapsed(&self) -> bool {
+        self.shape.is_collapsed()
+    }
+
+    fn is_overspecified(&self) -> bool {
+        self.shape.is_overspecified()
+    }
+
+    fn is_weighted(&self) -> bool {
+        self.shape.is_weighted()
+    }
+
+    fn value_weight(&self, value: &NodeValue) -> f32 {
+        self.shape.value_weight(value)
+    }
+
+    fn node_value_weight(&self, id: &NodeId, value: &NodeValue) -> f32 {
+        self.shape.node_value_weight(id, value)
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.shape.memory_usage()
+    }
+
+    fn get_last_collapsed_id(&self) -> Option<NodeId> {
+        self.shape.get_last_collapsed_id()
+    }
+
+    fn validate(&self) -> Result<()>
+    where
+        NodeId: Debug,
+    {
+        self.shape.validate()
+    }
+}
+
+impl<NodeId, NodeValue, Shape> WaveShape<NodeId, NodeValue>
+    for EventSourcedShape<NodeId, NodeValue, Shape>
+where
+    NodeId: Copy + Eq + Hash,
+    NodeValue: Clone,
+    Shape: WaveShape<NodeId, NodeValue>,
+{
+    fn choose_random_with_lowest_entropy_where(
+        &self,
+        rng: &mut impl EntropySource,
+        filter: impl Fn(&Node<NodeId, NodeValue, Self::Storage>) -> bool,
+    ) -> Option<&Node<NodeId, NodeValue, Self::Storage>> {
+        self.shape
+            .choose_random_with_lowest_entropy_where(rng, filter)
+    }
+
+    fn set_last_collapsed_id(&self, node_id: NodeId) {
+        self.shape.set_last_collapsed_id(node_id)
+    }
+
+    fn is_pruning_traced(&self, node_id: &NodeId) -> bool {
+        self.shape.is_pruning_traced(node_id)
+    }
+
+    fn record_pruning(&self, node_id: NodeId, pruning: Pruning<NodeId, NodeValue>) {
+        self.shape.record_pruning(node_id, pruning)
+    }
+
+    fn record_change(&self, node_id: NodeId, step: u32) {
+        if let Some(node) = self.shape.get_node(&node_id) {
+            let version = self.version.get() + 1;
+            self.version.set(version);
+            self.versions.borrow_mut().insert(node_id, version);
+            self.events.borrow_mut().push(DomainEvent {
+                version,
+                step,
+                node_id,
+                values: node.possible_values().to_vec(),
+                collapsed: node.is_collapsed(),
+            });
+        }
+        self.shape.record_change(node_id, step)
+    }
+}
@@ -0,0 +1,230 @@
+//! Sends the progress of a collapse into a channel, so a rendering thread can follow the
+//! generation running on a worker thread, without touching the shape concurrently, or reports
+//! each placed node to a callback, see [collapse_with_placement].
+
+use std::{
+    fmt::Debug,
+    hash::Hash,
+    sync::mpsc::{Sender, SyncSender},
+};
+
+use crate::error::{EngineError, Result};
+use crate::outcome::{CollapseOutcome, CollapseStats};
+use crate::rng::EntropySource;
+use crate::wave_function::{collapse_wave, WaveKernel, WaveShape, WaveSolver};
+
+/// A change of the shape during a collapse, see [collapse_with_events].
+#[derive(Debug, Clone, PartialEq)]
+pub enum CollapseEvent<NodeId, NodeValue> {
+    /// the node `node_id` was collapsed to `value` in `step`.
+    Collapsed {
+        step: u32,
+        node_id: NodeId,
+        value: NodeValue,
+    },
+    /// the propagation of `step` removed values from the node `node_id`, which is left with
+    /// `values`.
+    Narrowed {
+        step: u32,
+        node_id: NodeId,
+        values: Vec<NodeValue>,
+    },
+    /// the collapse finished successfully.
+    Finished(CollapseStats),
+    /// the collapse failed with the contained error message.
+    Failed(String),
+}
+
+/// The sending half of a channel for [CollapseEvent]s.
+///
+/// A [Sender] never blocks, so a slow receiver can fall behind by any number of steps. A
+/// [SyncSender] of [std::sync::mpsc::sync_channel] blocks while its buffer is full, which pauses
+/// the collapse until the receiver catches up. Each step sends at least one event, so with a
+/// buffer of `n` events the collapse is at most `n` steps ahead of the receiver and the memory
+/// used by pending events stays bounded.
+pub trait EventSender<Event> {
+    /// sends `event`, blocking if the channel is full. returns `false` if the receiver was
+    /// dropped.
+    fn send_event(&self, event: Event) -> bool;
+}
+
+impl<Event> EventSender<Event> for Sender<Event> {
+    fn send_event(&self, event: Event) -> bool {
+        self.send(event).is_ok()
+    }
+}
+
+impl<Event> EventSender<Event> for SyncSender<Event> {
+    fn send_event(&self, event: Event) -> bool {
+        self.send(event).is_ok()
+    }
+}
+
+/// like [crate::collapse_fully], but each change of the shape is sent to `events`. The events
+/// of a step are sent after the step is finished, the collapsed node first. The last event is
+/// either [CollapseEvent::Finished] or [CollapseEvent::Failed].
+///
+/// The collapse continues if the receiver is dropped. Use a [SyncSender] to pause the collapse
+/// while the receiver lags behind, see [EventSender].
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Cutoff};
+/// # use wave_collapse::wave_function::WaveSolver;
+/// use std::{sync::mpsc, thread};
+/// use wave_collapse::events::{collapse_with_events, CollapseEvent};
+/// # struct TestSolver;
+/// # impl WaveSolver<u32, Kernel2D<Cutoff, u32>> for TestSolver {
+/// #     fn is_valid(&self, _value: &u32, _kernel: &Kernel2D<Cutoff, u32>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// // the collapse pauses while 256 events are pending
+/// let (sender, receiver) = mpsc::sync_channel(256);
+/// let worker = thread::spawn(move || {
+///     let shape = TileMap2D::new(Size2D::square(50), Size2D::square(3), &[0u32, 1, 2]);
+///     let mut rng = rand::thread_rng();
+///     let _ = collapse_with_events::<_, _, _, Kernel2D<Cutoff, u32>, _>(
+///         shape,
+///         &TestSolver,
+///         &mut rng,
+///         sender,
+///     );
+/// });
+///
+/// for event in receiver {
+///     match event {
+///         CollapseEvent::Collapsed { node_id, value, .. } => println!("{node_id:?}: {value}"),
+///         CollapseEvent::Narrowed { .. } => {}
+///         CollapseEvent::Finished(stats) => println!("done after {} steps", stats.iterations),
+///         CollapseEvent::Failed(error) => eprintln!("{error}"),
+///     }
+/// }
+/// worker.join().unwrap();
+/// ```
+pub fn collapse_with_events<Shape, NodeId, NodeValue, Kernel, Solver>(
+    shape: Shape,
+    solver: &Solver,
+    rng: &mut impl EntropySource,
+    events: impl EventSender<CollapseEvent<NodeId, NodeValue>>,
+) -> Result<CollapseOutcome<Shape>>
+where
+    NodeId: Copy + Eq + Hash + Debug,
+    NodeValue: Clone + PartialEq + Debug,
+    Shape: WaveShape<NodeId, NodeValue>,
+    Kernel: WaveKernel<NodeId, NodeValue, Shape>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+{
+    // the entropy and collapsed state of each node after the previous step. Values are only
+    // removed during the collapse, so any node with a lower entropy changed.
+    let mut previous: Vec<(u32, bool)> = shape
+        .iter_nodes()
+        .map(|node| (node.entropy(), node.is_collapsed()))
+        .collect();
+
+    let mut collapse = collapse_wave::<_, _, _, Kernel, _>(shape, solver, rng);
+    let mut step = 0;
+    while let Some(shape) = Iterator::next(&mut &mut collapse) {
+        let mut narrowed = Vec::new();
+        for (node, previous) in shape.iter_nodes().zip(&mut previous) {
+            let current = (node.entropy(), node.is_collapsed());
+            if current == *previous {
+                continue;
+            }
+            *previous = current;
+
+            // only the chosen node is marked as collapsed, propagation only removes values
+            if let Some(value) = node.collapsed() {
+                events.send_event(CollapseEvent::Collapsed {
+                    step,
+                    node_id: node.id,
+                    value,
+                });
+            } else {
+                narrowed.push(CollapseEvent::Narrowed {
+                    step,
+                    node_id: node.id,
+                    values: node.possible_values().to_vec(),
+                });
+            }
+        }
+        for event in narrowed {
+            events.send_event(event);
+        }
+        step += 1;
+    }
+
+    let result = match collapse.return_or_self() {
+        Ok(result) => result,
+        Err(_) => Err(EngineError::IterationError.into()),
+    };
+    events.send_event(match &result {
+        Ok(outcome) => CollapseEvent::Finished(outcome.stats),
+        Err(error) => CollapseEvent::Failed(error.to_string()),
+    });
+    result
+}
+
+/// like [crate::collapse_fully], but `on_placed` is called with the id and value of each node
+/// as soon as it is collapsed, e.g. to build meshes or spawn props while the rest of the map is
+/// still collapsing. Collapsed values never change, but a later contradiction can still fail the
+/// collapse, so the placed nodes are only final if [Ok] is returned.
+///
+/// The collapsed node of each step is taken from
+/// [crate::wave_function::ReadShape::get_last_collapsed_id], so `shape` must implement it.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Cutoff};
+/// # use wave_collapse::wave_function::WaveSolver;
+/// use wave_collapse::events::collapse_with_placement;
+/// # struct TestSolver;
+/// # impl WaveSolver<char, Kernel2D<Cutoff, char>> for TestSolver {
+/// #     fn is_valid(&self, _value: &char, _kernel: &Kernel2D<Cutoff, char>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// let shape = TileMap2D::new(Size2D::square(50), Size2D::square(3), &['.', 'T']);
+/// let mut rng = rand::thread_rng();
+/// let result = collapse_with_placement::<_, _, _, Kernel2D<Cutoff, char>, _>(
+///     shape,
+///     &TestSolver,
+///     &mut rng,
+///     |(x, y), tile| {
+///         if *tile == 'T' {
+///             println!("spawn tree at {x}, {y}");
+///         }
+///     },
+/// );
+/// ```
+pub fn collapse_with_placement<Shape, NodeId, NodeValue, Kernel, Solver>(
+    shape: Shape,
+    solver: &Solver,
+    rng: &mut impl EntropySource,
+    mut on_placed: impl FnMut(NodeId, &NodeValue),
+) -> Result<CollapseOutcome<Shape>>
+where
+    NodeId: Copy + Eq + Hash + Debug,
+    NodeValue: Clone + PartialEq + Debug,
+    Shape: WaveShape<NodeId, NodeValue>,
+    Kernel: WaveKernel<NodeId, NodeValue, Shape>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+{
+    let mut collapse = collapse_wave::<_, _, _, Kernel, _>(shape, solver, rng);
+    while let Some(shape) = Iterator::next(&mut &mut collapse) {
+        let Some(node) = shape
+            .get_last_collapsed_id()
+            .and_then(|id| shape.get_node(&id))
+        else {
+            continue;
+        };
+        if let Some(value) = node.collapsed_ref() {
+            on_placed(node.id, &value);
+        }
+    }
+
+    match collapse.return_or_self() {
+        Ok(result) => result,
+        Err(_) => Err(EngineError::IterationError.into()),
+    }
+}
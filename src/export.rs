@@ -0,0 +1,132 @@
+//! An engine neutral JSON format for collapsed [TileMap2D]s, so results can be used without
+//! linking this crate.
+//!
+//! # Schema
+//! ```json
+//! {
+//!   "format": "wave-collapse-result",
+//!   "version": 1,
+//!   "width": 3,
+//!   "height": 2,
+//!   "palette": ["grass", "water"],
+//!   "cells": [0, 0, 1, 0, 1, 1],
+//!   "metadata": {
+//!     "seed": "000000000000002a",
+//!     "rule_hash": null
+//!   }
+//! }
+//! ```
+//! - `format` and `version` identify the schema. The version is incremented for every
+//!   incompatible change.
+//! - `palette` contains a label for every distinct value, in the order they first appear in
+//!   `cells`.
+//! - `cells` contains the index into `palette` of each node, row by row starting at the top
+//!   left, so the cell `(x, y)` is at index `y * width + x`.
+//! - `seed` and `rule_hash` are 64 bit hex strings, because JSON numbers can not represent all
+//!   64 bit integers exactly. They are `null` if not known.
+
+use std::io;
+
+use crate::tile2d::TileMap2D;
+use crate::wave_function::ReadShape;
+
+/// The version of the schema written by [write_json].
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Additional information about how a result was generated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExportMetadata {
+    /// the seed of the rng used to collapse the shape.
+    pub seed: Option<u64>,
+    /// a hash of the rules used to collapse the shape, e.g. calculated with a
+    /// [crate::digest::Fnv1aHasher].
+    pub rule_hash: Option<u64>,
+}
+
+/// Writes the collapsed `shape` as JSON, see the [module documentation](self) for the schema.
+/// `label` is used to create the palette entry of each value.
+///
+/// returns an error of kind [io::ErrorKind::InvalidInput] if `shape` is not collapsed.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::tile2d::{Size2D, TileMap2D};
+/// use std::fs::File;
+/// use wave_collapse::export::{write_json, ExportMetadata};
+/// # let shape = TileMap2D::new(Size2D::square(10), Size2D::square(3), &[0u32, 1]);
+/// let metadata = ExportMetadata {
+///     seed: Some(42),
+///     rule_hash: None,
+/// };
+/// let file = File::create("level.json").unwrap();
+/// write_json(file, &shape, &metadata, |value| format!("tile_{value}")).unwrap();
+/// ```
+pub fn write_json<NodeValue, W>(
+    mut writer: W,
+    shape: &TileMap2D<NodeValue>,
+    metadata: &ExportMetadata,
+    label: impl Fn(&NodeValue) -> String,
+) -> io::Result<()>
+where
+    NodeValue: Clone + PartialEq,
+    W: io::Write,
+{
+    let mut palette: Vec<NodeValue> = Vec::new();
+    let mut cells = Vec::new();
+    for node in shape.iter_nodes() {
+        let value = node
+            .collapsed()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "shape is not collapsed"))?;
+
+        let index = match palette.iter().position(|other| *other == value) {
+            Some(index) => index,
+            None => {
+                palette.push(value);
+                palette.len() - 1
+            }
+        };
+        cells.push(index.to_string());
+    }
+
+    let palette: Vec<_> = palette
+        .iter()
+        .map(|value| json_string(&label(value)))
+        .collect();
+    let hex = |value: Option<u64>| match value {
+        Some(value) => format!("\"{value:016x}\""),
+        None => "null".to_string(),
+    };
+
+    writeln!(writer, "{{")?;
+    writeln!(writer, "  \"format\": \"wave-collapse-result\",")?;
+    writeln!(writer, "  \"version\": {FORMAT_VERSION},")?;
+    writeln!(writer, "  \"width\": {},", shape.size().width)?;
+    writeln!(writer, "  \"height\": {},", shape.size().height)?;
+    writeln!(writer, "  \"palette\": [{}],", palette.join(", "))?;
+    writeln!(writer, "  \"cells\": [{}],", cells.join(", "))?;
+    writeln!(writer, "  \"metadata\": {{")?;
+    writeln!(writer, "    \"seed\": {},", hex(metadata.seed))?;
+    writeln!(writer, "    \"rule_hash\": {}", hex(metadata.rule_hash))?;
+    writeln!(writer, "  }}")?;
+    writeln!(writer, "}}")?;
+    writer.flush()
+}
+
+/// returns `value` as a quoted and escaped JSON string.
+fn json_string(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 2);
+    result.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if c.is_control() => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
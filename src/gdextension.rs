@@ -0,0 +1,153 @@
+//! Godot bindings, that expose tile map generation to GDScript.
+//!
+//! The classes are registered by the GDExtension library that depends on this crate. Godot
+//! only loads `cdylib` crates, so the library needs its own entry point:
+//! ```ignore
+//! use godot::prelude::*;
+//!
+//! struct LevelGeneration;
+//!
+//! #[gdextension]
+//! unsafe impl ExtensionLibrary for LevelGeneration {}
+//! ```
+//!
+//! ```gdscript
+//! var generator = WaveTileMapGenerator.new()
+//! generator.set_tile_count(2)
+//! generator.add_rule(0, 1, Vector2i.RIGHT)
+//! generator.add_rule(1, 0, Vector2i.RIGHT)
+//! if generator.generate(32, 32, 42):
+//!     var tile = generator.get_cell(3, 5)
+//! ```
+
+use std::collections::HashSet;
+
+use ::godot::prelude::*;
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::tile2d::wrapping_mode::Cutoff;
+use crate::tile2d::{Direction, Kernel2D, Neighborhood, Size2D, TileMap2D};
+use crate::wave_function::{ReadShape, WaveSolver};
+use crate::{collapse_wave, GenIterReturnResult};
+
+/// Allows two tiles next to each other in a direction. Missing neighbors do not constrain a
+/// tile.
+#[derive(Debug, Default)]
+struct AdjacencySolver {
+    allowed: HashSet<(u32, u32, Direction)>,
+}
+
+impl WaveSolver<u32, Kernel2D<Cutoff, u32>> for AdjacencySolver {
+    fn is_valid(&self, tile: &u32, kernel: &Kernel2D<Cutoff, u32>) -> bool {
+        Direction::ORTHOGONAL.iter().all(|&direction| {
+            if !kernel.is_changed_dir(direction) {
+                return true;
+            }
+            match kernel.get_dir(direction).node() {
+                Some(node) => {
+                    node.any_value(|neighbor| self.allowed.contains(&(*tile, *neighbor, direction)))
+                }
+                None => true,
+            }
+        })
+    }
+}
+
+/// Generates tile maps from adjacency rules between tiles, which are identified by their index.
+#[derive(GodotClass)]
+#[class(base = RefCounted, init)]
+pub struct WaveTileMapGenerator {
+    tile_count: u32,
+    solver: AdjacencySolver,
+    width: u32,
+    height: u32,
+    cells: Vec<i32>,
+}
+
+#[godot_api]
+impl WaveTileMapGenerator {
+    /// Sets the number of tiles. Tiles are identified by their index from `0` to `count - 1`.
+    #[func]
+    fn set_tile_count(&mut self, count: i64) {
+        self.tile_count = count.max(0) as u32;
+    }
+
+    /// Allows `neighbor` next to `tile` at `offset`, which must be one of the 4 orthogonal
+    /// unit vectors. The inverse rule is added as well.
+    #[func]
+    fn add_rule(&mut self, tile: i64, neighbor: i64, offset: Vector2i) {
+        let direction = match (offset.x, offset.y) {
+            (0, -1) => Direction::Top,
+            (0, 1) => Direction::Bottom,
+            (-1, 0) => Direction::Left,
+            (1, 0) => Direction::Right,
+            _ => {
+                godot_error!("offset {offset} is not an orthogonal unit vector");
+                return;
+            }
+        };
+        let (tile, neighbor) = (tile as u32, neighbor as u32);
+
+        self.solver.allowed.insert((tile, neighbor, direction));
+        self.solver
+            .allowed
+            .insert((neighbor, tile, direction.opposite()));
+    }
+
+    /// Removes all rules.
+    #[func]
+    fn clear_rules(&mut self) {
+        self.solver.allowed.clear();
+    }
+
+    /// Generates a map of `width` x `height` tiles using `seed`.
+    /// returns `false` if the rules could not be satisfied, in which case no cells are set.
+    #[func]
+    fn generate(&mut self, width: i64, height: i64, seed: i64) -> bool {
+        self.width = 0;
+        self.height = 0;
+        self.cells.clear();
+
+        if self.tile_count == 0 || width <= 0 || height <= 0 {
+            godot_error!("tile count, width and height must be positive");
+            return false;
+        }
+
+        let tiles: Vec<u32> = (0..self.tile_count).collect();
+        let size = Size2D::new(width as u32, height as u32);
+        let shape = TileMap2D::new(size, Size2D::square(3), &tiles)
+            .with_neighborhood(Neighborhood::Orthogonal);
+
+        let mut rng = StdRng::seed_from_u64(seed as u64);
+        let result =
+            collapse_wave::<_, _, _, Kernel2D<Cutoff, u32>, _>(shape, &self.solver, &mut rng)
+                .calc_result();
+        let shape = match result {
+            Ok(outcome) => outcome.shape,
+            Err(_) => return false,
+        };
+
+        self.cells = shape
+            .iter_nodes()
+            .map(|node| node.collapsed().expect("collapsed shape") as i32)
+            .collect();
+        self.width = size.width;
+        self.height = size.height;
+        true
+    }
+
+    /// returns the tile at `(x, y)` of the last generated map, or `-1` if there is none.
+    #[func]
+    fn get_cell(&self, x: i64, y: i64) -> i64 {
+        if !(0..self.width as i64).contains(&x) || !(0..self.height as i64).contains(&y) {
+            return -1;
+        }
+        self.cells[(y * self.width as i64 + x) as usize] as i64
+    }
+
+    /// returns the tiles of the last generated map row by row.
+    #[func]
+    fn get_cells(&self) -> PackedInt32Array {
+        PackedInt32Array::from(self.cells.as_slice())
+    }
+}
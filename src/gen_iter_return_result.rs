@@ -1,28 +1,29 @@
-use std::{ops::Generator, rc::Rc};
+use std::ops::Generator;
 
 use gen_iter::GenIterReturn;
 
-use crate::error::WaveCollapseError;
+use crate::error::{EngineError, WaveCollapseError};
+use crate::outcome::CollapseOutcome;
 
 pub trait GenIterReturnResult<T> {
-    fn calc_result(self) -> Result<Rc<T>, WaveCollapseError>;
+    fn calc_result(self) -> Result<CollapseOutcome<T>, WaveCollapseError>;
 }
 
-trait ResultRc<T> {
-    fn result(self) -> Result<Rc<T>, WaveCollapseError>;
+trait ResultOutcome<T> {
+    fn result(self) -> Result<CollapseOutcome<T>, WaveCollapseError>;
 }
 
-impl<T> ResultRc<T> for Result<Rc<T>, WaveCollapseError> {
-    fn result(self) -> Result<Rc<T>, WaveCollapseError> {
+impl<T> ResultOutcome<T> for Result<CollapseOutcome<T>, WaveCollapseError> {
+    fn result(self) -> Result<CollapseOutcome<T>, WaveCollapseError> {
         self
     }
 }
 
 impl<T, G: Generator + Unpin> GenIterReturnResult<T> for GenIterReturn<G>
 where
-    G::Return: ResultRc<T>,
+    G::Return: ResultOutcome<T>,
 {
-    fn calc_result(self) -> Result<Rc<T>, WaveCollapseError> {
+    fn calc_result(self) -> Result<CollapseOutcome<T>, WaveCollapseError> {
         // FIXME: for some reason I'm not allowed to use self in this function.
         //      Moving it into foo seems to fix the issue
         let mut my_self = self;
@@ -33,7 +34,7 @@ where
                 Ok(r) => Ok(r),
                 Err(e) => Err(e),
             },
-            Err(_) => Err(WaveCollapseError::IterationError),
+            Err(_) => Err(EngineError::IterationError.into()),
         }
     }
 }
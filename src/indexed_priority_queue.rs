@@ -0,0 +1,207 @@
+use std::{collections::HashMap, hash::Hash};
+
+/// A min priority queue where each key is stored at most once. The priority of a key
+/// can be updated in `O(log n)`.
+///
+/// Keys with equal priorities are popped in the order they were first pushed, so the order does
+/// not depend on the shape of the heap.
+pub struct IndexedPriorityQueue<Key, Priority> {
+    heap: Vec<(Key, Priority, u64)>,
+    positions: HashMap<Key, usize>,
+    pushed: u64,
+}
+
+impl<Key, Priority> Default for IndexedPriorityQueue<Key, Priority> {
+    fn default() -> Self {
+        IndexedPriorityQueue {
+            heap: Vec::new(),
+            positions: HashMap::new(),
+            pushed: 0,
+        }
+    }
+}
+
+impl<Key: Clone + Eq + Hash, Priority: Ord> IndexedPriorityQueue<Key, Priority> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn contains(&self, key: &Key) -> bool {
+        self.positions.contains_key(key)
+    }
+
+    /// returns the current priority of `key`.
+    pub fn priority(&self, key: &Key) -> Option<&Priority> {
+        self.positions.get(key).map(|index| &self.heap[*index].1)
+    }
+
+    /// Inserts `key` with `priority`. If `key` is already in the queue its priority is updated
+    /// instead. Returns `true` if `key` was not part of the queue.
+    pub fn push(&mut self, key: Key, priority: Priority) -> bool {
+        if let Some(&index) = self.positions.get(&key) {
+            self.heap[index].1 = priority;
+            let index = self.sift_up(index);
+            self.sift_down(index);
+            false
+        } else {
+            let index = self.heap.len();
+            self.positions.insert(key.clone(), index);
+            self.heap.push((key, priority, self.pushed));
+            self.pushed += 1;
+            self.sift_up(index);
+            true
+        }
+    }
+
+    /// removes and returns the key with the lowest priority.
+    pub fn pop(&mut self) -> Option<(Key, Priority)> {
+        if self.heap.is_empty() {
+            return None;
+        }
+
+        let last = self.heap.len() - 1;
+        self.swap(0, last);
+        let (key, priority, _) = self.heap.pop().expect("heap is not empty");
+        self.positions.remove(&key);
+
+        if !self.heap.is_empty() {
+            self.sift_down(0);
+        }
+
+        Some((key, priority))
+    }
+
+    /// returns `true` if the entry at `a` is popped before the entry at `b`.
+    fn is_before(&self, a: usize, b: usize) -> bool {
+        let (_, priority_a, pushed_a) = &self.heap[a];
+        let (_, priority_b, pushed_b) = &self.heap[b];
+        (priority_a, pushed_a) < (priority_b, pushed_b)
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.heap.swap(a, b);
+        *self
+            .positions
+            .get_mut(&self.heap[a].0)
+            .expect("every key in the heap has a position") = a;
+        *self
+            .positions
+            .get_mut(&self.heap[b].0)
+            .expect("every key in the heap has a position") = b;
+    }
+
+    fn sift_up(&mut self, mut index: usize) -> usize {
+        while index > 0 {
+            let parent = (index - 1) / 2;
+            if self.is_before(index, parent) {
+                self.swap(index, parent);
+                index = parent;
+            } else {
+                break;
+            }
+        }
+        index
+    }
+
+    fn sift_down(&mut self, mut index: usize) {
+        loop {
+            let left = 2 * index + 1;
+            let right = left + 1;
+            let mut smallest = index;
+
+            if left < self.heap.len() && self.is_before(left, smallest) {
+                smallest = left;
+            }
+            if right < self.heap.len() && self.is_before(right, smallest) {
+                smallest = right;
+            }
+            if smallest == index {
+                break;
+            }
+
+            self.swap(index, smallest);
+            index = smallest;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IndexedPriorityQueue;
+
+    fn pop_all(queue: &mut IndexedPriorityQueue<&'static str, i64>) -> Vec<&'static str> {
+        std::iter::from_fn(|| queue.pop().map(|(key, _)| key)).collect()
+    }
+
+    #[test]
+    fn pops_the_lowest_priority_first() {
+        let mut queue = IndexedPriorityQueue::new();
+        for (key, priority) in [("d", 4), ("b", 2), ("e", 5), ("a", 1), ("c", 3)] {
+            assert!(queue.push(key, priority));
+        }
+
+        assert_eq!(queue.len(), 5);
+        assert_eq!(pop_all(&mut queue), ["a", "b", "c", "d", "e"]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn push_updates_the_priority_of_queued_keys() {
+        let mut queue = IndexedPriorityQueue::new();
+        queue.push("a", 1);
+        queue.push("b", 5);
+        queue.push("c", 3);
+
+        // decrease
+        assert!(!queue.push("b", 0));
+        assert_eq!(queue.priority(&"b"), Some(&0));
+        // increase
+        assert!(!queue.push("a", 4));
+        assert_eq!(queue.priority(&"a"), Some(&4));
+
+        assert_eq!(queue.len(), 3);
+        assert_eq!(pop_all(&mut queue), ["b", "c", "a"]);
+    }
+
+    #[test]
+    fn pop_removes_the_key() {
+        let mut queue = IndexedPriorityQueue::new();
+        queue.push("a", 1);
+        queue.push("b", 2);
+
+        assert_eq!(queue.pop(), Some(("a", 1)));
+        assert!(!queue.contains(&"a"));
+        assert_eq!(queue.priority(&"a"), None);
+        assert!(queue.contains(&"b"));
+
+        // a popped key is pushed as a new key
+        assert!(queue.push("a", 3));
+        assert_eq!(pop_all(&mut queue), ["b", "a"]);
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn ties_are_popped_in_insertion_order() {
+        let mut queue = IndexedPriorityQueue::new();
+        for key in ["a", "b", "c", "d", "e", "f", "g"] {
+            queue.push(key, 1);
+        }
+        queue.push("h", 0);
+        // an update keeps the position of the key among equal priorities
+        queue.push("b", 2);
+        queue.push("b", 1);
+
+        assert_eq!(
+            pop_all(&mut queue),
+            ["h", "a", "b", "c", "d", "e", "f", "g"]
+        );
+    }
+}
@@ -1,14 +1,65 @@
 #![feature(associated_type_defaults)]
 #![feature(generators, generator_trait)]
 
-pub mod binary_heap_set;
+pub mod async_collapse;
+pub mod closure_shape;
+pub mod digest;
+pub mod domain;
 pub mod error;
+pub mod event_sourced;
+pub mod events;
 pub mod gen_iter_return_result;
+pub mod indexed_priority_queue;
+pub mod memoize;
+pub mod metadata;
 pub mod node;
+pub mod outcome;
+pub mod rng;
+pub mod search;
+pub mod telemetry;
+pub mod validate;
 pub mod wave_function;
+pub mod wrapping_mode;
 
+#[cfg(feature = "tile2d")]
+pub mod analysis;
+#[cfg(feature = "bench-data")]
+pub mod bench_data;
+#[cfg(feature = "tile2d")]
+pub mod constraints;
+#[cfg(feature = "cube-sphere")]
+pub mod cube_sphere;
+#[cfg(feature = "tile2d")]
+pub mod export;
+#[cfg(feature = "godot")]
+pub mod gdextension;
+#[cfg(feature = "tile2d")]
+pub mod merge;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+#[cfg(feature = "tile2d")]
+pub mod path;
+#[cfg(feature = "tile2d")]
+pub mod pipeline;
+#[cfg(feature = "tile2d")]
+pub mod progressive;
+#[cfg(feature = "quadtree")]
+pub mod quadtree;
+#[cfg(feature = "tile2d")]
+pub mod render;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
+#[cfg(feature = "tile2d")]
+pub mod sparse;
+#[cfg(all(feature = "tile2d", feature = "rand"))]
+pub mod testing;
 #[cfg(feature = "tile2d")]
 pub mod tile2d;
+#[cfg(feature = "tile3d")]
+pub mod tile3d;
+#[cfg(feature = "tile2d")]
+pub mod view;
 
 pub use gen_iter_return_result::GenIterReturnResult;
-pub use wave_function::collapse_wave;
+pub use outcome::CollapseOutcome;
+pub use wave_function::{collapse_fully, collapse_wave};
@@ -0,0 +1,125 @@
+//! An optional cache for expensive [WaveSolver]s.
+//!
+//! During propagation the same value is often validated against the same neighbor domains over
+//! and over again, especially with small tilesets. [MemoizedSolver] caches the result of
+//! [WaveSolver::is_valid] keyed by the value and a [KernelFingerprint] of the kernel.
+
+use std::{cell::RefCell, collections::HashMap, hash::Hash};
+
+use crate::wave_function::{ReadShape, WaveSolver};
+
+/// A kernel that can describe the possible values of its nodes with a single hash.
+pub trait KernelFingerprint {
+    /// returns a hash of the possible values of all nodes in the kernel except the center,
+    /// including their position relative to the center. Kernels with the same fingerprint must
+    /// be indistinguishable for a solver.
+    fn fingerprint(&self) -> u64;
+}
+
+/// A [WaveSolver] that caches the results of the wrapped solver.
+///
+/// This is only correct if the result of the wrapped solver only depends on the value and the
+/// possible values of the nodes around the center, e.g. not on the position of the center.
+/// Fingerprints are 64 bit hashes, so a collision, while unlikely, leads to a wrong result.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::wave_function::WaveSolver;
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Wrapping};
+/// use wave_collapse::memoize::MemoizedSolver;
+/// use wave_collapse::wave_function::{collapse_fully, CollapseConfig};
+/// # struct ExpensiveSolver;
+/// # impl WaveSolver<u32, Kernel2D<Wrapping, u32>> for ExpensiveSolver {
+/// #     fn is_valid(&self, _value: &u32, _kernel: &Kernel2D<Wrapping, u32>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// let tiles: Vec<u32> = vec![0, 1, 2];
+/// let shape = TileMap2D::new(Size2D::square(100), Size2D::square(3), &tiles);
+/// let solver = MemoizedSolver::new(ExpensiveSolver);
+/// let config = CollapseConfig::new(rand::thread_rng());
+/// let outcome = collapse_fully::<_, _, _, Kernel2D<Wrapping, u32>, _, _>(shape, &solver, config);
+/// ```
+#[derive(Debug)]
+pub struct MemoizedSolver<Solver, NodeValue> {
+    solver: Solver,
+    cache: RefCell<HashMap<NodeValue, HashMap<u64, bool>>>,
+}
+
+impl<Solver, NodeValue> MemoizedSolver<Solver, NodeValue> {
+    pub fn new(solver: Solver) -> Self {
+        MemoizedSolver {
+            solver,
+            cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// returns the wrapped solver.
+    pub fn inner(&self) -> &Solver {
+        &self.solver
+    }
+
+    /// returns the number of cached results.
+    pub fn len(&self) -> usize {
+        self.cache
+            .borrow()
+            .values()
+            .map(|results| results.len())
+            .sum()
+    }
+
+    /// returns `true` if no results are cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all cached results. This is required if the rules of the wrapped solver change.
+    pub fn clear(&self) {
+        self.cache.borrow_mut().clear();
+    }
+}
+
+impl<Solver, NodeValue, Kernel> WaveSolver<NodeValue, Kernel> for MemoizedSolver<Solver, NodeValue>
+where
+    Solver: WaveSolver<NodeValue, Kernel>,
+    NodeValue: Clone + Eq + Hash,
+    Kernel: KernelFingerprint,
+{
+    fn is_valid(&self, value: &NodeValue, kernel: &Kernel) -> bool {
+        let fingerprint = kernel.fingerprint();
+        if let Some(result) = self
+            .cache
+            .borrow()
+            .get(value)
+            .and_then(|results| results.get(&fingerprint))
+        {
+            return *result;
+        }
+
+        let result = self.solver.is_valid(value, kernel);
+        self.cache
+            .borrow_mut()
+            .entry(value.clone())
+            .or_default()
+            .insert(fingerprint, result);
+        result
+    }
+
+    fn kernel_radius(&self) -> Option<u32> {
+        self.solver.kernel_radius()
+    }
+
+    fn kernel_radii(&self) -> Option<Vec<u32>> {
+        self.solver.kernel_radii()
+    }
+
+    fn prepare<NodeId, Shape>(&mut self, shape: &Shape)
+    where
+        NodeValue: Clone,
+        Shape: ReadShape<NodeId, NodeValue> + ?Sized,
+    {
+        self.solver.prepare::<NodeId, Shape>(shape);
+        // the rules of the wrapped solver may have changed
+        self.clear();
+    }
+}
@@ -0,0 +1,126 @@
+//! Merging of collapsed [TileMap2D]s, e.g. to paste a prefab into a generated map.
+//!
+//! The pasted map replaces the nodes it covers. The nodes around it, the seam, are re-opened and
+//! collapsed again, so that the merged map satisfies the solver everywhere. All other nodes keep
+//! their values.
+
+use std::fmt::Debug;
+
+use crate::error::{EngineError, Result};
+use crate::outcome::CollapseOutcome;
+use crate::rng::EntropySource;
+use crate::tile2d::{Index2D, TileMap2D};
+use crate::wave_function::{
+    collapse_fully, propagate_collapsed, CollapseConfig, WaveKernel, WaveSolver,
+};
+
+/// returns the distance of `value` to the range `start..end`, 0 if it is inside the range.
+fn distance_to_range(value: u32, start: u32, end: u32) -> u32 {
+    if value < start {
+        start - value
+    } else if value >= end {
+        value + 1 - end
+    } else {
+        0
+    }
+}
+
+/// Pastes the collapsed `source` into the collapsed `target`, with the top left node of `source`
+/// at `offset`. Nodes of `source` outside of `target` are ignored.
+///
+/// All nodes of `target` that are not covered by `source` and are at most `seam_width` nodes
+/// away from it, diagonals included, are re-opened with `possible_values` and collapsed again.
+/// A wider seam gives the solver more room to connect both maps. With a `seam_width` of 0 the
+/// maps are merged without any repair.
+///
+/// The merged map keeps the kernel size and the settings of `target`, see
+/// [TileMap2D::with_settings_of].
+///
+/// returns [EngineError::NotCollapsed] if a node of `target` or `source` is not collapsed
+/// and [crate::error::PropagationError::InvalidSuperposition] if the seam can not be repaired.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Cutoff};
+/// # use wave_collapse::wave_function::WaveSolver;
+/// use wave_collapse::collapse_fully;
+/// use wave_collapse::merge::merge_maps;
+/// use wave_collapse::wave_function::CollapseConfig;
+/// # struct TestSolver;
+/// # impl WaveSolver<char, Kernel2D<Cutoff, char>> for TestSolver {
+/// #     fn is_valid(&self, _value: &char, _kernel: &Kernel2D<Cutoff, char>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// let tiles = ['#', '.', '~'];
+/// let mut rng = rand::thread_rng();
+/// let map = TileMap2D::new(Size2D::square(50), Size2D::square(3), &tiles);
+/// let map = collapse_fully::<_, _, _, Kernel2D<Cutoff, char>, _, _>(
+///     map,
+///     &TestSolver,
+///     CollapseConfig::new(&mut rng),
+/// )
+/// .expect("map collapsed");
+///
+/// let prefab = TileMap2D::from_template(
+///     Size2D::square(3),
+///     Size2D::square(3),
+///     &[Some('#'); 9],
+///     &tiles,
+/// );
+/// let merged = merge_maps::<_, Kernel2D<Cutoff, char>, _>(
+///     &map.shape,
+///     &prefab,
+///     (20, 20),
+///     2,
+///     &tiles,
+///     &TestSolver,
+///     &mut rng,
+/// );
+/// ```
+pub fn merge_maps<NodeValue, Kernel, Solver>(
+    target: &TileMap2D<NodeValue>,
+    source: &TileMap2D<NodeValue>,
+    offset: Index2D,
+    seam_width: u32,
+    possible_values: &[NodeValue],
+    solver: &Solver,
+    rng: &mut impl EntropySource,
+) -> Result<CollapseOutcome<TileMap2D<NodeValue>>>
+where
+    NodeValue: Clone + PartialEq + Debug,
+    Kernel: WaveKernel<Index2D, NodeValue, TileMap2D<NodeValue>>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+{
+    let size = *target.size();
+    let source_size = *source.size();
+    let end_x = offset.0.saturating_add(source_size.width);
+    let end_y = offset.1.saturating_add(source_size.height);
+
+    let collapsed = |shape: &TileMap2D<NodeValue>, x: u32, y: u32| {
+        shape[(x, y)]
+            .collapsed()
+            .ok_or_else(|| EngineError::NotCollapsed(format!("{:?}", (x, y))))
+    };
+
+    let mut template = Vec::with_capacity((size.width * size.height) as usize);
+    for y in 0..size.height {
+        for x in 0..size.width {
+            let distance =
+                distance_to_range(x, offset.0, end_x).max(distance_to_range(y, offset.1, end_y));
+            let value = if distance == 0 {
+                Some(collapsed(source, x - offset.0, y - offset.1)?)
+            } else if distance <= seam_width {
+                None
+            } else {
+                Some(collapsed(target, x, y)?)
+            };
+            template.push(value);
+        }
+    }
+
+    let merged = TileMap2D::from_template(size, *target.kernel_size(), &template, possible_values)
+        .with_settings_of(target);
+    let merged = propagate_collapsed::<_, _, _, Kernel, _>(merged, solver)?;
+    collapse_fully::<_, _, _, Kernel, _, _>(merged, solver, CollapseConfig::new(rng))
+}
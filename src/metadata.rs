@@ -0,0 +1,98 @@
+//! Auxiliary data, like collision masks or spawn tables, that is carried by node values but is
+//! not part of the rules.
+
+use std::{
+    fmt::{self, Debug, Formatter},
+    hash::{Hash, Hasher},
+    ops::Deref,
+    sync::Arc,
+};
+
+/// A node value with attached `metadata`. Only the `value` is compared and hashed, so solvers
+/// and rules only see the `value`, while the `metadata` is preserved in the collapsed shape.
+/// The metadata is shared, so cloning a [WithMetadata] is cheap.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::tile2d::{Size2D, TileMap2D};
+/// use wave_collapse::metadata::WithMetadata;
+/// struct Collision {
+///     solid: bool,
+/// }
+///
+/// let tiles = [
+///     WithMetadata::new('.', Collision { solid: false }),
+///     WithMetadata::new('#', Collision { solid: true }),
+/// ];
+/// let shape = TileMap2D::new(Size2D::square(10), Size2D::square(3), &tiles);
+/// // collapse the shape
+/// for (id, tile) in &shape {
+///     let solid = tile.metadata().solid;
+/// }
+/// ```
+pub struct WithMetadata<Value, Metadata> {
+    value: Value,
+    metadata: Arc<Metadata>,
+}
+
+impl<Value, Metadata> WithMetadata<Value, Metadata> {
+    pub fn new(value: Value, metadata: Metadata) -> Self {
+        WithMetadata {
+            value,
+            metadata: Arc::new(metadata),
+        }
+    }
+
+    /// returns the value, that is used by the rules.
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    /// returns the metadata attached to the value.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// returns the value and the metadata.
+    pub fn into_parts(self) -> (Value, Arc<Metadata>) {
+        (self.value, self.metadata)
+    }
+}
+
+impl<Value, Metadata> Deref for WithMetadata<Value, Metadata> {
+    type Target = Value;
+
+    fn deref(&self) -> &Value {
+        &self.value
+    }
+}
+
+impl<Value: Clone, Metadata> Clone for WithMetadata<Value, Metadata> {
+    fn clone(&self) -> Self {
+        WithMetadata {
+            value: self.value.clone(),
+            metadata: self.metadata.clone(),
+        }
+    }
+}
+
+/// Only the value is printed, so the metadata does not need to implement [Debug].
+impl<Value: Debug, Metadata> Debug for WithMetadata<Value, Metadata> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl<Value: PartialEq, Metadata> PartialEq for WithMetadata<Value, Metadata> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<Value: Eq, Metadata> Eq for WithMetadata<Value, Metadata> {}
+
+impl<Value: Hash, Metadata> Hash for WithMetadata<Value, Metadata> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.hash(state);
+    }
+}
@@ -3,28 +3,38 @@ use std::{
     fmt::Debug,
     hash::Hash,
     marker::PhantomData,
+    mem::size_of,
 };
 
-use crate::wave_function::WaveShape;
+use crate::domain::{Domain, DomainStorage};
+use crate::wave_function::ReadShape;
 
 /// This describes a single node within the wave function. It contains all possible values this node can
 /// be collopsed into.
+/// The possible values are stored in a [DomainStorage], which is a [Domain] by default.
 #[derive(Clone)]
-pub struct Node<Id, NodeValueDescription> {
+pub struct Node<Id, NodeValueDescription, Storage = Domain<NodeValueDescription>> {
     /// a unique id within a wave shape
     pub id: Id,
 
     /// all possible values this node can be collopsed into.
-    pub(super) possible_values: RefCell<Vec<NodeValueDescription>>,
+    pub(super) possible_values: RefCell<Storage>,
 
     /// denotes whether or not this cell is collapsed or not.
     pub(super) is_collapsed: RefCell<bool>,
+
+    /// denotes whether the values were changed by [Node::with_values_mut] since the change was
+    /// last propagated.
+    pub(super) is_dirty: RefCell<bool>,
+
+    _value_phantom: PhantomData<NodeValueDescription>,
 }
 
-impl<Id, NodeValue> Debug for Node<Id, NodeValue>
+impl<Id, NodeValue, Storage> Debug for Node<Id, NodeValue, Storage>
 where
     Id: Debug,
     NodeValue: Debug,
+    Storage: DomainStorage<NodeValue>,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Node")
@@ -36,26 +46,45 @@ where
 
 impl<Id, NodeValueDescription: Clone> Node<Id, NodeValueDescription> {
     pub fn new<Values>(id: Id, possible_values: Values) -> Self
+    where
+        Values: Into<Vec<NodeValueDescription>>,
+    {
+        Self::with_storage(id, possible_values)
+    }
+}
+
+impl<Id, NodeValueDescription, Storage> Node<Id, NodeValueDescription, Storage>
+where
+    NodeValueDescription: Clone,
+    Storage: DomainStorage<NodeValueDescription>,
+{
+    /// like [Node::new], but the possible values are stored in a custom [DomainStorage].
+    pub fn with_storage<Values>(id: Id, possible_values: Values) -> Self
     where
         Values: Into<Vec<NodeValueDescription>>,
     {
         Node {
             id,
-            possible_values: RefCell::new(possible_values.into()),
+            possible_values: RefCell::new(Storage::from_values(possible_values.into())),
             is_collapsed: RefCell::new(false),
+            is_dirty: RefCell::new(false),
+            _value_phantom: PhantomData::default(),
         }
     }
 
     pub fn collapsed(&self) -> Option<NodeValueDescription> {
         if self.is_collapsed() {
-            Some(self.possible_values.borrow()[0].clone())
+            Some(self.possible_values.borrow().as_slice()[0].clone())
         } else {
             None
         }
     }
 }
 
-impl<Id, NodeValueDescription> Node<Id, NodeValueDescription> {
+impl<Id, NodeValueDescription, Storage> Node<Id, NodeValueDescription, Storage>
+where
+    Storage: DomainStorage<NodeValueDescription>,
+{
     pub fn is_collapsed(&self) -> bool {
         *self.is_collapsed.borrow()
     }
@@ -63,19 +92,152 @@ impl<Id, NodeValueDescription> Node<Id, NodeValueDescription> {
     /// returns `true` if this node is overspecified, meaning that there are no valid
     /// values for it left.
     pub fn is_overspecified(&self) -> bool {
-        self.possible_values.borrow().len() == 0
+        self.possible_values.borrow().is_empty()
+    }
+
+    /// like [Node::collapsed], but returns a reference to the value instead of a clone.
+    pub fn collapsed_ref(&self) -> Option<Ref<'_, NodeValueDescription>> {
+        if self.is_collapsed() {
+            Some(Ref::map(self.possible_values.borrow(), |v| {
+                &v.as_slice()[0]
+            }))
+        } else {
+            None
+        }
     }
 
     pub fn possible_values(&self) -> Ref<'_, [NodeValueDescription]> {
         Ref::map(self.possible_values.borrow(), |v| v.as_slice())
     }
 
+    /// returns `true` if `f` returns `true` for any possible value.
+    pub fn any_value(&self, f: impl FnMut(&NodeValueDescription) -> bool) -> bool {
+        self.possible_values.borrow().as_slice().iter().any(f)
+    }
+
+    /// returns `true` if `f` returns `true` for all possible values. This is also `true` if the
+    /// node is overspecified.
+    pub fn all_values(&self, f: impl FnMut(&NodeValueDescription) -> bool) -> bool {
+        self.possible_values.borrow().as_slice().iter().all(f)
+    }
+
+    /// returns `true` if `value` is a possible value.
+    pub fn contains(&self, value: &NodeValueDescription) -> bool
+    where
+        NodeValueDescription: PartialEq,
+    {
+        self.possible_values.borrow().as_slice().contains(value)
+    }
+
     pub fn entropy(&self) -> u32 {
         self.possible_values.borrow().len() as u32
     }
+
+    /// returns the Shannon entropy of the possible values, where each value is as likely as its
+    /// `weight`. With equal weights this is `ln(entropy)`. If no value has a positive weight, the
+    /// values are as likely as if their weights were equal.
+    ///
+    /// The result is the same on every platform, see [crate::rng], because the logarithm is
+    /// calculated with basic float operations instead of [f32::ln].
+    pub fn weighted_entropy(&self, weight: impl Fn(&NodeValueDescription) -> f32) -> f32 {
+        let values = self.possible_values.borrow();
+        let weights: Vec<f32> = values.as_slice().iter().map(weight).collect();
+        let total: f32 = weights.iter().sum();
+        if total <= 0.0 {
+            return portable_ln(weights.len() as f32);
+        }
+        let weighted_log: f32 = weights
+            .iter()
+            .filter(|weight| **weight > 0.0)
+            .map(|weight| weight * portable_ln(*weight))
+            .sum();
+        portable_ln(total) - weighted_log / total
+    }
+
+    /// Changes the possible values with `f` and returns the result of `f`.
+    /// Constraints should change the values only through this, so that the node stays
+    /// consistent: a collapsed node that does not have exactly one possible value afterwards is
+    /// no longer collapsed.
+    ///
+    /// The node is marked as dirty, so the next step of a collapse propagates the change before
+    /// it chooses a node, see [Node::is_dirty].
+    ///
+    /// # Panics
+    /// if the possible values are borrowed, e.g. by `f` calling [Node::possible_values].
+    pub fn with_values_mut<R>(&self, f: impl FnOnce(&mut Storage) -> R) -> R {
+        let result = self.update_values(f);
+        *self.is_dirty.borrow_mut() = true;
+        result
+    }
+
+    /// like [Node::with_values_mut], but does not mark the node as dirty. Used by the collapse,
+    /// which propagates its own changes.
+    pub(crate) fn update_values<R>(&self, f: impl FnOnce(&mut Storage) -> R) -> R {
+        let mut values = self.possible_values.borrow_mut();
+        let result = f(&mut values);
+        if values.len() != 1 {
+            *self.is_collapsed.borrow_mut() = false;
+        }
+        result
+    }
+
+    /// returns `true` if the values were changed with [Node::with_values_mut] and the change was
+    /// not propagated yet.
+    pub fn is_dirty(&self) -> bool {
+        *self.is_dirty.borrow()
+    }
+
+    /// marks the change of the values as propagated and returns whether the node was dirty.
+    pub(crate) fn take_dirty(&self) -> bool {
+        self.is_dirty.replace(false)
+    }
+
+    /// Restores all values that were removed in `generation` or later, see
+    /// [DomainStorage::restore].
+    /// If any value is restored the node is no longer collapsed.
+    pub fn restore(&self, generation: u32) {
+        if self.update_values(|values| values.restore(generation)) > 0 {
+            *self.is_collapsed.borrow_mut() = false;
+        }
+    }
+
+    /// returns an estimate of the memory in bytes used by this node, see
+    /// [DomainStorage::heap_size].
+    pub fn memory_usage(&self) -> usize {
+        size_of::<Self>() + self.possible_values.borrow().heap_size()
+    }
+}
+
+/// returns the natural logarithm of `x`, calculated only with operations that IEEE 754 rounds
+/// exactly, so the result does not depend on the platform or libm like [f32::ln] does.
+/// `x` must not be negative.
+fn portable_ln(x: f32) -> f32 {
+    if x == 0.0 {
+        return f32::NEG_INFINITY;
+    }
+    if x.is_infinite() {
+        return f32::INFINITY;
+    }
+
+    // x = m * 2^e with m in [sqrt(0.5), sqrt(2)), every f32 is a normal f64
+    let bits = (x as f64).to_bits();
+    let mut exponent = ((bits >> 52) & 0x7ff) as i64 - 1023;
+    let mut mantissa = f64::from_bits((bits & ((1 << 52) - 1)) | (1023 << 52));
+    if mantissa > std::f64::consts::SQRT_2 {
+        mantissa /= 2.0;
+        exponent += 1;
+    }
+
+    // ln(m) = 2 * atanh(s) with s = (m - 1) / (m + 1) and |s| < 0.18
+    let s = (mantissa - 1.0) / (mantissa + 1.0);
+    let s2 = s * s;
+    let series = (1..=7)
+        .rev()
+        .fold(0.0, |sum, k| sum * s2 + 1.0 / (2 * k - 1) as f64);
+    (exponent as f64 * std::f64::consts::LN_2 + 2.0 * s * series) as f32
 }
 
-impl<Id, NodeValueDescription> Hash for Node<Id, NodeValueDescription>
+impl<Id, NodeValueDescription, Storage> Hash for Node<Id, NodeValueDescription, Storage>
 where
     Id: Hash,
 {
@@ -84,9 +246,9 @@ where
     }
 }
 
-impl<Id, NodeValueDescription> Eq for Node<Id, NodeValueDescription> where Id: Eq {}
+impl<Id, NodeValueDescription, Storage> Eq for Node<Id, NodeValueDescription, Storage> where Id: Eq {}
 
-impl<Id, NodeValueDescription> PartialEq for Node<Id, NodeValueDescription>
+impl<Id, NodeValueDescription, Storage> PartialEq for Node<Id, NodeValueDescription, Storage>
 where
     Id: PartialEq,
 {
@@ -95,9 +257,10 @@ where
     }
 }
 
-impl<Id, NodeValueDescription> PartialOrd for Node<Id, NodeValueDescription>
+impl<Id, NodeValueDescription, Storage> PartialOrd for Node<Id, NodeValueDescription, Storage>
 where
     Id: PartialEq,
+    Storage: DomainStorage<NodeValueDescription>,
 {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         if *self == *other {
@@ -107,9 +270,10 @@ where
     }
 }
 
-impl<Id, NodeValueDescription> Ord for Node<Id, NodeValueDescription>
+impl<Id, NodeValueDescription, Storage> Ord for Node<Id, NodeValueDescription, Storage>
 where
     Id: Eq,
+    Storage: DomainStorage<NodeValueDescription>,
 {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         if self == other {
@@ -122,7 +286,12 @@ where
 /// The internally used iterator type when iterating node ids.
 pub type NodeIdIter<T> = std::vec::IntoIter<T>;
 
-/// An iterator over nodes, that uses [NodeIdIter] and [WaveShape] to
+/// An iterator over the nodes of a kernel and their direction from the center, see
+/// [crate::wave_function::WaveKernel::iter_directed].
+pub type DirectedNodeIter<'a, Direction, NodeId, NodeValueDescription, Storage> =
+    std::vec::IntoIter<(Direction, &'a Node<NodeId, NodeValueDescription, Storage>)>;
+
+/// An iterator over nodes, that uses [NodeIdIter] and [ReadShape] to
 /// iterate nodes.
 pub struct NodeIter<'a, NodeId, NodeValueDescription, Shape: ?Sized> {
     shape: &'a Shape,
@@ -150,9 +319,9 @@ impl<'a, NodeId, NodeValueDescription, Shape> Iterator
 where
     NodeId: 'a,
     NodeValueDescription: Clone + 'a,
-    Shape: WaveShape<NodeId, NodeValueDescription> + ?Sized,
+    Shape: ReadShape<NodeId, NodeValueDescription> + ?Sized,
 {
-    type Item = &'a Node<NodeId, NodeValueDescription>;
+    type Item = &'a Node<NodeId, NodeValueDescription, Shape::Storage>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.iterator.next().map(|id| {
@@ -162,3 +331,46 @@ where
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Node;
+
+    #[test]
+    fn weighted_entropy_with_equal_weights_is_ln_of_entropy() {
+        let node = Node::new(0, vec!['a', 'b', 'c', 'd']);
+
+        let entropy = node.weighted_entropy(|_| 2.0);
+        assert!((entropy - 4f32.ln()).abs() < 1e-5, "{entropy}");
+    }
+
+    #[test]
+    fn weighted_entropy_ignores_values_without_weight() {
+        let node = Node::new(0, vec!['a', 'b', 'c']);
+
+        let entropy = node.weighted_entropy(|value| if *value == 'a' { 1.0 } else { 0.0 });
+        assert_eq!(entropy, 0.0);
+    }
+
+    #[test]
+    fn weighted_entropy_without_positive_weights_is_uniform() {
+        let node = Node::new(0, vec!['a', 'b']);
+
+        let entropy = node.weighted_entropy(|_| 0.0);
+        assert!((entropy - 2f32.ln()).abs() < 1e-5, "{entropy}");
+    }
+
+    #[test]
+    fn with_values_mut_marks_the_node_as_dirty() {
+        let node = Node::new(0, vec!['a', 'b']);
+        assert!(!node.is_dirty());
+
+        node.update_values(|values| values.retain(0, |value| *value == 'a'));
+        assert!(!node.is_dirty());
+
+        node.with_values_mut(|values| values.restore(0));
+        assert!(node.is_dirty());
+        assert!(node.take_dirty());
+        assert!(!node.is_dirty());
+    }
+}
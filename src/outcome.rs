@@ -0,0 +1,83 @@
+//! The result of a successful collapse.
+
+use std::{rc::Rc, time::Duration};
+
+/// A collapsed shape together with [CollapseStats] about how it was collapsed.
+#[derive(Debug)]
+pub struct CollapseOutcome<Shape> {
+    pub shape: Rc<Shape>,
+    pub stats: CollapseStats,
+}
+
+impl<Shape> CollapseOutcome<Shape> {
+    /// returns the collapsed shape, or [None] if it is still shared, e.g. with a shape yielded
+    /// by [crate::collapse_wave]. The shape returned by [crate::collapse_fully] is never shared.
+    ///
+    /// Unlike the outcome, the shape can be sent to another thread, so a collapse can run on a
+    /// worker thread.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Cutoff};
+    /// # use wave_collapse::wave_function::WaveSolver;
+    /// use rand::{rngs::StdRng, SeedableRng};
+    /// use std::thread;
+    /// use wave_collapse::collapse_fully;
+    /// use wave_collapse::wave_function::CollapseConfig;
+    /// # struct TestSolver;
+    /// # impl WaveSolver<u32, Kernel2D<Cutoff, u32>> for TestSolver {
+    /// #     fn is_valid(&self, _value: &u32, _kernel: &Kernel2D<Cutoff, u32>) -> bool {
+    /// #         todo!()
+    /// #     }
+    /// # }
+    /// let shape = TileMap2D::new(Size2D::square(100), Size2D::square(3), &[0u32, 1, 2]);
+    /// let solver = TestSolver;
+    /// let worker = thread::spawn(move || {
+    ///     let config = CollapseConfig::new(StdRng::seed_from_u64(7));
+    ///     collapse_fully::<_, _, _, Kernel2D<Cutoff, u32>, _, _>(shape, &solver, config)
+    ///         .map(|outcome| outcome.into_shape())
+    /// });
+    /// let shape = worker.join().unwrap();
+    /// ```
+    pub fn into_shape(self) -> Option<Shape> {
+        Rc::try_unwrap(self.shape).ok()
+    }
+}
+
+impl<Shape> Clone for CollapseOutcome<Shape> {
+    fn clone(&self) -> Self {
+        CollapseOutcome {
+            shape: self.shape.clone(),
+            stats: self.stats,
+        }
+    }
+}
+
+/// Statistics about a collapse, so that runs can be logged and compared.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CollapseStats {
+    /// the seed of the rng. The rng passed to [crate::collapse_wave] does not expose its seed,
+    /// so this is [None] unless it is set by the caller.
+    pub seed: Option<u64>,
+    /// the number of collapsed nodes, not counting nodes that were collapsed by propagation.
+    pub iterations: u32,
+    /// the number of contradictions that were encountered.
+    pub contradictions: u32,
+    /// the number of times the collapse was started again after a contradiction.
+    pub retries: u32,
+    /// the time from the first iteration until the result was returned. This includes the
+    /// time spent by the caller between iterations.
+    pub elapsed: Duration,
+    /// an estimate of the peak memory in bytes used by the collapse: the shape, see
+    /// [crate::wave_function::ReadShape::memory_usage], and the propagation queue and
+    /// bookkeeping.
+    pub peak_memory: usize,
+}
+
+impl<Shape> CollapseOutcome<Shape> {
+    /// Sets the seed of the [CollapseStats].
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.stats.seed = Some(seed);
+        self
+    }
+}
@@ -0,0 +1,465 @@
+//! Collapses large [TileMap2D]s on multiple threads.
+
+use std::{fmt::Debug, num::NonZeroUsize, sync::Mutex, thread};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use vecgrid::Vecgrid;
+
+use crate::error::Result;
+use crate::node::Node;
+use crate::tile2d::wrapping_mode::Cutoff;
+use crate::tile2d::{Index2D, Kernel2D, Neighborhood, Size2D, TileMap2D, TileMap2DConfig};
+use crate::wave_function::{
+    collapse_fully, collapse_wave_where, propagate_collapsed, CollapseConfig, ReadShape, WaveSolver,
+};
+use crate::GenIterReturnResult;
+
+/// The boundary bands between the chunks of a map.
+///
+/// The last `reach` columns and rows of each chunk are part of a band, except for the chunks at
+/// the right and bottom edge of the map, which have no following chunk.
+#[derive(Clone, Copy, Debug)]
+struct Bands {
+    size: Size2D,
+    chunk_size: Size2D,
+    reach_x: u32,
+    reach_y: u32,
+}
+
+impl Bands {
+    fn contains(&self, (x, y): Index2D) -> bool {
+        fn in_band(pos: u32, len: u32, chunk_len: u32, reach: u32) -> bool {
+            let is_last_chunk = (pos / chunk_len + 1) * chunk_len >= len;
+            !is_last_chunk && pos % chunk_len >= chunk_len - reach
+        }
+
+        in_band(x, self.size.width, self.chunk_size.width, self.reach_x)
+            || in_band(y, self.size.height, self.chunk_size.height, self.reach_y)
+    }
+}
+
+/// A chunk with a margin of already collapsed band nodes, that can be collapsed on its own.
+struct Chunk<NodeValue> {
+    /// the position of the top left node of the chunk, including its margin, within the full map.
+    origin: Index2D,
+    size: Size2D,
+    /// the nodes of the chunk, ordered row by row, with ids relative to `origin`.
+    nodes: Vec<Node<Index2D, NodeValue>>,
+    /// the settings of the map for the nodes of the chunk, see [TileMap2D::cropped_config].
+    config: Option<Box<TileMap2DConfig<NodeValue>>>,
+    seed: u64,
+}
+
+impl<NodeValue: Clone> Chunk<NodeValue> {
+    /// copies the nodes of `shape` from `min` up to, but excluding, `max`.
+    fn extract(shape: &TileMap2D<NodeValue>, min: Index2D, max: Index2D, seed: u64) -> Self {
+        let (min_x, min_y) = min;
+        let (max_x, max_y) = max;
+
+        let mut nodes = Vec::new();
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let node = shape
+                    .get_node(&(x, y))
+                    .expect("chunks are within the shape");
+                let chunk_node = Node::new((x - min_x, y - min_y), node.possible_values().to_vec());
+                *chunk_node.is_collapsed.borrow_mut() = node.is_collapsed();
+                nodes.push(chunk_node);
+            }
+        }
+
+        let size = Size2D::new(max_x - min_x, max_y - min_y);
+        Chunk {
+            origin: min,
+            size,
+            nodes,
+            config: shape.cropped_config(min, size),
+            seed,
+        }
+    }
+}
+
+/// returns how far the kernel of `shape` reaches along the x and y axis.
+fn kernel_reach<NodeValue: Clone>(shape: &TileMap2D<NodeValue>) -> (u32, u32) {
+    let kernel_size = *shape.kernel_size();
+    let radius_x = ((kernel_size.width - 1) / 2) as i64;
+    let radius_y = ((kernel_size.height - 1) / 2) as i64;
+    let offsets = shape.neighborhood().offsets(radius_x, radius_y);
+
+    let reach_x = offsets
+        .iter()
+        .map(|(x, _)| x.unsigned_abs() as u32)
+        .max()
+        .unwrap_or(0);
+    let reach_y = offsets
+        .iter()
+        .map(|(_, y)| y.unsigned_abs() as u32)
+        .max()
+        .unwrap_or(0);
+    (reach_x, reach_y)
+}
+
+/// Collapses `chunks` with `collapse` on as many threads as are available and returns the
+/// collapsed values of all chunks.
+fn collapse_chunks<NodeValue, Collapse>(
+    chunks: Vec<Chunk<NodeValue>>,
+    collapse: Collapse,
+) -> Result<Vec<(Index2D, NodeValue)>>
+where
+    NodeValue: Send,
+    Collapse: Fn(Chunk<NodeValue>) -> Result<Vec<(Index2D, NodeValue)>> + Sync,
+{
+    let workers = thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+        .min(chunks.len());
+    let chunks = Mutex::new(chunks);
+
+    let results: Vec<Result<Vec<(Index2D, NodeValue)>>> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut collapsed = Vec::new();
+                    loop {
+                        let chunk = chunks.lock().expect("chunk queue is not poisoned").pop();
+                        match chunk {
+                            Some(chunk) => collapsed.extend(collapse(chunk)?),
+                            None => return Ok(collapsed),
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    });
+
+    let mut collapsed = Vec::new();
+    for result in results {
+        collapsed.extend(result?);
+    }
+    Ok(collapsed)
+}
+
+/// Collapses `shape` by splitting it into chunks of `chunk_size`, which are collapsed on
+/// multiple threads.
+///
+/// The last columns and rows of each chunk, as far as a kernel reaches, form boundary bands
+/// between the chunks. Those bands are collapsed first on the calling thread. Afterwards the
+/// interior of each chunk only depends on itself and the surrounding bands, so the chunks are
+/// collapsed in parallel. Each chunk uses a [StdRng] seeded from `rng`. The chunks keep the
+/// spread, weights and biases of `shape`, but only the collapsed values are returned, so no
+/// prunings are logged, see [TileMap2D::with_pruning_log].
+///
+/// Only [Cutoff] kernels are supported, because wrapping kernels would connect chunks at
+/// opposite edges of the map. `chunk_size` must be larger than the reach of the kernel.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::wave_function::WaveSolver;
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Cutoff};
+/// use wave_collapse::parallel::collapse_parallel;
+/// # struct TestSolver;
+/// # impl WaveSolver<u32, Kernel2D<Cutoff, u32>> for TestSolver {
+/// #     fn is_valid(&self, _value: &u32, _kernel: &Kernel2D<Cutoff, u32>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// let tiles: Vec<u32> = vec![0, 1, 2];
+/// let shape = TileMap2D::new(Size2D::square(1024), Size2D::square(3), &tiles);
+/// let result = collapse_parallel(shape, &TestSolver, Size2D::square(64), &mut rand::thread_rng());
+/// ```
+pub fn collapse_parallel<NodeValue, Solver>(
+    shape: TileMap2D<NodeValue>,
+    solver: &Solver,
+    chunk_size: Size2D,
+    rng: &mut impl Rng,
+) -> Result<Vecgrid<NodeValue>>
+where
+    NodeValue: Clone + PartialEq + Debug + Send,
+    Solver: WaveSolver<NodeValue, Kernel2D<Cutoff, NodeValue>> + Sync,
+{
+    let size = *shape.size();
+    let kernel_size = *shape.kernel_size();
+    let neighborhood = shape.neighborhood().clone();
+    let (reach_x, reach_y) = kernel_reach(&shape);
+
+    let bands = Bands {
+        size,
+        chunk_size,
+        reach_x,
+        reach_y,
+    };
+    assert!(
+        chunk_size.width > bands.reach_x && chunk_size.height > bands.reach_y,
+        "Chunks must be larger than the reach of the kernel"
+    );
+
+    let shape = collapse_wave_where::<_, _, _, Kernel2D<Cutoff, NodeValue>, _, _>(
+        shape,
+        solver,
+        rng,
+        move |id| bands.contains(*id),
+    )
+    .calc_result()?
+    .shape;
+
+    let mut chunks = Vec::new();
+    for chunk_y in (0..size.height).step_by(chunk_size.height as usize) {
+        for chunk_x in (0..size.width).step_by(chunk_size.width as usize) {
+            let min_x = chunk_x.saturating_sub(bands.reach_x);
+            let min_y = chunk_y.saturating_sub(bands.reach_y);
+            let max_x = (chunk_x + chunk_size.width).min(size.width);
+            let max_y = (chunk_y + chunk_size.height).min(size.height);
+            chunks.push(Chunk::extract(
+                &shape,
+                (min_x, min_y),
+                (max_x, max_y),
+                rng.gen(),
+            ));
+        }
+    }
+
+    let collapsed = collapse_chunks(chunks, |chunk| {
+        collapse_chunk(chunk, solver, kernel_size, &neighborhood, bands)
+    })?;
+
+    let mut values: Vec<_> = shape.iter_nodes().map(|node| node.collapsed()).collect();
+    for ((x, y), value) in collapsed {
+        values[(y * size.width + x) as usize] = Some(value);
+    }
+
+    let values = values
+        .into_iter()
+        .collect::<Option<Vec<_>>>()
+        .expect("every node is either part of a band or the interior of a chunk");
+
+    Ok(
+        Vecgrid::from_column_major(values, size.width as usize, size.height as usize)
+            .expect("dimensions should match with the shape"),
+    )
+}
+
+/// Collapses the interior of `chunk` and returns the collapsed values with their ids in the
+/// full map.
+fn collapse_chunk<NodeValue, Solver>(
+    chunk: Chunk<NodeValue>,
+    solver: &Solver,
+    kernel_size: Size2D,
+    neighborhood: &Neighborhood,
+    bands: Bands,
+) -> Result<Vec<(Index2D, NodeValue)>>
+where
+    NodeValue: Clone + PartialEq + Debug,
+    Solver: WaveSolver<NodeValue, Kernel2D<Cutoff, NodeValue>>,
+{
+    let (origin_x, origin_y) = chunk.origin;
+    let to_global = move |(x, y): Index2D| (x + origin_x, y + origin_y);
+
+    let shape = TileMap2D::from_nodes(chunk.size, kernel_size, chunk.nodes)
+        .with_neighborhood(neighborhood.clone())
+        .with_config(chunk.config);
+
+    let mut rng = StdRng::seed_from_u64(chunk.seed);
+    let shape = collapse_wave_where::<_, _, _, Kernel2D<Cutoff, NodeValue>, _, _>(
+        shape,
+        solver,
+        &mut rng,
+        move |id| !bands.contains(to_global(*id)),
+    )
+    .calc_result()?
+    .shape;
+
+    let collapsed = shape
+        .iter_nodes()
+        .filter(|node| !bands.contains(to_global(node.id)))
+        .map(|node| {
+            let value = node
+                .collapsed()
+                .expect("the interior of a collapsed chunk is collapsed");
+            (to_global(node.id), value)
+        })
+        .collect();
+
+    Ok(collapsed)
+}
+
+/// A rectangular part of a [TileMap2D] that can be collapsed independently of the rest of the
+/// map, see [find_sub_maps].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SubMap {
+    /// the position of the top left node of the sub-map within the full map.
+    pub origin: Index2D,
+    pub size: Size2D,
+}
+
+/// returns the ranges of `0..len` that are separated by at least `reach` consecutive closed
+/// positions, trimmed to their first and last open position. `is_open` returns `true` if a
+/// position contains a node that is not collapsed.
+fn open_ranges(len: u32, reach: u32, is_open: impl Fn(u32) -> bool) -> Vec<(u32, u32)> {
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for pos in (0..len).filter(|pos| is_open(*pos)) {
+        match ranges.last_mut() {
+            // the closed positions in between are too few to separate the ranges
+            Some((_, end)) if pos - *end < reach => *end = pos + 1,
+            _ => ranges.push((pos, pos + 1)),
+        }
+    }
+    ranges
+}
+
+/// returns the sub-maps of `shape` that are separated by fully collapsed rows and columns,
+/// row by row. Sub-maps that only contain collapsed nodes are skipped.
+///
+/// A separator must be at least as wide as the kernel reaches, so no kernel of a node in one
+/// sub-map contains a node of another sub-map that is not collapsed. Narrower runs of collapsed
+/// rows or columns are part of a sub-map.
+pub fn find_sub_maps<NodeValue: Clone>(shape: &TileMap2D<NodeValue>) -> Vec<SubMap> {
+    let size = *shape.size();
+    let (reach_x, reach_y) = kernel_reach(shape);
+    let is_open = |x: u32, y: u32| !shape[(x, y)].is_collapsed();
+
+    let rows = open_ranges(size.height, reach_y, |y| {
+        (0..size.width).any(|x| is_open(x, y))
+    });
+    let columns = open_ranges(size.width, reach_x, |x| {
+        (0..size.height).any(|y| is_open(x, y))
+    });
+
+    let mut sub_maps = Vec::new();
+    for &(min_y, max_y) in &rows {
+        for &(min_x, max_x) in &columns {
+            let has_open_node = (min_y..max_y).any(|y| (min_x..max_x).any(|x| is_open(x, y)));
+            if has_open_node {
+                sub_maps.push(SubMap {
+                    origin: (min_x, min_y),
+                    size: Size2D::new(max_x - min_x, max_y - min_y),
+                });
+            }
+        }
+    }
+    sub_maps
+}
+
+/// Collapses `shape` by collapsing each of its [find_sub_maps] on its own, on multiple threads,
+/// and reassembles the results.
+///
+/// The separators between the sub-maps must already be collapsed, e.g. roads or walls authored
+/// with [TileMap2D::from_template]. Each sub-map is collapsed together with the separator nodes
+/// its kernels reach, which are propagated into the sub-map first, see
+/// [crate::wave_function::propagate_collapsed]. Because the sub-maps share no open nodes, no
+/// locking is needed while they are collapsed. Each sub-map uses a [StdRng] seeded from `rng`.
+/// The sub-maps keep the spread, weights and biases of `shape`, but only the collapsed values
+/// are returned, so no prunings are logged, see [TileMap2D::with_pruning_log].
+///
+/// Like [collapse_parallel], only [Cutoff] kernels are supported.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::wave_function::WaveSolver;
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Cutoff};
+/// use wave_collapse::parallel::collapse_sub_maps;
+/// # struct TestSolver;
+/// # impl WaveSolver<char, Kernel2D<Cutoff, char>> for TestSolver {
+/// #     fn is_valid(&self, _value: &char, _kernel: &Kernel2D<Cutoff, char>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// // a road every 32 rows and columns splits the map into blocks
+/// let size = Size2D::square(256);
+/// let template: Vec<_> = (0..size.height)
+///     .flat_map(|y| (0..size.width).map(move |x| (x % 32 == 0 || y % 32 == 0).then_some('=')))
+///     .collect();
+/// let shape = TileMap2D::from_template(size, Size2D::square(3), &template, &['=', '#', '.']);
+/// let result = collapse_sub_maps(shape, &TestSolver, &mut rand::thread_rng());
+/// ```
+pub fn collapse_sub_maps<NodeValue, Solver>(
+    shape: TileMap2D<NodeValue>,
+    solver: &Solver,
+    rng: &mut impl Rng,
+) -> Result<Vecgrid<NodeValue>>
+where
+    NodeValue: Clone + PartialEq + Debug + Send,
+    Solver: WaveSolver<NodeValue, Kernel2D<Cutoff, NodeValue>> + Sync,
+{
+    let size = *shape.size();
+    let kernel_size = *shape.kernel_size();
+    let neighborhood = shape.neighborhood().clone();
+    let (reach_x, reach_y) = kernel_reach(&shape);
+
+    let chunks = find_sub_maps(&shape)
+        .into_iter()
+        .map(|sub_map| {
+            let (x, y) = sub_map.origin;
+            let min = (x.saturating_sub(reach_x), y.saturating_sub(reach_y));
+            let max = (
+                (x + sub_map.size.width + reach_x).min(size.width),
+                (y + sub_map.size.height + reach_y).min(size.height),
+            );
+            Chunk::extract(&shape, min, max, rng.gen())
+        })
+        .collect();
+
+    let collapsed = collapse_chunks(chunks, |chunk| {
+        collapse_sub_map(chunk, solver, kernel_size, &neighborhood)
+    })?;
+
+    let mut values: Vec<_> = shape.iter_nodes().map(|node| node.collapsed()).collect();
+    for ((x, y), value) in collapsed {
+        values[(y * size.width + x) as usize] = Some(value);
+    }
+
+    let values = values
+        .into_iter()
+        .collect::<Option<Vec<_>>>()
+        .expect("every node is either collapsed or part of a sub-map");
+
+    Ok(
+        Vecgrid::from_column_major(values, size.width as usize, size.height as usize)
+            .expect("dimensions should match with the shape"),
+    )
+}
+
+/// Collapses the sub-map in `chunk` and returns all collapsed values with their ids in the full
+/// map.
+fn collapse_sub_map<NodeValue, Solver>(
+    chunk: Chunk<NodeValue>,
+    solver: &Solver,
+    kernel_size: Size2D,
+    neighborhood: &Neighborhood,
+) -> Result<Vec<(Index2D, NodeValue)>>
+where
+    NodeValue: Clone + PartialEq + Debug,
+    Solver: WaveSolver<NodeValue, Kernel2D<Cutoff, NodeValue>>,
+{
+    let (origin_x, origin_y) = chunk.origin;
+
+    let shape = TileMap2D::from_nodes(chunk.size, kernel_size, chunk.nodes)
+        .with_neighborhood(neighborhood.clone())
+        .with_config(chunk.config);
+    let shape = propagate_collapsed::<_, _, _, Kernel2D<Cutoff, NodeValue>, _>(shape, solver)?;
+
+    let rng = StdRng::seed_from_u64(chunk.seed);
+    let shape = collapse_fully::<_, _, _, Kernel2D<Cutoff, NodeValue>, _, _>(
+        shape,
+        solver,
+        CollapseConfig::new(rng),
+    )?
+    .shape;
+
+    let collapsed = shape
+        .iter_nodes()
+        .map(|node| {
+            let value = node.collapsed().expect("a collapsed sub-map is collapsed");
+            ((node.id.0 + origin_x, node.id.1 + origin_y), value)
+        })
+        .collect();
+
+    Ok(collapsed)
+}
@@ -0,0 +1,107 @@
+//! Stamps paths, e.g. roads or corridors, into a [TileMap2D] before it is collapsed.
+//!
+//! The nodes along the path are restricted to a class of values, so the collapse fills in the
+//! exact tiles of the path and everything around it consistently.
+
+use std::collections::HashMap;
+
+use crate::indexed_priority_queue::IndexedPriorityQueue;
+use crate::node::Node;
+use crate::tile2d::{Index2D, TileMap2D};
+use crate::wave_function::ReadShape;
+
+/// returns the shortest path of orthogonal steps from `from` to `to`, including both, that
+/// only crosses nodes for which `passable` returns `true`, or [None] if there is no such path.
+///
+/// The path is found with A*, where each step has a cost of 1.
+pub fn find_path<NodeValue: Clone>(
+    shape: &TileMap2D<NodeValue>,
+    from: Index2D,
+    to: Index2D,
+    passable: impl Fn(&Node<Index2D, NodeValue>) -> bool,
+) -> Option<Vec<Index2D>> {
+    let is_passable = |id: &Index2D| shape.get_node(id).is_some_and(&passable);
+    if !is_passable(&from) || !is_passable(&to) {
+        return None;
+    }
+
+    let heuristic = |(x, y): Index2D| x.abs_diff(to.0) + y.abs_diff(to.1);
+
+    let mut open_list = IndexedPriorityQueue::new();
+    open_list.push(from, heuristic(from));
+    let mut cost: HashMap<Index2D, u32> = HashMap::from([(from, 0)]);
+    let mut previous: HashMap<Index2D, Index2D> = HashMap::new();
+
+    while let Some((id, _)) = open_list.pop() {
+        if id == to {
+            let mut path = vec![to];
+            while let Some(&id) = previous.get(path.last().expect("path is never empty")) {
+                path.push(id);
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        let (x, y) = id;
+        let neighbors = [
+            x.checked_sub(1).map(|x| (x, y)),
+            Some((x + 1, y)),
+            y.checked_sub(1).map(|y| (x, y)),
+            Some((x, y + 1)),
+        ];
+        let neighbor_cost = cost[&id] + 1;
+        for neighbor in neighbors.into_iter().flatten() {
+            if !is_passable(&neighbor) {
+                continue;
+            }
+            if cost
+                .get(&neighbor)
+                .is_some_and(|cost| *cost <= neighbor_cost)
+            {
+                continue;
+            }
+            cost.insert(neighbor, neighbor_cost);
+            previous.insert(neighbor, id);
+            open_list.push(neighbor, neighbor_cost + heuristic(neighbor));
+        }
+    }
+
+    None
+}
+
+/// Restricts the nodes along a path through all `waypoints`, in order, to the values in
+/// `class`, e.g. all road tiles. The path only crosses nodes that can still be a value in
+/// `class`. `shape` must not be collapsed yet.
+///
+/// returns the nodes of the path or [None] if the waypoints can not be connected, in which
+/// case `shape` is not changed.
+///
+/// # Example
+/// ```no_run
+/// use wave_collapse::path::stamp_path;
+/// use wave_collapse::tile2d::{Size2D, TileMap2D};
+///
+/// let tiles = ["grass", "road_straight", "road_corner", "house"];
+/// let shape = TileMap2D::new(Size2D::square(30), Size2D::square(3), &tiles);
+/// let route = stamp_path(&shape, &[(0, 5), (15, 20), (29, 3)], &["road_straight", "road_corner"]);
+/// ```
+pub fn stamp_path<NodeValue: Clone + PartialEq>(
+    shape: &TileMap2D<NodeValue>,
+    waypoints: &[Index2D],
+    class: &[NodeValue],
+) -> Option<Vec<Index2D>> {
+    let passable = |node: &Node<Index2D, NodeValue>| node.any_value(|value| class.contains(value));
+
+    let mut route: Vec<Index2D> = waypoints.first().copied().into_iter().collect();
+    for segment in waypoints.windows(2) {
+        let path = find_path(shape, segment[0], segment[1], passable)?;
+        route.extend(path.into_iter().skip(1));
+    }
+
+    for id in &route {
+        let node = shape.get_node(id).expect("path only contains valid ids");
+        node.with_values_mut(|values| values.retain(0, |value| class.contains(value)));
+    }
+
+    Some(route)
+}
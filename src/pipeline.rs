@@ -0,0 +1,185 @@
+//! Chains multiple collapses of [TileMap2D]s, e.g. a terrain pass followed by a structure pass
+//! and a decoration pass. Each pass derives the possible values of its nodes from the collapsed
+//! values of the previous pass.
+
+use std::{fmt::Debug, rc::Rc};
+
+use crate::error::Result;
+use crate::node::Node;
+use crate::outcome::{CollapseOutcome, CollapseStats};
+use crate::rng::EntropySource;
+use crate::tile2d::{Index2D, Size2D, TileMap2D};
+use crate::wave_function::{collapse_fully, CollapseConfig, ReadShape, WaveKernel, WaveSolver};
+
+/// The result of the passes run so far.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Cutoff};
+/// # use wave_collapse::wave_function::WaveSolver;
+/// use wave_collapse::pipeline::Pipeline;
+/// # #[derive(Clone, Debug, PartialEq)]
+/// # enum Terrain { Land, Water }
+/// # #[derive(Clone, Debug, PartialEq)]
+/// # enum Structure { Empty, House, Bridge }
+/// # struct TerrainSolver;
+/// # impl WaveSolver<Terrain, Kernel2D<Cutoff, Terrain>> for TerrainSolver {
+/// #     fn is_valid(&self, _value: &Terrain, _kernel: &Kernel2D<Cutoff, Terrain>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct StructureSolver;
+/// # impl WaveSolver<Structure, Kernel2D<Cutoff, Structure>> for StructureSolver {
+/// #     fn is_valid(&self, _value: &Structure, _kernel: &Kernel2D<Cutoff, Structure>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// let mut rng = rand::thread_rng();
+/// let terrain = TileMap2D::new(
+///     Size2D::square(50),
+///     Size2D::square(3),
+///     &[Terrain::Land, Terrain::Water],
+/// );
+/// let pipeline = Pipeline::start::<Kernel2D<Cutoff, Terrain>, _>(terrain, &TerrainSolver, &mut rng)
+///     .and_then(|terrain| {
+///         terrain.then::<_, Kernel2D<Cutoff, Structure>, _>(
+///             Size2D::square(3),
+///             |_, terrain| match terrain {
+///                 Terrain::Land => vec![Structure::Empty, Structure::House],
+///                 Terrain::Water => vec![Structure::Empty, Structure::Bridge],
+///             },
+///             &StructureSolver,
+///             &mut rng,
+///         )
+///     });
+/// ```
+#[derive(Debug)]
+pub struct Pipeline<NodeValue: Clone> {
+    shape: Rc<TileMap2D<NodeValue>>,
+    stats: Vec<CollapseStats>,
+}
+
+impl<NodeValue> Pipeline<NodeValue>
+where
+    NodeValue: Clone + PartialEq + Debug,
+{
+    /// Collapses `shape` as the first pass.
+    pub fn start<Kernel, Solver>(
+        shape: TileMap2D<NodeValue>,
+        solver: &Solver,
+        rng: &mut impl EntropySource,
+    ) -> Result<Self>
+    where
+        Kernel: WaveKernel<Index2D, NodeValue, TileMap2D<NodeValue>>,
+        Solver: WaveSolver<NodeValue, Kernel>,
+    {
+        let outcome =
+            collapse_fully::<_, _, _, Kernel, _, _>(shape, solver, CollapseConfig::new(rng))?;
+        Ok(Pipeline {
+            shape: outcome.shape,
+            stats: vec![outcome.stats],
+        })
+    }
+
+    /// Collapses the next pass. The possible values of each node are the values returned by
+    /// `domain` for the position and the collapsed value of the node in the previous pass.
+    ///
+    /// The map of the next pass has none of the settings of the previous pass, e.g. weights,
+    /// because they are for values of a different type. Use [Pipeline::then_configured] to set
+    /// them.
+    pub fn then<Next, Kernel, Solver>(
+        self,
+        kernel_size: Size2D,
+        domain: impl Fn(Index2D, &NodeValue) -> Vec<Next>,
+        solver: &Solver,
+        rng: &mut impl EntropySource,
+    ) -> Result<Pipeline<Next>>
+    where
+        Next: Clone + PartialEq + Debug,
+        Kernel: WaveKernel<Index2D, Next, TileMap2D<Next>>,
+        Solver: WaveSolver<Next, Kernel>,
+    {
+        self.then_configured::<_, Kernel, _>(kernel_size, domain, |shape| shape, solver, rng)
+    }
+
+    /// like [Pipeline::then], but `configure` sets up the map of the next pass before it is
+    /// collapsed, e.g. with [TileMap2D::with_weights] or [TileMap2D::with_bias].
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Cutoff};
+    /// # use wave_collapse::wave_function::WaveSolver;
+    /// # use wave_collapse::pipeline::Pipeline;
+    /// # #[derive(Clone, Debug, PartialEq)]
+    /// # enum Terrain { Land, Water }
+    /// # #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+    /// # enum Structure { Empty, House }
+    /// # struct StructureSolver;
+    /// # impl WaveSolver<Structure, Kernel2D<Cutoff, Structure>> for StructureSolver {
+    /// #     fn is_valid(&self, _value: &Structure, _kernel: &Kernel2D<Cutoff, Structure>) -> bool {
+    /// #         todo!()
+    /// #     }
+    /// # }
+    /// # let terrain: Pipeline<Terrain> = todo!();
+    /// let mut rng = rand::thread_rng();
+    /// // houses are rare
+    /// let structures = terrain.then_configured::<_, Kernel2D<Cutoff, Structure>, _>(
+    ///     Size2D::square(3),
+    ///     |_, _| vec![Structure::Empty, Structure::House],
+    ///     |shape| shape.with_weights(&[(Structure::Empty, 9.0), (Structure::House, 1.0)]),
+    ///     &StructureSolver,
+    ///     &mut rng,
+    /// );
+    /// ```
+    pub fn then_configured<Next, Kernel, Solver>(
+        self,
+        kernel_size: Size2D,
+        domain: impl Fn(Index2D, &NodeValue) -> Vec<Next>,
+        configure: impl FnOnce(TileMap2D<Next>) -> TileMap2D<Next>,
+        solver: &Solver,
+        rng: &mut impl EntropySource,
+    ) -> Result<Pipeline<Next>>
+    where
+        Next: Clone + PartialEq + Debug,
+        Kernel: WaveKernel<Index2D, Next, TileMap2D<Next>>,
+        Solver: WaveSolver<Next, Kernel>,
+    {
+        let size = *self.shape.size();
+        let nodes = self
+            .shape
+            .iter_nodes()
+            .map(|node| {
+                let value = node.collapsed().expect("previous pass is collapsed");
+                Node::new(node.id, domain(node.id, &value))
+            })
+            .collect();
+        let shape = configure(TileMap2D::from_nodes(size, kernel_size, nodes));
+
+        let outcome =
+            collapse_fully::<_, _, _, Kernel, _, _>(shape, solver, CollapseConfig::new(rng))?;
+        let mut stats = self.stats;
+        stats.push(outcome.stats);
+        Ok(Pipeline {
+            shape: outcome.shape,
+            stats,
+        })
+    }
+
+    /// returns the collapsed shape of the last pass.
+    pub fn shape(&self) -> &Rc<TileMap2D<NodeValue>> {
+        &self.shape
+    }
+
+    /// returns the [CollapseStats] of each pass.
+    pub fn stats(&self) -> &[CollapseStats] {
+        &self.stats
+    }
+
+    /// returns the [CollapseOutcome] of the last pass.
+    pub fn finish(self) -> CollapseOutcome<TileMap2D<NodeValue>> {
+        CollapseOutcome {
+            shape: self.shape,
+            stats: *self.stats.last().expect("there is at least one pass"),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Progressive refinement of [TileMap2D]s, e.g. to show a quick preview in an editor before the
+//! full collapse is finished.
+//!
+//! The preview collapses only every `stride`th node in both directions. The other nodes are
+//! still constrained by propagation, so the full collapse continues from the preview without
+//! contradicting it. Once the preview is finished, its shape is no longer shared and can be
+//! passed to [crate::collapse_wave].
+//!
+//! # Example
+//! ```no_run
+//! # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Cutoff};
+//! # use wave_collapse::wave_function::WaveSolver;
+//! use std::rc::Rc;
+//! use wave_collapse::{collapse_wave, GenIterReturnResult};
+//! use wave_collapse::progressive::{collapse_preview, preview_values};
+//! # struct TestSolver;
+//! # impl WaveSolver<u32, Kernel2D<Cutoff, u32>> for TestSolver {
+//! #     fn is_valid(&self, _value: &u32, _kernel: &Kernel2D<Cutoff, u32>) -> bool {
+//! #         todo!()
+//! #     }
+//! # }
+//! let mut rng = rand::thread_rng();
+//! let shape = TileMap2D::new(Size2D::square(256), Size2D::square(3), &[0u32, 1, 2]);
+//!
+//! let preview = collapse_preview::<_, Kernel2D<Cutoff, u32>, _>(shape, &TestSolver, &mut rng, 8)
+//!     .calc_result()
+//!     .expect("preview collapsed");
+//! let proxy = preview_values(&preview.shape, 8);
+//! // show_preview(&proxy);
+//!
+//! let shape = Rc::try_unwrap(preview.shape).unwrap_or_else(|_| panic!("preview is not shared"));
+//! let result = collapse_wave::<_, _, _, Kernel2D<Cutoff, u32>, _>(shape, &TestSolver, &mut rng)
+//!     .calc_result();
+//! ```
+
+use std::{fmt::Debug, ops::Generator, rc::Rc};
+
+use gen_iter::GenIterReturn;
+
+use crate::error::Result;
+use crate::outcome::CollapseOutcome;
+use crate::rng::EntropySource;
+use crate::tile2d::{Index2D, Size2D, TileMap2D};
+use crate::wave_function::{collapse_wave_where, WaveKernel, WaveSolver};
+
+/// returns `true` if the node `id` is part of the preview with `stride`.
+pub fn is_preview_node(id: &Index2D, stride: u32) -> bool {
+    id.0 % stride == 0 && id.1 % stride == 0
+}
+
+/// returns the size of the preview of a map of `size` with `stride`.
+pub fn preview_size(size: Size2D, stride: u32) -> Size2D {
+    Size2D::new(size.width.div_ceil(stride), size.height.div_ceil(stride))
+}
+
+/// like [crate::collapse_wave], but only the nodes of the preview with `stride` are collapsed,
+/// see [is_preview_node]. `stride` must not be 0.
+pub fn collapse_preview<'solver, NodeValue, Kernel, Solver>(
+    shape: TileMap2D<NodeValue>,
+    solver: &'solver Solver,
+    rng: &'solver mut impl EntropySource,
+    stride: u32,
+) -> GenIterReturn<
+    impl Generator<
+            Yield = Rc<TileMap2D<NodeValue>>,
+            Return = Result<CollapseOutcome<TileMap2D<NodeValue>>>,
+        > + 'solver,
+>
+where
+    NodeValue: Clone + PartialEq + Debug + 'solver,
+    Kernel: WaveKernel<Index2D, NodeValue, TileMap2D<NodeValue>>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+{
+    assert!(stride > 0, "stride must not be 0");
+    collapse_wave_where::<_, _, _, Kernel, _, _>(shape, solver, rng, move |id| {
+        is_preview_node(id, stride)
+    })
+}
+
+/// returns the collapsed values of the preview nodes of `shape` row by row, so the value of the
+/// preview cell `(x, y)` is at `y * width + x`, where the width is the width of the
+/// [preview_size]. Nodes that are not collapsed are [None].
+pub fn preview_values<NodeValue: Clone>(
+    shape: &TileMap2D<NodeValue>,
+    stride: u32,
+) -> Vec<Option<NodeValue>> {
+    let size = preview_size(*shape.size(), stride);
+    let mut values = Vec::with_capacity((size.width * size.height) as usize);
+    for y in 0..size.height {
+        for x in 0..size.width {
+            values.push(shape[(x * stride, y * stride)].collapsed());
+        }
+    }
+    values
+}
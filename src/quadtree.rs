@@ -0,0 +1,268 @@
+//! A quadtree backed shape, where uniform regions are represented by a single large node and
+//! only detailed regions are subdivided into smaller nodes.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::domain::Domain;
+use crate::node::{DirectedNodeIter, Node, NodeIdIter};
+use crate::wave_function::{ReadShape, WaveKernel, WaveShape};
+
+/// The offsets of the orthogonal neighbors within a [QuadKernel].
+const OFFSETS: [(i64, i64); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+/// The id of a node in a [QuadTreeMap]. `x` and `y` are the top left corner and `size` is the
+/// width and height of the node, measured in the smallest possible node size of 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct QuadIndex {
+    pub x: u32,
+    pub y: u32,
+    pub size: u32,
+}
+
+impl QuadIndex {
+    pub fn new(x: u32, y: u32, size: u32) -> Self {
+        QuadIndex { x, y, size }
+    }
+
+    /// returns `true` if the point `(x, y)` is within this node.
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && x < self.x + self.size && y >= self.y && y < self.y + self.size
+    }
+}
+
+/// A square map of `size * size` cells, where each node covers a square of cells.
+pub struct QuadTreeMap<NodeValue: Clone> {
+    size: u32,
+
+    last_collapsed: RefCell<Option<QuadIndex>>,
+
+    nodes: Vec<Node<QuadIndex, NodeValue>>,
+    indices: HashMap<QuadIndex, usize>,
+}
+
+impl<NodeValue: Clone> QuadTreeMap<NodeValue> {
+    /// Create a new [QuadTreeMap]. `size` and `min_node_size` must be powers of 2.
+    /// A node is subdivided into 4 nodes of half its size as long as `subdivide` returns `true`
+    /// and the nodes would not be smaller than `min_node_size`.
+    /// `possible_values` must not be empty.
+    pub fn new<Subdivide>(
+        size: u32,
+        min_node_size: u32,
+        subdivide: Subdivide,
+        possible_values: &[NodeValue],
+    ) -> Self
+    where
+        Subdivide: Fn(QuadIndex) -> bool,
+    {
+        assert!(size.is_power_of_two(), "Size must be a power of 2");
+        assert!(
+            min_node_size.is_power_of_two() && min_node_size <= size,
+            "Min node size must be a power of 2 and not larger than size"
+        );
+        assert!(!possible_values.is_empty(), "At least one value required!");
+
+        let mut nodes = Vec::new();
+        let mut indices = HashMap::new();
+
+        let mut open_list = vec![QuadIndex::new(0, 0, size)];
+        while let Some(id) = open_list.pop() {
+            if id.size > min_node_size && subdivide(id) {
+                let half = id.size / 2;
+                open_list.push(QuadIndex::new(id.x + half, id.y + half, half));
+                open_list.push(QuadIndex::new(id.x, id.y + half, half));
+                open_list.push(QuadIndex::new(id.x + half, id.y, half));
+                open_list.push(QuadIndex::new(id.x, id.y, half));
+            } else {
+                indices.insert(id, nodes.len());
+                nodes.push(Node::new(id, possible_values));
+            }
+        }
+
+        QuadTreeMap {
+            size,
+            last_collapsed: RefCell::new(None),
+            nodes,
+            indices,
+        }
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// returns the id of the node containing the cell `(x, y)`.
+    pub fn node_at(&self, x: u32, y: u32) -> Option<QuadIndex> {
+        if x >= self.size || y >= self.size {
+            return None;
+        }
+
+        let mut id = QuadIndex::new(0, 0, self.size);
+        loop {
+            if self.indices.contains_key(&id) {
+                return Some(id);
+            }
+            let half = id.size / 2;
+            if half == 0 {
+                return None;
+            }
+            id = QuadIndex::new(
+                if x >= id.x + half { id.x + half } else { id.x },
+                if y >= id.y + half { id.y + half } else { id.y },
+                half,
+            );
+        }
+    }
+
+    /// returns the ids of all nodes touching the side of `id` in the direction `offset`.
+    /// `offset` must be an orthogonal offset of length 1.
+    pub fn neighbors(&self, id: QuadIndex, offset: (i64, i64)) -> Vec<QuadIndex> {
+        assert!(
+            OFFSETS.contains(&offset),
+            "only orthogonal offsets are supported"
+        );
+
+        // the row or column of cells just outside of `id`
+        let outside = match offset {
+            (1, 0) => id.x as i64 + id.size as i64,
+            (-1, 0) => id.x as i64 - 1,
+            (0, 1) => id.y as i64 + id.size as i64,
+            _ => id.y as i64 - 1,
+        };
+        if outside < 0 || outside >= self.size as i64 {
+            return Vec::new();
+        }
+        let outside = outside as u32;
+        let horizontal = offset.0 != 0;
+
+        let (start, end) = if horizontal {
+            (id.y, id.y + id.size)
+        } else {
+            (id.x, id.x + id.size)
+        };
+
+        let mut result = Vec::new();
+        let mut position = start;
+        while position < end {
+            let neighbor = if horizontal {
+                self.node_at(outside, position)
+            } else {
+                self.node_at(position, outside)
+            }
+            .expect("every cell within the map belongs to a node");
+
+            result.push(neighbor);
+            let neighbor_start = if horizontal { neighbor.y } else { neighbor.x };
+            position = neighbor_start + neighbor.size;
+        }
+        result
+    }
+}
+
+impl<NodeValue> ReadShape<QuadIndex, NodeValue> for QuadTreeMap<NodeValue>
+where
+    NodeValue: Clone,
+{
+    fn get_node(&self, id: &QuadIndex) -> Option<&Node<QuadIndex, NodeValue>> {
+        self.indices.get(id).map(|index| &self.nodes[*index])
+    }
+
+    fn iter_node_ids(&self) -> NodeIdIter<QuadIndex> {
+        let ids: Vec<_> = self.nodes.iter().map(|node| node.id).collect();
+        ids.into_iter()
+    }
+
+    fn get_last_collapsed_id(&self) -> Option<QuadIndex> {
+        *self.last_collapsed.borrow()
+    }
+}
+
+impl<NodeValue> WaveShape<QuadIndex, NodeValue> for QuadTreeMap<NodeValue>
+where
+    NodeValue: Clone,
+{
+    fn set_last_collapsed_id(&self, node_id: QuadIndex) {
+        let _ = self.last_collapsed.borrow_mut().insert(node_id);
+    }
+}
+
+/// A kernel containing a node of a [QuadTreeMap] and all nodes touching one of its sides.
+/// A side can have multiple smaller neighbors or share a larger neighbor with other nodes.
+pub struct QuadKernel<NodeValueDescription: Clone> {
+    shape: Rc<QuadTreeMap<NodeValueDescription>>,
+    node_id: QuadIndex,
+}
+
+impl<NodeValueDescription: Clone> QuadKernel<NodeValueDescription> {
+    /// returns all nodes touching the side of the center node in the direction `(dx, dy)`.
+    pub fn get_side(&self, dx: i64, dy: i64) -> Vec<&Node<QuadIndex, NodeValueDescription>> {
+        self.shape
+            .neighbors(self.node_id, (dx, dy))
+            .iter()
+            .filter_map(|id| self.shape.get_node(id))
+            .collect()
+    }
+}
+
+impl<NodeValueDescription: Clone>
+    WaveKernel<QuadIndex, NodeValueDescription, QuadTreeMap<NodeValueDescription>>
+    for QuadKernel<NodeValueDescription>
+{
+    /// the offset of the side in the local frame of the center node.
+    type Direction = (i64, i64);
+
+    fn new(
+        shape: Rc<QuadTreeMap<NodeValueDescription>>,
+        node: &Node<QuadIndex, NodeValueDescription>,
+    ) -> Self {
+        QuadKernel {
+            shape,
+            node_id: node.id,
+        }
+    }
+
+    fn center_id(&self) -> QuadIndex {
+        self.node_id
+    }
+
+    fn center(&self) -> &Node<QuadIndex, NodeValueDescription> {
+        self.shape
+            .get_node(&self.node_id)
+            .expect("the kernel is created from a valid node")
+    }
+
+    fn iter_node_ids(&self) -> NodeIdIter<QuadIndex> {
+        let mut ids = vec![self.node_id];
+        ids.extend(self.iter_node_ids_without_center());
+        ids.into_iter()
+    }
+
+    fn iter_node_ids_without_center(&self) -> NodeIdIter<QuadIndex> {
+        let mut ids = Vec::new();
+        for offset in OFFSETS {
+            ids.extend(self.shape.neighbors(self.node_id, offset));
+        }
+        ids.into_iter()
+    }
+
+    fn iter_directed(
+        &self,
+    ) -> DirectedNodeIter<
+        '_,
+        (i64, i64),
+        QuadIndex,
+        NodeValueDescription,
+        Domain<NodeValueDescription>,
+    > {
+        let mut nodes = Vec::new();
+        for offset in OFFSETS {
+            nodes.extend(
+                self.get_side(offset.0, offset.1)
+                    .into_iter()
+                    .map(|node| (offset, node)),
+            );
+        }
+        nodes.into_iter()
+    }
+}
@@ -0,0 +1,182 @@
+//! Diagnostic rendering of [TileMap2D]s, that makes rule bugs visible.
+//!
+//! Edge matching tilesets describe each tile by the sockets on its edges and only allow tiles
+//! next to each other if the touching sockets match. [write_edge_svg] draws each tile with its
+//! sockets and highlights every pair of neighbors whose sockets do not match in red, so a wrong
+//! rule shows up as a red seam instead of a subtly wrong map.
+
+use std::{fmt::Display, io};
+
+use crate::tile2d::{Direction, Index2D, TileMap2D};
+
+/// Two neighbors whose touching sockets do not match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EdgeMismatch {
+    /// the first node.
+    pub id: Index2D,
+    /// the direction from `id` to the neighbor, either [Direction::Right] or
+    /// [Direction::Bottom].
+    pub direction: Direction,
+}
+
+impl EdgeMismatch {
+    /// returns the id of the neighbor.
+    pub fn neighbor(&self) -> Index2D {
+        let (x, y) = self.direction.offset();
+        ((self.id.0 as i64 + x) as u32, (self.id.1 as i64 + y) as u32)
+    }
+}
+
+/// returns all pairs of orthogonal neighbors in `shape` whose sockets do not match, row by row.
+/// `socket` returns the socket of a value on the edge in a [Direction]. The sockets of two
+/// neighbors match if they are equal. Nodes that are not collapsed are ignored.
+pub fn find_edge_mismatches<NodeValue, Socket>(
+    shape: &TileMap2D<NodeValue>,
+    socket: impl Fn(&NodeValue, Direction) -> Socket,
+) -> Vec<EdgeMismatch>
+where
+    NodeValue: Clone,
+    Socket: PartialEq,
+{
+    let size = *shape.size();
+    let mut mismatches = Vec::new();
+    for y in 0..size.height {
+        for x in 0..size.width {
+            let Some(value) = shape[(x, y)].collapsed() else {
+                continue;
+            };
+            for direction in [Direction::Right, Direction::Bottom] {
+                let mismatch = EdgeMismatch {
+                    id: (x, y),
+                    direction,
+                };
+                let (nx, ny) = mismatch.neighbor();
+                let Some(neighbor) = shape.get(nx, ny).and_then(|node| node.collapsed()) else {
+                    continue;
+                };
+                if socket(&value, direction) != socket(&neighbor, direction.opposite()) {
+                    mismatches.push(mismatch);
+                }
+            }
+        }
+    }
+    mismatches
+}
+
+/// Draws `shape` as SVG, each node as a square of `cell_size` pixels with the `label` of its
+/// value in the center and the `socket` of each orthogonal edge next to that edge. Edges between
+/// neighbors whose sockets do not match are drawn in red, see [find_edge_mismatches]. Nodes that
+/// are not collapsed are drawn in gray.
+///
+/// returns the mismatches that were highlighted.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::tile2d::{Size2D, TileMap2D};
+/// use std::fs::File;
+/// use wave_collapse::render::write_edge_svg;
+/// use wave_collapse::tile2d::Direction;
+/// # let shape = TileMap2D::new(Size2D::square(10), Size2D::square(3), &['-', '|', '+']);
+/// let socket = |tile: &char, direction: Direction| match (*tile, direction) {
+///     ('-', Direction::Left | Direction::Right) => "road",
+///     ('|', Direction::Top | Direction::Bottom) => "road",
+///     ('+', _) => "road",
+///     _ => "grass",
+/// };
+/// let file = File::create("sockets.svg").unwrap();
+/// let mismatches = write_edge_svg(file, &shape, 48, socket, |tile| tile.to_string()).unwrap();
+/// assert!(mismatches.is_empty(), "{mismatches:?}");
+/// ```
+pub fn write_edge_svg<NodeValue, Socket, W>(
+    mut writer: W,
+    shape: &TileMap2D<NodeValue>,
+    cell_size: u32,
+    socket: impl Fn(&NodeValue, Direction) -> Socket,
+    label: impl Fn(&NodeValue) -> String,
+) -> io::Result<Vec<EdgeMismatch>>
+where
+    NodeValue: Clone,
+    Socket: PartialEq + Display,
+    W: io::Write,
+{
+    let size = *shape.size();
+    let cell = cell_size as f32;
+    let font_size = cell / 5.0;
+
+    writeln!(
+        writer,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="monospace" text-anchor="middle" dominant-baseline="middle">"#,
+        size.width * cell_size,
+        size.height * cell_size
+    )?;
+
+    for y in 0..size.height {
+        for x in 0..size.width {
+            let (left, top) = (x as f32 * cell, y as f32 * cell);
+            let value = shape[(x, y)].collapsed();
+            let fill = if value.is_some() {
+                "white"
+            } else {
+                "lightgray"
+            };
+            writeln!(
+                writer,
+                r#"<rect x="{left}" y="{top}" width="{cell}" height="{cell}" fill="{fill}" stroke="black" stroke-width="0.5"/>"#
+            )?;
+
+            let Some(value) = value else {
+                continue;
+            };
+            let (center_x, center_y) = (left + cell / 2.0, top + cell / 2.0);
+            writeln!(
+                writer,
+                r#"<text x="{center_x}" y="{center_y}" font-size="{}">{}</text>"#,
+                font_size * 1.5,
+                xml_escape(&label(&value))
+            )?;
+            for direction in Direction::ORTHOGONAL {
+                let (dx, dy) = direction.offset();
+                let socket_x = center_x + dx as f32 * cell * 0.35;
+                let socket_y = center_y + dy as f32 * cell * 0.35;
+                writeln!(
+                    writer,
+                    r#"<text x="{socket_x}" y="{socket_y}" font-size="{font_size}" fill="dimgray">{}</text>"#,
+                    xml_escape(&socket(&value, direction).to_string())
+                )?;
+            }
+        }
+    }
+
+    let mismatches = find_edge_mismatches(shape, &socket);
+    for mismatch in &mismatches {
+        let (x, y) = (mismatch.id.0 as f32 * cell, mismatch.id.1 as f32 * cell);
+        let (x1, y1, x2, y2) = match mismatch.direction {
+            Direction::Right => (x + cell, y, x + cell, y + cell),
+            _ => (x, y + cell, x + cell, y + cell),
+        };
+        writeln!(
+            writer,
+            r#"<line x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}" stroke="red" stroke-width="{}"/>"#,
+            cell / 10.0
+        )?;
+    }
+
+    writeln!(writer, "</svg>")?;
+    writer.flush()?;
+    Ok(mismatches)
+}
+
+/// returns `value` with the characters that have a special meaning in XML escaped.
+fn xml_escape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
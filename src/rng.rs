@@ -0,0 +1,273 @@
+//! Random number helpers, that make the collapse reproducible.
+//!
+//! Each step of [crate::collapse_wave] makes exactly 2 calls to [EntropySource::next_u64]: one to
+//! choose the node with the lowest entropy and one to choose the value of that node, see
+//! [EntropySource::choose_node] and [EntropySource::choose_value]. Given the same seed, a
+//! [DeterministicRng], the same initial shape and a deterministic
+//! [crate::wave_function::WaveSolver], the result is the same across crate versions and
+//! platforms.
+//! This only holds for the default implementation of
+//! [crate::wave_function::WaveShape::choose_random_with_lowest_entropy_where]. Changes to the
+//! number or order of these calls are considered breaking changes.
+//!
+//! No decision depends on the iteration order of a [std::collections::HashMap] or
+//! [std::collections::HashSet], which differs between platforms and runs. Hash containers are
+//! only used for lookups, nodes are visited in the order of
+//! [crate::wave_function::ReadShape::iter_node_ids] and ties in priority queues are broken by
+//! insertion order. Custom shapes and solvers need to follow the same rules to keep this
+//! guarantee. Use [crate::testing::assert_golden_deterministic] to test it.
+//!
+//! Weighted shapes keep the guarantee, because weights only pass through float operations that
+//! IEEE 754 rounds exactly. [crate::node::Node::weighted_entropy] does not use [f32::ln], whose
+//! precision differs between platforms.
+
+use std::hash::{Hash, Hasher};
+
+#[cfg(feature = "rand")]
+use rand::RngCore;
+
+use crate::digest::Fnv1aHasher;
+
+/// The source of all random decisions of the collapse.
+///
+/// With the `rand` feature this is implemented for every [rand::RngCore], so any rng of [rand]
+/// can be used. Without it, [SplitMix64] or a custom implementation can be used, e.g. to replay
+/// recorded decisions.
+///
+/// The collapse makes two kinds of random decisions: which of the nodes with the lowest entropy
+/// is collapsed next and which value it is collapsed to. Each is made by its own method, so a
+/// test can replace one while the other stays random, see [SplitDecisions].
+pub trait EntropySource {
+    /// returns the next random number. All 64 bits must be random.
+    fn next_u64(&mut self) -> u64;
+
+    /// returns the index of the node to collapse next, out of `len` nodes with the lowest
+    /// entropy, or [None] if there is no node to choose. `weights` contains the weight of each
+    /// node, if the shape prefers some of them, e.g. [crate::tile2d::TileMap2D::with_spread].
+    ///
+    /// The default implementation uses [weighted_index] or [random_index].
+    fn choose_node(&mut self, len: usize, weights: Option<&[f32]>) -> Option<usize> {
+        match weights {
+            Some(weights) => weighted_index(self, weights),
+            None => random_index(self, len),
+        }
+    }
+
+    /// returns the index of the value a node is collapsed to, out of its `len` possible values,
+    /// or [None] if there is no value to choose. `weights` contains the weight of each value,
+    /// if the shape is weighted.
+    ///
+    /// The default implementation uses [weighted_index] or [random_index].
+    fn choose_value(&mut self, len: usize, weights: Option<&[f32]>) -> Option<usize> {
+        match weights {
+            Some(weights) => weighted_index(self, weights),
+            None => random_index(self, len),
+        }
+    }
+}
+
+#[cfg(feature = "rand")]
+impl<R: RngCore + ?Sized> EntropySource for R {
+    fn next_u64(&mut self) -> u64 {
+        RngCore::next_u64(self)
+    }
+}
+
+/// An [EntropySource] that makes the node decisions with `nodes` and the value decisions with
+/// `values`, see [EntropySource::choose_node] and [EntropySource::choose_value]. All other random
+/// numbers are taken from `nodes`.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Cutoff};
+/// # use wave_collapse::wave_function::WaveSolver;
+/// use wave_collapse::collapse_wave;
+/// use wave_collapse::rng::{FixedChoice, SplitDecisions};
+/// # struct TestSolver;
+/// # impl WaveSolver<u32, Kernel2D<Cutoff, u32>> for TestSolver {
+/// #     fn is_valid(&self, _value: &u32, _kernel: &Kernel2D<Cutoff, u32>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// // random nodes, but always the first possible value
+/// let mut rng = SplitDecisions::new(rand::thread_rng(), FixedChoice(0));
+/// let shape = TileMap2D::new(Size2D::square(10), Size2D::square(3), &[0u32, 1, 2]);
+/// let result = collapse_wave::<_, _, _, Kernel2D<Cutoff, u32>, _>(shape, &TestSolver, &mut rng)
+///     .calc_result();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitDecisions<Nodes, Values> {
+    pub nodes: Nodes,
+    pub values: Values,
+}
+
+impl<Nodes: EntropySource, Values: EntropySource> SplitDecisions<Nodes, Values> {
+    pub fn new(nodes: Nodes, values: Values) -> Self {
+        SplitDecisions { nodes, values }
+    }
+}
+
+impl<Nodes: EntropySource, Values: EntropySource> EntropySource for SplitDecisions<Nodes, Values> {
+    fn next_u64(&mut self) -> u64 {
+        self.nodes.next_u64()
+    }
+
+    fn choose_node(&mut self, len: usize, weights: Option<&[f32]>) -> Option<usize> {
+        self.nodes.choose_node(len, weights)
+    }
+
+    fn choose_value(&mut self, len: usize, weights: Option<&[f32]>) -> Option<usize> {
+        self.values.choose_value(len, weights)
+    }
+}
+
+/// An [EntropySource] that always makes the same decision, e.g. to pin one kind of decision
+/// in a test, see [SplitDecisions]. It chooses the contained index or the last one, if there
+/// are fewer choices. Weights are ignored and [EntropySource::next_u64] always returns 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedChoice(pub usize);
+
+impl EntropySource for FixedChoice {
+    fn next_u64(&mut self) -> u64 {
+        0
+    }
+
+    fn choose_node(&mut self, len: usize, _weights: Option<&[f32]>) -> Option<usize> {
+        len.checked_sub(1).map(|last| self.0.min(last))
+    }
+
+    fn choose_value(&mut self, len: usize, _weights: Option<&[f32]>) -> Option<usize> {
+        self.choose_node(len, None)
+    }
+}
+
+/// returns a random index in `0..len` or [None] if `len` is 0.
+///
+/// This makes exactly one call to [EntropySource::next_u64], independent of `len` and the
+/// platform. The result is slightly biased for very large `len`, which is irrelevant for the
+/// number of values or nodes to choose from.
+pub fn random_index(rng: &mut (impl EntropySource + ?Sized), len: usize) -> Option<usize> {
+    if len == 0 {
+        return None;
+    }
+    let random = rng.next_u64() as u128;
+    Some(((random * len as u128) >> 64) as usize)
+}
+
+/// returns a random index in `0..weights.len()`, where each index is as likely as its weight,
+/// or [None] if the sum of the weights is not positive.
+///
+/// Like [random_index] this makes exactly one call to [EntropySource::next_u64].
+pub fn weighted_index(rng: &mut (impl EntropySource + ?Sized), weights: &[f32]) -> Option<usize> {
+    let random = rng.next_u64();
+    let total: f64 = weights.iter().map(|&weight| f64::from(weight)).sum();
+    if total <= 0.0 || weights.is_empty() {
+        return None;
+    }
+
+    // the upper 53 bits fill the mantissa of an f64 in 0..1
+    let mut target = (random >> 11) as f64 / (1u64 << 53) as f64 * total;
+    for (index, &weight) in weights.iter().enumerate() {
+        target -= f64::from(weight);
+        if target < 0.0 {
+            return Some(index);
+        }
+    }
+    // rounding can leave a tiny rest, which belongs to the last value with a weight
+    weights.iter().rposition(|&weight| weight > 0.0)
+}
+
+/// returns an rng for the decisions about `key`, e.g. a node id, that only depends on `seed` and
+/// `key`. Decisions made with substreams do not change if other decisions are added, removed or
+/// reordered.
+pub fn substream(seed: u64, key: &impl Hash) -> SplitMix64 {
+    let mut hasher = Fnv1aHasher::default();
+    seed.hash(&mut hasher);
+    key.hash(&mut hasher);
+    SplitMix64(hasher.finish())
+}
+
+/// A small and fast rng, used for [substream]s. This is not suitable for cryptography.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        SplitMix64(seed)
+    }
+
+    /// returns the next random number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// With the `rand` feature [SplitMix64] is an [EntropySource] because it is a [rand::RngCore].
+#[cfg(not(feature = "rand"))]
+impl EntropySource for SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        SplitMix64::next_u64(self)
+    }
+}
+
+#[cfg(feature = "rand")]
+impl RngCore for SplitMix64 {
+    fn next_u32(&mut self) -> u32 {
+        (SplitMix64::next_u64(self) >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        SplitMix64::next_u64(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = SplitMix64::next_u64(self).to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// A seedable rng that produces the same values across platforms and versions of this crate.
+///
+/// This wraps a [rand_chacha::ChaCha8Rng], because the algorithm behind [rand::rngs::StdRng] is
+/// allowed to change between versions of [rand].
+#[cfg(feature = "deterministic")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeterministicRng(rand_chacha::ChaCha8Rng);
+
+#[cfg(feature = "deterministic")]
+impl DeterministicRng {
+    pub fn new(seed: u64) -> Self {
+        use rand::SeedableRng;
+        DeterministicRng(rand_chacha::ChaCha8Rng::seed_from_u64(seed))
+    }
+}
+
+#[cfg(feature = "deterministic")]
+impl RngCore for DeterministicRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.0.try_fill_bytes(dest)
+    }
+}
@@ -0,0 +1,130 @@
+//! Searches seeds for results that score well on a set of objectives, automating the common
+//! "generate until it looks good" loop.
+
+use std::{fmt::Debug, hash::Hash};
+
+use crate::outcome::CollapseOutcome;
+use crate::rng::EntropySource;
+use crate::wave_function::{collapse_fully, CollapseConfig, WaveKernel, WaveShape, WaveSolver};
+
+/// A weighted set of objectives used to score collapsed shapes. The score of a shape is the
+/// weighted sum of all objectives, higher scores are better.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Cutoff};
+/// # use wave_collapse::wave_function::WaveSolver;
+/// use rand::{rngs::StdRng, SeedableRng};
+/// use wave_collapse::search::SeedSearch;
+/// # #[derive(Clone, Debug, PartialEq)]
+/// # enum Tile { Land, Water }
+/// # struct TestSolver;
+/// # impl WaveSolver<Tile, Kernel2D<Cutoff, Tile>> for TestSolver {
+/// #     fn is_valid(&self, _value: &Tile, _kernel: &Kernel2D<Cutoff, Tile>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// let search = SeedSearch::new()
+///     // prefer maps that are about 30% water
+///     .with_objective(1.0, |shape: &TileMap2D<Tile>| {
+///         let water = shape.iter().filter(|(_, tile)| **tile == Tile::Water).count();
+///         let ratio = water as f64 / 2500.0;
+///         -(ratio - 0.3).abs()
+///     });
+/// let best = search.run::<_, _, Kernel2D<Cutoff, Tile>, _, _>(
+///     || TileMap2D::new(Size2D::square(50), Size2D::square(3), &[Tile::Land, Tile::Water]),
+///     &TestSolver,
+///     0..100,
+///     StdRng::seed_from_u64,
+/// );
+/// if let Some(best) = best {
+///     println!("seed {} scored {}", best.seed, best.score);
+/// }
+/// ```
+pub struct SeedSearch<'a, Shape> {
+    objectives: Vec<(f64, Box<dyn Fn(&Shape) -> f64 + 'a>)>,
+}
+
+/// The best result found by [SeedSearch::run].
+#[derive(Debug)]
+pub struct SeedResult<Shape> {
+    /// the seed the rng was created with.
+    pub seed: u64,
+    /// the weighted score of the result.
+    pub score: f64,
+    /// the collapsed shape.
+    pub outcome: CollapseOutcome<Shape>,
+}
+
+impl<Shape> Default for SeedSearch<'_, Shape> {
+    fn default() -> Self {
+        SeedSearch {
+            objectives: Vec::new(),
+        }
+    }
+}
+
+impl<'a, Shape> SeedSearch<'a, Shape> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an `objective` whose result is multiplied by `weight`. Use a negative weight for
+    /// objectives that should be minimized.
+    pub fn with_objective(mut self, weight: f64, objective: impl Fn(&Shape) -> f64 + 'a) -> Self {
+        self.objectives.push((weight, Box::new(objective)));
+        self
+    }
+
+    /// returns the weighted score of `shape`.
+    pub fn score(&self, shape: &Shape) -> f64 {
+        self.objectives
+            .iter()
+            .map(|(weight, objective)| weight * objective(shape))
+            .sum()
+    }
+
+    /// Collapses a shape created by `create_shape` for each of the `seeds`, with an rng created
+    /// by `rng`, and returns the result with the highest score. Seeds that fail to collapse are
+    /// skipped. returns [None] if no seed could be collapsed.
+    /// If multiple results have the same score, the first one is returned.
+    pub fn run<NodeId, NodeValue, Kernel, Solver, R>(
+        &self,
+        create_shape: impl Fn() -> Shape,
+        solver: &Solver,
+        seeds: impl IntoIterator<Item = u64>,
+        rng: impl Fn(u64) -> R,
+    ) -> Option<SeedResult<Shape>>
+    where
+        NodeId: Copy + Eq + Hash + Debug,
+        NodeValue: Clone + PartialEq + Debug,
+        Shape: WaveShape<NodeId, NodeValue>,
+        Kernel: WaveKernel<NodeId, NodeValue, Shape>,
+        Solver: WaveSolver<NodeValue, Kernel>,
+        R: EntropySource,
+    {
+        let mut best: Option<SeedResult<Shape>> = None;
+        for seed in seeds {
+            let config = CollapseConfig::new(rng(seed)).with_seed(seed);
+            let Ok(outcome) =
+                collapse_fully::<_, _, _, Kernel, _, _>(create_shape(), solver, config)
+            else {
+                continue;
+            };
+
+            let score = self.score(&outcome.shape);
+            let is_better = match &best {
+                Some(best) => score > best.score,
+                None => true,
+            };
+            if is_better {
+                best = Some(SeedResult {
+                    seed,
+                    score,
+                    outcome,
+                });
+            }
+        }
+        best
+    }
+}
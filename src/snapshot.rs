@@ -0,0 +1,276 @@
+//! Snapshots of the state of a [TileMap2D], that can be stored with [rkyv].
+//!
+//! An archived snapshot can be memory mapped and a [TileMap2D] can be created from it directly
+//! with [TileMap2D::from_archived], without deserializing the snapshot first.
+//! Only the possible values of each node are stored, so values that were removed before the
+//! snapshot was taken can not be restored, see [crate::domain::Domain::restore]. The settings
+//! of the map, e.g. the [crate::tile2d::Neighborhood] and the weights, are not part of a
+//! snapshot and must be set again after loading, see [TileMap2D::with_settings_of].
+//!
+//! A [CheckpointStack] keeps named snapshots of a map in memory, e.g. for nested "try this idea"
+//! workflows in an editor.
+
+use std::collections::VecDeque;
+
+use rkyv::{Archive, Deserialize, Infallible, Serialize};
+
+use crate::domain::Domain;
+use crate::node::Node;
+use crate::tile2d::{Size2D, TileMap2D};
+use crate::wave_function::ReadShape;
+
+/// The state of a single node within a [TileMapSnapshot].
+#[derive(Archive, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NodeSnapshot<NodeValue> {
+    pub possible_values: Vec<NodeValue>,
+    pub is_collapsed: bool,
+}
+
+/// The state of a [TileMap2D]. The nodes are ordered row by row.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::tile2d::{Size2D, TileMap2D};
+/// use wave_collapse::snapshot::TileMapSnapshot;
+/// let tiles: Vec<u32> = vec![0, 1, 2];
+/// let shape = TileMap2D::new(Size2D::square(1024), Size2D::square(3), &tiles);
+///
+/// let bytes = rkyv::to_bytes::<_, 4096>(&shape.snapshot()).unwrap();
+/// // the bytes could also be memory mapped from a file
+/// let archived = unsafe { rkyv::archived_root::<TileMapSnapshot<u32>>(&bytes) };
+/// let shape = TileMap2D::from_archived(archived);
+/// ```
+#[derive(Archive, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TileMapSnapshot<NodeValue> {
+    pub width: u32,
+    pub height: u32,
+    pub kernel_width: u32,
+    pub kernel_height: u32,
+    pub nodes: Vec<NodeSnapshot<NodeValue>>,
+}
+
+impl<NodeValue: Clone> TileMap2D<NodeValue> {
+    /// returns a snapshot of the current state of this map.
+    pub fn snapshot(&self) -> TileMapSnapshot<NodeValue> {
+        TileMapSnapshot {
+            width: self.size().width,
+            height: self.size().height,
+            kernel_width: self.kernel_size().width,
+            kernel_height: self.kernel_size().height,
+            nodes: self
+                .iter_nodes()
+                .map(|node| NodeSnapshot {
+                    possible_values: node.possible_values().to_vec(),
+                    is_collapsed: node.is_collapsed(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Restores the state of all nodes of this map from `snapshot`, which must have been taken
+    /// from a map of the same size.
+    pub fn restore_snapshot(&self, snapshot: &TileMapSnapshot<NodeValue>) {
+        assert!(
+            snapshot.width == self.size().width && snapshot.height == self.size().height,
+            "snapshot must have the size of the map"
+        );
+
+        for (node, state) in self.iter_nodes().zip(&snapshot.nodes) {
+            *node.possible_values.borrow_mut() = Domain::new(state.possible_values.clone());
+            *node.is_collapsed.borrow_mut() = state.is_collapsed;
+        }
+    }
+
+    /// Create a new [TileMap2D] from a `snapshot`.
+    pub fn from_snapshot(snapshot: &TileMapSnapshot<NodeValue>) -> Self {
+        let size = Size2D::new(snapshot.width, snapshot.height);
+        let kernel_size = Size2D::new(snapshot.kernel_width, snapshot.kernel_height);
+
+        Self::from_node_states(
+            size,
+            kernel_size,
+            snapshot
+                .nodes
+                .iter()
+                .map(|node| (node.possible_values.clone(), node.is_collapsed)),
+        )
+    }
+
+    /// Create a new [TileMap2D] directly from an archived `snapshot`, without deserializing the
+    /// whole snapshot first.
+    pub fn from_archived(snapshot: &ArchivedTileMapSnapshot<NodeValue>) -> Self
+    where
+        NodeValue: Archive,
+        NodeValue::Archived: Deserialize<NodeValue, Infallible>,
+    {
+        let size = Size2D::new(snapshot.width, snapshot.height);
+        let kernel_size = Size2D::new(snapshot.kernel_width, snapshot.kernel_height);
+
+        Self::from_node_states(
+            size,
+            kernel_size,
+            snapshot.nodes.iter().map(|node| {
+                let possible_values = node
+                    .possible_values
+                    .iter()
+                    .map(|value| {
+                        value
+                            .deserialize(&mut Infallible)
+                            .expect("deserializing with Infallible can not fail")
+                    })
+                    .collect();
+                (possible_values, node.is_collapsed)
+            }),
+        )
+    }
+
+    fn from_node_states(
+        size: Size2D,
+        kernel_size: Size2D,
+        states: impl Iterator<Item = (Vec<NodeValue>, bool)>,
+    ) -> Self {
+        let nodes: Vec<_> = states
+            .enumerate()
+            .map(|(index, (possible_values, is_collapsed))| {
+                let index = index as u32;
+                let node = Node::new((index % size.width, index / size.width), possible_values);
+                *node.is_collapsed.borrow_mut() = is_collapsed;
+                node
+            })
+            .collect();
+
+        assert_eq!(
+            nodes.len(),
+            (size.width * size.height) as usize,
+            "snapshot must contain a node for every position"
+        );
+
+        Self::from_nodes(size, kernel_size, nodes)
+    }
+}
+
+/// A stack of named snapshots of a [TileMap2D], e.g. to try an edit and undo it if it does not
+/// work out. Checkpoints can be nested, popping a checkpoint restores the map to the state it
+/// had when the checkpoint was pushed.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::tile2d::{Size2D, TileMap2D};
+/// use wave_collapse::snapshot::CheckpointStack;
+/// let shape = TileMap2D::new(Size2D::square(64), Size2D::square(3), &[0u32, 1, 2]);
+/// let mut checkpoints = CheckpointStack::new();
+///
+/// checkpoints.push("river", &shape);
+/// // stamp a river into the map
+/// checkpoints.push("bridge", &shape);
+/// // try to place a bridge, which does not work out
+/// checkpoints.pop(&shape);
+/// assert_eq!(checkpoints.peek(), Some("river"));
+/// // keep the river
+/// checkpoints.commit();
+/// ```
+///
+/// Each checkpoint stores a full snapshot of the map. Use [CheckpointStack::bounded] to keep
+/// only the most recent checkpoints, e.g. for the undo history of a long editing session.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CheckpointStack<NodeValue> {
+    checkpoints: VecDeque<(String, TileMapSnapshot<NodeValue>)>,
+    capacity: Option<usize>,
+}
+
+impl<NodeValue: Clone> CheckpointStack<NodeValue> {
+    pub fn new() -> Self {
+        CheckpointStack {
+            checkpoints: VecDeque::new(),
+            capacity: None,
+        }
+    }
+
+    /// Creates a stack that keeps at most `capacity` checkpoints. Pushing a checkpoint onto a
+    /// full stack drops the bottom checkpoint, so the memory used stays bounded and the oldest
+    /// states can no longer be restored. `capacity` must not be 0.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use wave_collapse::tile2d::{Size2D, TileMap2D};
+    /// use wave_collapse::snapshot::CheckpointStack;
+    /// let shape = TileMap2D::new(Size2D::square(64), Size2D::square(3), &[0u32, 1, 2]);
+    /// let mut undo = CheckpointStack::bounded(100);
+    /// for step in 0..1000 {
+    ///     undo.push(format!("edit {step}"), &shape);
+    ///     // edit the map
+    /// }
+    /// assert_eq!(undo.len(), 100);
+    /// assert_eq!(undo.names().next(), Some("edit 900"));
+    /// ```
+    pub fn bounded(capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity must not be 0");
+        CheckpointStack {
+            checkpoints: VecDeque::with_capacity(capacity),
+            capacity: Some(capacity),
+        }
+    }
+
+    /// returns the maximum number of checkpoints or [None] if the stack is unbounded.
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    /// Pushes a checkpoint called `name` with the current state of `shape`. If the stack is
+    /// [CheckpointStack::bounded] and full, the bottom checkpoint is dropped.
+    pub fn push(&mut self, name: impl Into<String>, shape: &TileMap2D<NodeValue>) {
+        if self.capacity == Some(self.checkpoints.len()) {
+            self.checkpoints.pop_front();
+        }
+        self.checkpoints.push_back((name.into(), shape.snapshot()));
+    }
+
+    /// Removes the top checkpoint and restores `shape` to it.
+    /// returns the name of the checkpoint or [None] if the stack is empty.
+    pub fn pop(&mut self, shape: &TileMap2D<NodeValue>) -> Option<String> {
+        let (name, snapshot) = self.checkpoints.pop_back()?;
+        shape.restore_snapshot(&snapshot);
+        Some(name)
+    }
+
+    /// Removes all checkpoints down to and including the top most checkpoint called `name` and
+    /// restores `shape` to it. returns `false` and changes nothing if there is no such
+    /// checkpoint.
+    pub fn pop_to(&mut self, name: &str, shape: &TileMap2D<NodeValue>) -> bool {
+        let Some(index) = self
+            .checkpoints
+            .iter()
+            .rposition(|(other, _)| other == name)
+        else {
+            return false;
+        };
+        let (_, snapshot) = &self.checkpoints[index];
+        shape.restore_snapshot(snapshot);
+        self.checkpoints.truncate(index);
+        true
+    }
+
+    /// Removes the top checkpoint without restoring it, which keeps the changes made since it
+    /// was pushed. returns the name of the checkpoint or [None] if the stack is empty.
+    pub fn commit(&mut self) -> Option<String> {
+        self.checkpoints.pop_back().map(|(name, _)| name)
+    }
+
+    /// returns the name of the top checkpoint.
+    pub fn peek(&self) -> Option<&str> {
+        self.checkpoints.back().map(|(name, _)| name.as_str())
+    }
+
+    /// returns the names of all checkpoints, from the bottom to the top of the stack.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.checkpoints.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.checkpoints.is_empty()
+    }
+}
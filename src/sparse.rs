@@ -0,0 +1,138 @@
+//! A compact representation of collapsed [TileMap2D]s where one value dominates, e.g. large
+//! worlds that are mostly ocean or void.
+//!
+//! A [SparseMap2D] only stores the most common value once and every node with a different value
+//! as an exception, so its size depends on the number of exceptions instead of the size of the
+//! map. With the `snapshot` feature it can be stored with [rkyv].
+
+#[cfg(feature = "snapshot")]
+use rkyv::{Archive, Deserialize, Serialize};
+
+use crate::tile2d::{Index2D, Size2D, TileMap2D};
+use crate::wave_function::ReadShape;
+
+/// A collapsed map of `width` x `height` nodes, where every node has the `default` value,
+/// except for the nodes in `exceptions`.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::tile2d::{Size2D, TileMap2D};
+/// use wave_collapse::sparse::SparseMap2D;
+/// # let shape = TileMap2D::new(Size2D::square(1024), Size2D::square(3), &['~', '#']);
+/// // collapse the shape
+/// let sparse = SparseMap2D::from_shape(&shape).expect("shape is collapsed");
+/// println!("{} island tiles in the ocean", sparse.exceptions.len());
+/// assert_eq!(sparse.get((0, 0)), shape[(0, 0)].collapsed().as_ref());
+/// ```
+#[cfg_attr(feature = "snapshot", derive(Archive, Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SparseMap2D<NodeValue> {
+    pub width: u32,
+    pub height: u32,
+    pub default: NodeValue,
+    /// the nodes that do not have the `default` value, ordered row by row.
+    pub exceptions: Vec<(Index2D, NodeValue)>,
+}
+
+impl<NodeValue: Clone + PartialEq> SparseMap2D<NodeValue> {
+    /// returns the collapsed `shape` with its most common value as the default, or [None] if a
+    /// node of `shape` is not collapsed or `shape` has no nodes.
+    pub fn from_shape(shape: &TileMap2D<NodeValue>) -> Option<Self> {
+        let values = shape
+            .iter_nodes()
+            .map(|node| node.collapsed())
+            .collect::<Option<Vec<_>>>()?;
+
+        // the number of nodes of each value, in the order the values first appear
+        let mut counts: Vec<(&NodeValue, usize)> = Vec::new();
+        for value in &values {
+            match counts.iter_mut().find(|(other, _)| *other == value) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((value, 1)),
+            }
+        }
+        // the first of the most common values, so the result does not depend on the sort order
+        let mut default = counts.first()?;
+        for entry in &counts {
+            if entry.1 > default.1 {
+                default = entry;
+            }
+        }
+        let default = default.0.clone();
+
+        Self::with_default(shape, default)
+    }
+
+    /// like [SparseMap2D::from_shape], but with a fixed `default` value, e.g. so that multiple
+    /// chunks of a world share the same default.
+    pub fn with_default(shape: &TileMap2D<NodeValue>, default: NodeValue) -> Option<Self> {
+        let size = *shape.size();
+        let mut exceptions = Vec::new();
+        for y in 0..size.height {
+            for x in 0..size.width {
+                let value = shape[(x, y)].collapsed()?;
+                if value != default {
+                    exceptions.push(((x, y), value));
+                }
+            }
+        }
+
+        Some(SparseMap2D {
+            width: size.width,
+            height: size.height,
+            default,
+            exceptions,
+        })
+    }
+
+    /// returns the size of the map.
+    pub fn size(&self) -> Size2D {
+        Size2D::new(self.width, self.height)
+    }
+
+    /// returns the value of the node `id`, or [None] if `id` is outside of the map.
+    pub fn get(&self, id: Index2D) -> Option<&NodeValue> {
+        if id.0 >= self.width || id.1 >= self.height {
+            return None;
+        }
+
+        let key = (id.1, id.0);
+        match self
+            .exceptions
+            .binary_search_by_key(&key, |((x, y), _)| (*y, *x))
+        {
+            Ok(index) => Some(&self.exceptions[index].1),
+            Err(_) => Some(&self.default),
+        }
+    }
+
+    /// returns the share of nodes that are exceptions, between 0 and 1.
+    pub fn density(&self) -> f32 {
+        let nodes = self.width as usize * self.height as usize;
+        if nodes == 0 {
+            return 0.0;
+        }
+        self.exceptions.len() as f32 / nodes as f32
+    }
+
+    /// returns the values of all nodes row by row, so the value of the node `(x, y)` is at
+    /// `y * width + x`.
+    pub fn to_values(&self) -> Vec<NodeValue> {
+        let mut values = vec![self.default.clone(); self.width as usize * self.height as usize];
+        for ((x, y), value) in &self.exceptions {
+            values[(*y * self.width + *x) as usize] = value.clone();
+        }
+        values
+    }
+
+    /// returns a collapsed [TileMap2D] with the values of this map. `possible_values` are the
+    /// values the map was collapsed from.
+    pub fn to_shape(
+        &self,
+        kernel_size: Size2D,
+        possible_values: &[NodeValue],
+    ) -> TileMap2D<NodeValue> {
+        let template: Vec<_> = self.to_values().into_iter().map(Some).collect();
+        TileMap2D::from_template(self.size(), kernel_size, &template, possible_values)
+    }
+}
@@ -0,0 +1,229 @@
+//! Per step statistics of [crate::wave_function::collapse_wave_with_telemetry], that can be
+//! written as CSV or JSON lines for further analysis.
+
+use std::{collections::HashMap, fmt::Debug, hash::Hash, io};
+
+use crate::wave_function::ReadShape;
+
+/// Statistics about a single step of the collapse, which collapses one node and propagates
+/// the changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepTelemetry<NodeId> {
+    /// the index of the step, starting at 0.
+    pub step: u32,
+    /// the id of the node that was collapsed in this step.
+    pub node_id: NodeId,
+    /// the sum of the entropy of all nodes before this step.
+    pub entropy_before: u64,
+    /// the sum of the entropy of all nodes after this step.
+    pub entropy_after: u64,
+    /// the number of values removed in this step, including the values removed by collapsing
+    /// the node.
+    pub pruned: u64,
+    /// the maximum length of the propagation queue during this step.
+    pub queue_length: usize,
+}
+
+/// Receives the [StepTelemetry] of each step.
+pub trait TelemetrySink<NodeId> {
+    fn record(&mut self, telemetry: &StepTelemetry<NodeId>);
+
+    /// returns `false` if the sink ignores all telemetry, so it does not need to be collected.
+    fn is_enabled(&self) -> bool {
+        true
+    }
+}
+
+/// A sink that ignores all telemetry.
+impl<NodeId> TelemetrySink<NodeId> for () {
+    fn record(&mut self, _telemetry: &StepTelemetry<NodeId>) {}
+
+    fn is_enabled(&self) -> bool {
+        false
+    }
+}
+
+impl<NodeId, Sink: TelemetrySink<NodeId>> TelemetrySink<NodeId> for &mut Sink {
+    fn record(&mut self, telemetry: &StepTelemetry<NodeId>) {
+        (**self).record(telemetry)
+    }
+
+    fn is_enabled(&self) -> bool {
+        (**self).is_enabled()
+    }
+}
+
+/// A [TelemetrySink] that keeps the total remaining entropy after each step, e.g. to plot the
+/// convergence of a collapse or to find tilesets that stop converging before they fail.
+///
+/// Use [region_entropy] on the shapes yielded by the collapse to track the entropy per region.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntropyHistory {
+    initial: Option<u64>,
+    totals: Vec<u64>,
+}
+
+impl EntropyHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// returns the total entropy before the first step or [None] if no step was recorded.
+    pub fn initial(&self) -> Option<u64> {
+        self.initial
+    }
+
+    /// returns the total entropy after each step.
+    pub fn totals(&self) -> &[u64] {
+        &self.totals
+    }
+}
+
+impl<NodeId> TelemetrySink<NodeId> for EntropyHistory {
+    fn record(&mut self, telemetry: &StepTelemetry<NodeId>) {
+        self.initial.get_or_insert(telemetry.entropy_before);
+        self.totals.push(telemetry.entropy_after);
+    }
+}
+
+/// returns the sum of the entropy of the nodes in each region of `shape`. `region` returns the
+/// region of a node.
+pub fn region_entropy<NodeId, NodeValue, Shape, Region>(
+    shape: &Shape,
+    region: impl Fn(&NodeId) -> Region,
+) -> HashMap<Region, u64>
+where
+    NodeValue: Clone,
+    Shape: ReadShape<NodeId, NodeValue> + ?Sized,
+    Region: Eq + Hash,
+{
+    let mut entropy = HashMap::new();
+    for node in shape.iter_nodes() {
+        *entropy.entry(region(&node.id)).or_default() += node.entropy() as u64;
+    }
+    entropy
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TelemetryFormat {
+    /// comma separated values with a header row.
+    Csv,
+    /// one JSON object per line.
+    JsonLines,
+}
+
+/// A [TelemetrySink] that writes each step to `writer`. Node ids are written as strings
+/// using their [Debug] representation.
+///
+/// Write errors do not interrupt the collapse. The first error is kept and returned by
+/// [TelemetryWriter::finish], all following steps are ignored.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::wave_function::WaveSolver;
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Wrapping};
+/// use std::fs::File;
+/// use wave_collapse::telemetry::TelemetryWriter;
+/// use wave_collapse::wave_function::collapse_wave_with_telemetry;
+/// use wave_collapse::GenIterReturnResult;
+/// # struct TestSolver;
+/// # impl WaveSolver<u32, Kernel2D<Wrapping, u32>> for TestSolver {
+/// #     fn is_valid(&self, _value: &u32, _kernel: &Kernel2D<Wrapping, u32>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// let tiles: Vec<u32> = vec![0, 1, 2];
+/// let shape = TileMap2D::new(Size2D::square(10), Size2D::square(3), &tiles);
+///
+/// let mut telemetry = TelemetryWriter::csv(File::create("telemetry.csv").unwrap());
+/// let mut rng = rand::thread_rng();
+/// let result = collapse_wave_with_telemetry::<_, _, _, Kernel2D<Wrapping, u32>, _, _>(
+///     shape,
+///     &TestSolver,
+///     &mut rng,
+///     &mut telemetry,
+/// )
+/// .calc_result();
+/// telemetry.finish().unwrap();
+/// ```
+pub struct TelemetryWriter<W: io::Write> {
+    writer: W,
+    format: TelemetryFormat,
+    header_written: bool,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> TelemetryWriter<W> {
+    pub fn new(writer: W, format: TelemetryFormat) -> Self {
+        TelemetryWriter {
+            writer,
+            format,
+            header_written: false,
+            error: None,
+        }
+    }
+
+    pub fn csv(writer: W) -> Self {
+        Self::new(writer, TelemetryFormat::Csv)
+    }
+
+    pub fn json_lines(writer: W) -> Self {
+        Self::new(writer, TelemetryFormat::JsonLines)
+    }
+
+    /// Flushes the writer and returns it, or the first error that occurred while writing.
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write<NodeId: Debug>(&mut self, telemetry: &StepTelemetry<NodeId>) -> io::Result<()> {
+        let node_id = format!("{:?}", telemetry.node_id);
+
+        match self.format {
+            TelemetryFormat::Csv => {
+                if !self.header_written {
+                    writeln!(
+                        self.writer,
+                        "step,node_id,entropy_before,entropy_after,pruned,queue_length"
+                    )?;
+                    self.header_written = true;
+                }
+                writeln!(
+                    self.writer,
+                    "{},\"{}\",{},{},{},{}",
+                    telemetry.step,
+                    node_id.replace('"', "\"\""),
+                    telemetry.entropy_before,
+                    telemetry.entropy_after,
+                    telemetry.pruned,
+                    telemetry.queue_length
+                )
+            }
+            TelemetryFormat::JsonLines => writeln!(
+                self.writer,
+                "{{\"step\":{},\"node_id\":\"{}\",\"entropy_before\":{},\"entropy_after\":{},\"pruned\":{},\"queue_length\":{}}}",
+                telemetry.step,
+                node_id.replace('\\', "\\\\").replace('"', "\\\""),
+                telemetry.entropy_before,
+                telemetry.entropy_after,
+                telemetry.pruned,
+                telemetry.queue_length
+            ),
+        }
+    }
+}
+
+impl<NodeId: Debug, W: io::Write> TelemetrySink<NodeId> for TelemetryWriter<W> {
+    fn record(&mut self, telemetry: &StepTelemetry<NodeId>) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(error) = self.write(telemetry) {
+            self.error = Some(error);
+        }
+    }
+}
@@ -0,0 +1,498 @@
+//! Utilities that help with testing custom [WaveSolver] implementations.
+
+#[cfg(feature = "test-util")]
+pub mod fuzz;
+#[cfg(feature = "test-util")]
+pub mod inject;
+#[cfg(feature = "test-util")]
+pub mod minimize;
+
+use std::{fmt::Debug, hash::Hash};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::node::Node;
+use crate::tile2d::wrapping_mode::Cutoff;
+use crate::tile2d::{kernel_from_nodes, probe_neighbor, Index2D, Kernel2D, Size2D, TileMap2D};
+#[cfg(feature = "deterministic")]
+use crate::wave_function::{collapse_fully, CollapseConfig};
+use crate::wave_function::{WaveKernel, WaveSolver};
+use crate::{collapse_wave, GenIterReturnResult};
+
+/// A pair of values for which a [WaveSolver] does not report symmetric compatibility.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymmetryViolation<NodeValue> {
+    /// the value of the center node of the kernel.
+    pub value: NodeValue,
+    /// the value of the single neighbor in the kernel.
+    pub neighbor: NodeValue,
+    /// the offset of `neighbor` relative to `value`.
+    pub offset: (i64, i64),
+    /// `true` if `value` is valid with `neighbor` at `offset`.
+    pub forward: bool,
+    /// `true` if `neighbor` is valid with `value` at the opposite offset.
+    pub backward: bool,
+}
+
+/// Checks that `solver` treats compatibility between two values symmetrically.
+///
+/// For every offset within `kernel_size` and every pair of `tiles` a kernel is created that only
+/// contains the center value and a single collapsed neighbor. All other nodes in that kernel
+/// are inside of the map and left unconstrained, so solvers that treat the border differently
+/// are not affected. `value` being valid next to `neighbor` at `offset` should mean that
+/// `neighbor` is valid next to `value` at `-offset`. Every pair where this is not the case is
+/// returned.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::wave_function::WaveSolver;
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, wrapping_mode::Cutoff};
+/// use wave_collapse::testing::check_solver_symmetry;
+/// # struct TestSolver;
+/// # impl WaveSolver<u32, Kernel2D<Cutoff, u32>> for TestSolver {
+/// #     fn is_valid(&self, _value: &u32, _kernel: &Kernel2D<Cutoff, u32>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// let tiles: Vec<u32> = vec![0, 1, 2];
+/// let violations = check_solver_symmetry(&TestSolver, &tiles, Size2D::square(3));
+/// assert!(violations.is_empty(), "{violations:?}");
+/// ```
+pub fn check_solver_symmetry<NodeValue, Solver>(
+    solver: &Solver,
+    tiles: &[NodeValue],
+    kernel_size: Size2D,
+) -> Vec<SymmetryViolation<NodeValue>>
+where
+    NodeValue: Clone,
+    Solver: WaveSolver<NodeValue, Kernel2D<Cutoff, NodeValue>>,
+{
+    let radius_x = ((kernel_size.width - 1) / 2) as i64;
+    let radius_y = ((kernel_size.height - 1) / 2) as i64;
+
+    let mut violations = Vec::new();
+
+    for dy in 0..=radius_y {
+        for dx in -radius_x..=radius_x {
+            // only check half of the offsets, the other half is covered by `backward`
+            if dy == 0 && dx <= 0 {
+                continue;
+            }
+
+            for value in tiles {
+                for neighbor in tiles {
+                    let forward =
+                        probe_neighbor(solver, tiles, kernel_size, value, neighbor, (dx, dy));
+                    let backward =
+                        probe_neighbor(solver, tiles, kernel_size, neighbor, value, (-dx, -dy));
+
+                    if forward != backward {
+                        violations.push(SymmetryViolation {
+                            value: value.clone(),
+                            neighbor: neighbor.clone(),
+                            offset: (dx, dy),
+                            forward,
+                            backward,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    violations
+}
+
+/// A node of a kernel created with [sample_kernel].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleNode<'a, NodeValue> {
+    /// a node that is not collapsed and can be any of the values. A single value describes a
+    /// node that was narrowed down by propagation, but not collapsed yet.
+    Open(&'a [NodeValue]),
+    /// a collapsed node.
+    Collapsed(NodeValue),
+}
+
+/// Creates a [Kernel2D] from a grid of `nodes`, row by row with `width` nodes per row, so
+/// [WaveSolver::is_valid] can be tested against a hand-written neighborhood without collapsing a
+/// map. The center of the grid is the center of the kernel. The grid is the whole map, so all
+/// nodes outside of it are [crate::tile2d::NeighborState::OutOfBounds].
+///
+/// The width and height of the grid must be uneven.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::wave_function::WaveSolver;
+/// # use wave_collapse::tile2d::{Kernel2D, wrapping_mode::Cutoff};
+/// use wave_collapse::testing::{sample_kernel, SampleNode::{Collapsed, Open}};
+/// # struct RoadSolver;
+/// # impl WaveSolver<char, Kernel2D<Cutoff, char>> for RoadSolver {
+/// #     fn is_valid(&self, _value: &char, _kernel: &Kernel2D<Cutoff, char>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// // a road ends above the center, the other neighbors are undecided
+/// let any = Open(&['.', '|']);
+/// let kernel = sample_kernel(3, &[any, Collapsed('|'), any, any, any, any, any, any, any]);
+/// assert!(RoadSolver.is_valid(&'|', &kernel));
+/// ```
+pub fn sample_kernel<NodeValue: Clone>(
+    width: u32,
+    nodes: &[SampleNode<NodeValue>],
+) -> Kernel2D<Cutoff, NodeValue> {
+    assert!(width > 0, "width must not be 0");
+    assert_eq!(
+        nodes.len() % width as usize,
+        0,
+        "nodes must contain full rows"
+    );
+    let size = Size2D::new(width, (nodes.len() / width as usize) as u32);
+    assert!(size.width % 2 == 1, "Kernel width must be uneven");
+    assert!(size.height % 2 == 1, "Kernel height must be uneven");
+
+    let nodes = nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| {
+            let id = (index as u32 % width, index as u32 / width);
+            match node {
+                SampleNode::Open(values) => Node::new(id, *values),
+                SampleNode::Collapsed(value) => {
+                    let node = Node::new(id, vec![value.clone()]);
+                    *node.is_collapsed.borrow_mut() = true;
+                    node
+                }
+            }
+        })
+        .collect();
+    kernel_from_nodes(size, nodes)
+}
+
+/// The result of [chi_square_distribution].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChiSquare {
+    /// the chi-square statistic of the observed value frequencies.
+    pub statistic: f64,
+    /// the degrees of freedom of the test, one less than the number of expected values.
+    pub degrees_of_freedom: usize,
+    /// the number of collapsed nodes that were counted, one per successful run.
+    pub samples: usize,
+    /// the number of runs that failed to collapse and were ignored.
+    pub failed_runs: u64,
+}
+
+/// Collapses `runs` shapes created by `create_shape` and compares the frequencies of the
+/// resulting values against `expected` weights using a chi-square test.
+///
+/// The nodes of a single map depend on their neighbors, so only one random node of each map is
+/// counted. That way the samples are independent and the statistic can be compared against the
+/// critical values of the chi-square distribution. Run `n` uses a [StdRng] seeded with
+/// `seed + n`, so the result is reproducible. Values that are not part of `expected` are not
+/// counted. The weights in `expected` must be positive.
+///
+/// returns [None] if no value of `expected` was counted, e.g. because every run failed.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::wave_function::WaveSolver;
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Wrapping};
+/// use wave_collapse::testing::chi_square_distribution;
+/// # struct TestSolver;
+/// # impl WaveSolver<u32, Kernel2D<Wrapping, u32>> for TestSolver {
+/// #     fn is_valid(&self, _value: &u32, _kernel: &Kernel2D<Wrapping, u32>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// let tiles: Vec<u32> = vec![0, 1];
+/// let result = chi_square_distribution::<_, Kernel2D<Wrapping, u32>, _, _>(
+///     || TileMap2D::new(Size2D::square(10), Size2D::square(3), &tiles),
+///     &TestSolver,
+///     1000,
+///     42,
+///     &[(0, 1.0), (1, 3.0)],
+/// )
+/// .expect("some runs succeeded");
+/// // 3.841 is the critical value for 1 degree of freedom at p = 0.05
+/// assert!(result.statistic < 3.841);
+/// ```
+pub fn chi_square_distribution<NodeValue, Kernel, Solver, CreateShape>(
+    create_shape: CreateShape,
+    solver: &Solver,
+    runs: u64,
+    seed: u64,
+    expected: &[(NodeValue, f64)],
+) -> Option<ChiSquare>
+where
+    NodeValue: Clone + PartialEq + Debug,
+    Kernel: WaveKernel<Index2D, NodeValue, TileMap2D<NodeValue>>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+    CreateShape: Fn() -> TileMap2D<NodeValue>,
+{
+    assert!(
+        expected.iter().all(|(_, weight)| *weight > 0.0),
+        "expected weights must be positive"
+    );
+
+    let mut observed = vec![0usize; expected.len()];
+    let mut failed_runs = 0;
+
+    for run in 0..runs {
+        let mut rng = StdRng::seed_from_u64(seed.wrapping_add(run));
+        let result = collapse_wave(create_shape(), solver, &mut rng).calc_result();
+
+        let collapsed = match result
+            .ok()
+            .and_then(|outcome| outcome.shape.get_collapsed())
+        {
+            Some(collapsed) => collapsed,
+            None => {
+                failed_runs += 1;
+                continue;
+            }
+        };
+
+        let values: Vec<_> = collapsed.elements_row_major_iter().collect();
+        let value = values[rng.gen_range(0..values.len())];
+        if let Some(index) = expected.iter().position(|(v, _)| v == value) {
+            observed[index] += 1;
+        }
+    }
+
+    let samples: usize = observed.iter().sum();
+    if samples == 0 {
+        return None;
+    }
+    let total_weight: f64 = expected.iter().map(|(_, weight)| weight).sum();
+
+    let statistic = observed
+        .iter()
+        .zip(expected)
+        .map(|(&observed, (_, weight))| {
+            let expected = samples as f64 * weight / total_weight;
+            let difference = observed as f64 - expected;
+            difference * difference / expected
+        })
+        .sum();
+
+    Some(ChiSquare {
+        statistic,
+        degrees_of_freedom: expected.len().saturating_sub(1),
+        samples,
+        failed_runs,
+    })
+}
+
+/// Collapses `shape` with a [StdRng] seeded with `seed` and asserts that the digest of the
+/// result, see [TileMap2D::collapsed_digest], is `expected_digest`.
+///
+/// This makes it easy to detect unintended changes of the generated output. The panic message
+/// contains the actual digest, so a new expected value can be copied from a failing test.
+/// The algorithm of [StdRng] may change between versions of [rand], which changes the digest.
+/// Use [assert_golden_deterministic] for digests that must stay the same.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::wave_function::WaveSolver;
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Wrapping};
+/// use wave_collapse::testing::assert_golden;
+/// # struct TestSolver;
+/// # impl WaveSolver<u32, Kernel2D<Wrapping, u32>> for TestSolver {
+/// #     fn is_valid(&self, _value: &u32, _kernel: &Kernel2D<Wrapping, u32>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// let tiles: Vec<u32> = vec![0, 1];
+/// let shape = TileMap2D::new(Size2D::square(10), Size2D::square(3), &tiles);
+/// assert_golden::<_, Kernel2D<Wrapping, u32>, _>(shape, &TestSolver, 42, 0x1234_5678_9abc_def0);
+/// ```
+#[track_caller]
+pub fn assert_golden<NodeValue, Kernel, Solver>(
+    shape: TileMap2D<NodeValue>,
+    solver: &Solver,
+    seed: u64,
+    expected_digest: u64,
+) where
+    NodeValue: Clone + PartialEq + Hash + Debug,
+    Kernel: WaveKernel<Index2D, NodeValue, TileMap2D<NodeValue>>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+{
+    let mut rng = StdRng::seed_from_u64(seed);
+    let shape = match collapse_wave(shape, solver, &mut rng).calc_result() {
+        Ok(outcome) => outcome.shape,
+        Err(error) => panic!("Failed to collapse wave with seed {seed}: {error:?}"),
+    };
+
+    let digest = shape
+        .collapsed_digest()
+        .expect("a successfully collapsed shape is fully collapsed");
+
+    assert_eq!(
+        digest, expected_digest,
+        "golden digest mismatch for seed {seed}: got {digest:#018x}, expected {expected_digest:#018x}"
+    );
+}
+
+/// like [assert_golden], but collapses `shape` with [CollapseConfig::seeded]. The digest is the
+/// same across platforms and versions of this crate and [rand], see [crate::rng].
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::wave_function::WaveSolver;
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Wrapping};
+/// use wave_collapse::testing::assert_golden_deterministic;
+/// # struct TestSolver;
+/// # impl WaveSolver<u32, Kernel2D<Wrapping, u32>> for TestSolver {
+/// #     fn is_valid(&self, _value: &u32, _kernel: &Kernel2D<Wrapping, u32>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// let tiles: Vec<u32> = vec![0, 1];
+/// let shape = TileMap2D::new(Size2D::square(10), Size2D::square(3), &tiles);
+/// assert_golden_deterministic::<_, Kernel2D<Wrapping, u32>, _>(
+///     shape,
+///     &TestSolver,
+///     42,
+///     0x1234_5678_9abc_def0,
+/// );
+/// ```
+#[cfg(feature = "deterministic")]
+#[track_caller]
+pub fn assert_golden_deterministic<NodeValue, Kernel, Solver>(
+    shape: TileMap2D<NodeValue>,
+    solver: &Solver,
+    seed: u64,
+    expected_digest: u64,
+) where
+    NodeValue: Clone + PartialEq + Hash + Debug,
+    Kernel: WaveKernel<Index2D, NodeValue, TileMap2D<NodeValue>>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+{
+    let config = CollapseConfig::seeded(seed);
+    let shape = match collapse_fully::<_, _, _, Kernel, _, _>(shape, solver, config) {
+        Ok(outcome) => outcome.shape,
+        Err(error) => panic!("Failed to collapse wave with seed {seed}: {error:?}"),
+    };
+
+    let digest = shape
+        .collapsed_digest()
+        .expect("a successfully collapsed shape is fully collapsed");
+
+    assert_eq!(
+        digest, expected_digest,
+        "golden digest mismatch for seed {seed}: got {digest:#018x}, expected {expected_digest:#018x}"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        chi_square_distribution, sample_kernel,
+        SampleNode::{Collapsed, Open},
+    };
+    use crate::tile2d::wrapping_mode::Cutoff;
+    use crate::tile2d::{AdjacencySolver2D, Direction, Kernel2D, NeighborState, Size2D, TileMap2D};
+    use crate::wave_function::{WaveKernel, WaveSolver};
+
+    const GRASS: u8 = 0;
+    const TREE: u8 = 1;
+
+    /// allows every value everywhere.
+    struct AnySolver;
+
+    impl WaveSolver<u8, Kernel2D<Cutoff, u8>> for AnySolver {
+        fn is_valid(&self, _value: &u8, _kernel: &Kernel2D<Cutoff, u8>) -> bool {
+            true
+        }
+    }
+
+    /// 10.828 is the critical value for 1 degree of freedom at p = 0.001
+    const CRITICAL: f64 = 10.828;
+
+    #[test]
+    fn chi_square_accepts_the_expected_distribution() {
+        let result = chi_square_distribution::<_, Kernel2D<Cutoff, u8>, _, _>(
+            || TileMap2D::new(Size2D::square(1), Size2D::square(1), &[GRASS, TREE]),
+            &AnySolver,
+            500,
+            42,
+            &[(GRASS, 1.0), (TREE, 1.0)],
+        )
+        .expect("every run succeeds");
+
+        assert_eq!(result.samples, 500);
+        assert_eq!(result.failed_runs, 0);
+        assert_eq!(result.degrees_of_freedom, 1);
+        assert!(result.statistic < CRITICAL, "{result:?}");
+    }
+
+    #[test]
+    fn chi_square_rejects_a_biased_distribution() {
+        let biased = || {
+            TileMap2D::new_weighted(
+                Size2D::square(1),
+                Size2D::square(1),
+                &[(GRASS, 3.0), (TREE, 1.0)],
+            )
+        };
+
+        let uniform = chi_square_distribution::<_, Kernel2D<Cutoff, u8>, _, _>(
+            biased,
+            &AnySolver,
+            500,
+            42,
+            &[(GRASS, 1.0), (TREE, 1.0)],
+        )
+        .expect("every run succeeds");
+        assert!(uniform.statistic > CRITICAL, "{uniform:?}");
+
+        let weighted = chi_square_distribution::<_, Kernel2D<Cutoff, u8>, _, _>(
+            biased,
+            &AnySolver,
+            500,
+            42,
+            &[(GRASS, 3.0), (TREE, 1.0)],
+        )
+        .expect("every run succeeds");
+        assert!(weighted.statistic < CRITICAL, "{weighted:?}");
+    }
+
+    #[test]
+    fn sample_kernel_places_the_nodes_around_the_center() {
+        let mut nodes = [Open(&[GRASS, TREE]); 15];
+        nodes[2] = Collapsed(TREE);
+        nodes[8] = Open(&[GRASS]);
+        let kernel = sample_kernel(5, &nodes);
+
+        assert_eq!(kernel.center_id(), (2, 1));
+        let above = kernel.get(0, -1).node().expect("above the center");
+        assert!(above.is_collapsed());
+        assert_eq!(above.collapsed(), Some(TREE));
+        let right = kernel
+            .get_dir(Direction::Right)
+            .node()
+            .expect("right of the center");
+        assert!(!right.is_collapsed());
+        assert_eq!(&*right.possible_values(), [GRASS]);
+        assert!(matches!(kernel.get(3, 0), NeighborState::OutsideKernel));
+    }
+
+    #[test]
+    fn sample_kernel_tests_a_solver() {
+        // trees are never next to each other
+        let solver = AdjacencySolver2D::new()
+            .with_rule(GRASS, GRASS, Direction::Right)
+            .with_rule(GRASS, TREE, Direction::Right)
+            .with_rule(TREE, GRASS, Direction::Right)
+            .with_rule(GRASS, GRASS, Direction::Bottom)
+            .with_rule(GRASS, TREE, Direction::Bottom)
+            .with_rule(TREE, GRASS, Direction::Bottom);
+        let any = Open(&[GRASS, TREE]);
+        let kernel = sample_kernel(
+            3,
+            &[any, Collapsed(TREE), any, any, any, any, any, any, any],
+        );
+
+        assert!(solver.is_valid(&GRASS, &kernel));
+        assert!(!solver.is_valid(&TREE, &kernel));
+    }
+}
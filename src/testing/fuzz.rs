@@ -0,0 +1,95 @@
+//! Random tilesets for fuzzing the collapse algorithm and custom [WaveSolver]s.
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::tile2d::Kernel2D;
+use crate::wave_function::WaveSolver;
+
+/// The offsets of the orthogonal neighbors. The opposite of `OFFSETS[d]` is `OFFSETS[(d + 2) % 4]`.
+const OFFSETS: [(i64, i64); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+/// A random tileset with random, but symmetric adjacency rules between orthogonal neighbors.
+///
+/// Tiles are represented by their index. [RandomTileset] is also a [WaveSolver] for any
+/// [Kernel2D] with a radius of at least 1.
+#[derive(Debug, Clone)]
+pub struct RandomTileset {
+    /// all tiles in this tileset.
+    pub tiles: Vec<u32>,
+    /// `compatible[direction][value][neighbor]`
+    compatible: [Vec<Vec<bool>>; 4],
+}
+
+/// panics with a clear message if `density` is not a probability, instead of panicking in
+/// [Rng::gen_bool].
+fn assert_density(density: f64) {
+    assert!(
+        (0.0..=1.0).contains(&density),
+        "density must be between 0 and 1, but is {density}"
+    );
+}
+
+impl RandomTileset {
+    /// Generates a tileset with between 1 and `max_tiles` tiles. Each pair of tiles is compatible
+    /// in a direction with the probability `density`, which must be between 0 and 1.
+    pub fn generate(rng: &mut impl Rng, max_tiles: u32, density: f64) -> Self {
+        assert!(max_tiles > 0, "At least one tile required!");
+        let tile_count = rng.gen_range(1..=max_tiles);
+        Self::generate_with_count(rng, tile_count, density)
+    }
+
+    /// Generates a tileset with exactly `tile_count` tiles. Each pair of tiles is compatible
+    /// in a direction with the probability `density`, which must be between 0 and 1.
+    pub fn generate_with_count(rng: &mut impl Rng, tile_count: u32, density: f64) -> Self {
+        assert_density(density);
+        let count = tile_count as usize;
+        let mut compatible: [Vec<Vec<bool>>; 4] = Default::default();
+        for direction in compatible.iter_mut() {
+            *direction = vec![vec![false; count]; count];
+        }
+
+        for direction in 0..2 {
+            for value in 0..count {
+                for neighbor in 0..count {
+                    let is_compatible = rng.gen_bool(density);
+                    compatible[direction][value][neighbor] = is_compatible;
+                    compatible[direction + 2][neighbor][value] = is_compatible;
+                }
+            }
+        }
+
+        RandomTileset {
+            tiles: (0..tile_count).collect(),
+            compatible,
+        }
+    }
+
+    /// returns `true` if `neighbor` can be placed at `offset` next to `value`. Only orthogonal
+    /// offsets with a length of 1 are constrained.
+    pub fn is_compatible(&self, value: u32, neighbor: u32, offset: (i64, i64)) -> bool {
+        match OFFSETS.iter().position(|o| *o == offset) {
+            Some(direction) => self.compatible[direction][value as usize][neighbor as usize],
+            None => true,
+        }
+    }
+
+    /// returns a random, non empty subset of the tiles, that can be used as the initial domain
+    /// of a node.
+    pub fn random_domain(&self, rng: &mut impl Rng) -> Vec<u32> {
+        let amount = rng.gen_range(1..=self.tiles.len());
+        self.tiles.choose_multiple(rng, amount).copied().collect()
+    }
+}
+
+impl<WrappingMode> WaveSolver<u32, Kernel2D<WrappingMode, u32>> for RandomTileset {
+    fn is_valid(&self, value: &u32, kernel: &Kernel2D<WrappingMode, u32>) -> bool {
+        OFFSETS
+            .iter()
+            .all(|&offset| match kernel.get(offset.0, offset.1).node() {
+                Some(node) => {
+                    node.any_value(|neighbor| self.is_compatible(*value, *neighbor, offset))
+                }
+                None => true,
+            })
+    }
+}
@@ -0,0 +1,71 @@
+//! Hooks to force [crate::collapse_wave] to fail, so that error handling can be tested
+//! deterministically.
+
+use std::cell::RefCell;
+
+use crate::error::{EngineError, PropagationError, WaveCollapseError};
+
+thread_local! {
+    static INJECTED_FAILURE: RefCell<Option<(u64, InjectedFailure)>> = RefCell::new(None);
+}
+
+/// The kind of failure that is injected into [crate::collapse_wave].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InjectedFailure {
+    /// fail with [PropagationError::InvalidSuperposition]
+    Contradiction,
+    /// fail with [EngineError::IterationError]
+    IterationError,
+}
+
+impl From<InjectedFailure> for WaveCollapseError {
+    fn from(failure: InjectedFailure) -> Self {
+        match failure {
+            InjectedFailure::Contradiction => PropagationError::InvalidSuperposition.into(),
+            InjectedFailure::IterationError => EngineError::IterationError.into(),
+        }
+    }
+}
+
+/// Removes the injected failure when dropped.
+#[must_use = "the failure is removed as soon as the guard is dropped"]
+pub struct InjectionGuard {
+    _private: (),
+}
+
+impl Drop for InjectionGuard {
+    fn drop(&mut self) {
+        INJECTED_FAILURE.with(|injected| injected.borrow_mut().take());
+    }
+}
+
+/// Makes the next [crate::collapse_wave] driven on the current thread fail with `failure` before
+/// collapsing the node of `step`, where step 0 is the first node collapsed.
+///
+/// The failure is only triggered once and removed when the returned guard is dropped.
+///
+/// # Example
+/// ```no_run
+/// use wave_collapse::testing::inject::{inject_failure, InjectedFailure};
+///
+/// let _guard = inject_failure(3, InjectedFailure::Contradiction);
+/// // collapse_wave(...) now fails after 3 iterations
+/// ```
+pub fn inject_failure(step: u64, failure: InjectedFailure) -> InjectionGuard {
+    INJECTED_FAILURE.with(|injected| *injected.borrow_mut() = Some((step, failure)));
+    InjectionGuard { _private: () }
+}
+
+/// returns the injected failure for `step`, if there is one. Called by [crate::collapse_wave].
+pub(crate) fn take_injected_failure(step: u64) -> Option<WaveCollapseError> {
+    INJECTED_FAILURE.with(|injected| {
+        let mut injected = injected.borrow_mut();
+        match *injected {
+            Some((failure_step, failure)) if failure_step == step => {
+                injected.take();
+                Some(failure.into())
+            }
+            _ => None,
+        }
+    })
+}
@@ -0,0 +1,148 @@
+//! Shrinking of failing collapses into minimal reproductions, e.g. for bug reports against a
+//! [WaveSolver] or the collapse itself.
+
+use std::{fmt::Debug, mem::discriminant};
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::error::WaveCollapseError;
+use crate::tile2d::{Index2D, Size2D, TileMap2D};
+use crate::wave_function::{WaveKernel, WaveSolver};
+use crate::{collapse_wave, GenIterReturnResult};
+
+/// A collapse of a map of `size` with `tiles` as the possible values and a [StdRng] seeded with
+/// `seed`, see [minimize].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailingCase<NodeValue> {
+    pub seed: u64,
+    pub size: Size2D,
+    pub tiles: Vec<NodeValue>,
+}
+
+/// The result of [minimize].
+#[derive(Debug)]
+pub struct Minimized<NodeValue> {
+    /// the smallest case that still fails.
+    pub case: FailingCase<NodeValue>,
+    /// the error of `case`.
+    pub error: WaveCollapseError,
+    /// the number of collapses that were run.
+    pub attempts: u32,
+}
+
+/// returns `true` if both errors are the same variant, ignoring their context.
+fn same_kind(a: &WaveCollapseError, b: &WaveCollapseError) -> bool {
+    match (a, b) {
+        (WaveCollapseError::Propagation(a, _), WaveCollapseError::Propagation(b, _)) => {
+            discriminant(a) == discriminant(b)
+        }
+        (WaveCollapseError::Engine(a, _), WaveCollapseError::Engine(b, _)) => {
+            discriminant(a) == discriminant(b)
+        }
+        _ => false,
+    }
+}
+
+/// Shrinks a failing `case` to a smaller map and fewer tiles that still fail with the same kind
+/// of error, e.g. a contradiction.
+///
+/// Each candidate is created with `create_shape` and collapsed with the seed of the current
+/// case and the following `seeds - 1` seeds, because a smaller map consumes the rng
+/// differently. The map size is halved or reduced by one along each axis and each tile is
+/// removed, until no candidate fails anymore. The result only depends on the inputs, so it can
+/// be shared as a reproduction. The algorithm of [StdRng] may change between versions of
+/// [rand].
+///
+/// returns [None] if `case` does not fail.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Cutoff};
+/// use rand::{rngs::StdRng, SeedableRng};
+/// use wave_collapse::testing::fuzz::RandomTileset;
+/// use wave_collapse::testing::minimize::{minimize, FailingCase};
+///
+/// let tileset = RandomTileset::generate_with_count(&mut StdRng::seed_from_u64(3), 12, 0.3);
+/// let case = FailingCase {
+///     seed: 42,
+///     size: Size2D::square(64),
+///     tiles: tileset.tiles.clone(),
+/// };
+/// let minimized = minimize::<_, Kernel2D<Cutoff, u32>, _, _>(
+///     case,
+///     |size, tiles| TileMap2D::new(size, Size2D::square(3), tiles),
+///     &tileset,
+///     4,
+/// );
+/// if let Some(minimized) = minimized {
+///     println!("{:?} fails with {}", minimized.case, minimized.error);
+/// }
+/// ```
+pub fn minimize<NodeValue, Kernel, Solver, CreateShape>(
+    case: FailingCase<NodeValue>,
+    create_shape: CreateShape,
+    solver: &Solver,
+    seeds: u64,
+) -> Option<Minimized<NodeValue>>
+where
+    NodeValue: Clone + PartialEq + Debug,
+    Kernel: WaveKernel<Index2D, NodeValue, TileMap2D<NodeValue>>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+    CreateShape: Fn(Size2D, &[NodeValue]) -> TileMap2D<NodeValue>,
+{
+    assert!(seeds > 0, "at least one seed is required");
+
+    let mut attempts = 0;
+    let mut run = |seed: u64, size: Size2D, tiles: &[NodeValue]| {
+        attempts += 1;
+        let mut rng = StdRng::seed_from_u64(seed);
+        collapse_wave::<_, _, _, Kernel, _>(create_shape(size, tiles), solver, &mut rng)
+            .calc_result()
+            .err()
+    };
+
+    let mut error = run(case.seed, case.size, &case.tiles)?;
+    let mut case = case;
+
+    'shrink: loop {
+        let Size2D { width, height } = case.size;
+        let mut candidates = Vec::new();
+        for size in [
+            Size2D::new(width / 2, height),
+            Size2D::new(width - 1, height),
+            Size2D::new(width, height / 2),
+            Size2D::new(width, height - 1),
+        ] {
+            if size.width > 0 && size.height > 0 && size != case.size {
+                candidates.push((size, case.tiles.clone()));
+            }
+        }
+        if case.tiles.len() > 1 {
+            for index in 0..case.tiles.len() {
+                let mut tiles = case.tiles.clone();
+                tiles.remove(index);
+                candidates.push((case.size, tiles));
+            }
+        }
+
+        for (size, tiles) in candidates {
+            for seed in (0..seeds).map(|offset| case.seed.wrapping_add(offset)) {
+                match run(seed, size, &tiles) {
+                    Some(candidate_error) if same_kind(&candidate_error, &error) => {
+                        case = FailingCase { seed, size, tiles };
+                        error = candidate_error;
+                        continue 'shrink;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        break;
+    }
+
+    Some(Minimized {
+        case,
+        error,
+        attempts,
+    })
+}
@@ -1,11 +1,22 @@
-use std::cell::RefCell;
+use std::cell::{Ref, RefCell};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
+use std::ops::Index;
 use std::rc::Rc;
+use std::sync::Arc;
 
 use vecgrid::Vecgrid;
 
-use crate::node::{Node, NodeIdIter};
-use crate::wave_function::{WaveKernel, WaveShape};
+use crate::digest::Fnv1aHasher;
+use crate::domain::Domain;
+use crate::error::{EngineError, Result};
+use crate::memoize::KernelFingerprint;
+use crate::node::{DirectedNodeIter, Node, NodeIdIter};
+use crate::rng::EntropySource;
+use crate::wave_function::{
+    lowest_entropy_candidates, Pruning, ReadShape, WaveKernel, WaveShape, WaveSolver,
+};
 
 use gen_iter::gen_iter;
 
@@ -27,11 +38,228 @@ impl Size2D {
     }
 }
 
+/// A direction from the center of a [Kernel2D] to one of its 8 direct neighbors.
+/// `Top` is the direction of decreasing `y`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Direction {
+    /// the 4 orthogonal directions.
+    pub const ORTHOGONAL: [Direction; 4] = [
+        Direction::Top,
+        Direction::Right,
+        Direction::Bottom,
+        Direction::Left,
+    ];
+
+    /// all 8 directions.
+    pub const ALL: [Direction; 8] = [
+        Direction::Top,
+        Direction::TopRight,
+        Direction::Right,
+        Direction::BottomRight,
+        Direction::Bottom,
+        Direction::BottomLeft,
+        Direction::Left,
+        Direction::TopLeft,
+    ];
+
+    /// returns the offset `(x, y)` of the neighbor in this direction.
+    pub fn offset(&self) -> (i64, i64) {
+        match self {
+            Direction::Top => (0, -1),
+            Direction::Bottom => (0, 1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+            Direction::TopLeft => (-1, -1),
+            Direction::TopRight => (1, -1),
+            Direction::BottomLeft => (-1, 1),
+            Direction::BottomRight => (1, 1),
+        }
+    }
+
+    /// returns the direction pointing the other way.
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::Top => Direction::Bottom,
+            Direction::Bottom => Direction::Top,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+            Direction::TopLeft => Direction::BottomRight,
+            Direction::TopRight => Direction::BottomLeft,
+            Direction::BottomLeft => Direction::TopRight,
+            Direction::BottomRight => Direction::TopLeft,
+        }
+    }
+}
+
+/// Selects which nodes within the kernel size are part of a [Kernel2D].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Neighborhood {
+    /// all nodes within the kernel size, e.g. the 8-neighborhood for a kernel size of 3.
+    #[default]
+    Full,
+    /// only nodes in the same row or column as the center, e.g. the 4-neighborhood for a
+    /// kernel size of 3.
+    Orthogonal,
+    /// the center and the nodes at the given offsets, e.g. knight moves or the node 5 to the
+    /// right. The offsets are not limited by the kernel size, which allows constraints between
+    /// nodes that are not adjacent.
+    /// The inverse of each offset is also part of the neighborhood, otherwise changes to a node
+    /// would not propagate back to the nodes that depend on it.
+    Offsets(Arc<[(i64, i64)]>),
+}
+
+impl Neighborhood {
+    /// returns `true` if the node at the offset `(x, y)` from the center is part of the
+    /// neighborhood. This ignores the kernel size.
+    pub fn contains(&self, x: i64, y: i64) -> bool {
+        match self {
+            Neighborhood::Full => true,
+            Neighborhood::Orthogonal => x == 0 || y == 0,
+            Neighborhood::Offsets(offsets) => {
+                (x, y) == (0, 0) || offsets.contains(&(x, y)) || offsets.contains(&(-x, -y))
+            }
+        }
+    }
+
+    /// returns all offsets that are part of the neighborhood for the given kernel radius.
+    /// The center `(0, 0)` is always included.
+    pub fn offsets(&self, radius_x: i64, radius_y: i64) -> Vec<(i64, i64)> {
+        match self {
+            Neighborhood::Offsets(offsets) => {
+                let mut result = vec![(0, 0)];
+                for &(x, y) in offsets.iter() {
+                    for offset in [(x, y), (-x, -y)] {
+                        if !result.contains(&offset) {
+                            result.push(offset);
+                        }
+                    }
+                }
+                result
+            }
+            _ => {
+                let mut offsets = Vec::new();
+                for y in -radius_y..=radius_y {
+                    for x in -radius_x..=radius_x {
+                        if self.contains(x, y) {
+                            offsets.push((x, y));
+                        }
+                    }
+                }
+                offsets
+            }
+        }
+    }
+}
+
+/// The shift applied by [wrapping_mode::StaggeredWrapping] when a kernel wraps around an
+/// edge of a [TileMap2D].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stagger {
+    /// the shift along the y axis each time the left or right edge is crossed.
+    pub horizontal: i64,
+    /// the shift along the x axis each time the top or bottom edge is crossed.
+    pub vertical: i64,
+}
+
+/// looks up the weight of a value, see [TileMap2D::with_weights].
+type WeightLookup<NodeValue> = fn(&HashMap<NodeValue, f32>, &NodeValue) -> Option<f32>;
+
+/// The optional settings of a [TileMap2D] that change how nodes and values are chosen or what
+/// is logged, together with the state they need during a collapse. A map only allocates this
+/// once one of the settings is used.
+pub(crate) struct TileMap2DConfig<NodeValue> {
+    /// the number of recently collapsed nodes, that ties are broken against, see
+    /// [TileMap2D::with_spread].
+    spread: usize,
+    recent: RefCell<VecDeque<Index2D>>,
+
+    /// the weight of each value and a function that looks up the weight of a value, because
+    /// that needs `NodeValue: Hash`, see [TileMap2D::with_weights].
+    weights: Option<(HashMap<NodeValue, f32>, WeightLookup<NodeValue>)>,
+    /// the bias of each node row by row and the class of values it applies to, see
+    /// [TileMap2D::with_bias].
+    biases: Vec<(Vec<f32>, fn(&NodeValue) -> bool)>,
+    /// the nodes whose removed values are logged and the log, see
+    /// [TileMap2D::with_pruning_log].
+    traced: HashSet<Index2D>,
+    prunings: RefCell<HashMap<Index2D, Vec<Pruning<Index2D, NodeValue>>>>,
+}
+
+impl<NodeValue> Default for TileMap2DConfig<NodeValue> {
+    fn default() -> Self {
+        TileMap2DConfig {
+            spread: 0,
+            recent: RefCell::new(VecDeque::new()),
+            weights: None,
+            biases: Vec::new(),
+            traced: HashSet::new(),
+            prunings: RefCell::new(HashMap::new()),
+        }
+    }
+}
+
+impl<NodeValue: Clone> TileMap2DConfig<NodeValue> {
+    /// returns a copy of the settings without the state of a collapse, e.g. for a new map
+    /// that is built from the nodes of this one.
+    pub(crate) fn settings(&self) -> Self {
+        TileMap2DConfig {
+            spread: self.spread,
+            weights: self.weights.clone(),
+            biases: self.biases.clone(),
+            traced: self.traced.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// like [TileMap2DConfig::settings], but for the part of a map of `map_size` from `origin`
+    /// with `size`, e.g. a chunk that is collapsed on its own. The bias masks only contain the
+    /// nodes of the part. No prunings are logged, because the part is discarded after the
+    /// collapse.
+    pub(crate) fn crop(&self, map_size: Size2D, origin: Index2D, size: Size2D) -> Self {
+        let (origin_x, origin_y) = origin;
+        let biases = self
+            .biases
+            .iter()
+            .map(|(mask, class)| {
+                let mask = (origin_y..origin_y + size.height)
+                    .flat_map(|y| {
+                        (origin_x..origin_x + size.width)
+                            .map(move |x| mask[(y * map_size.width + x) as usize])
+                    })
+                    .collect();
+                (mask, *class)
+            })
+            .collect();
+
+        TileMap2DConfig {
+            spread: self.spread,
+            weights: self.weights.clone(),
+            biases,
+            ..Default::default()
+        }
+    }
+}
+
 pub struct TileMap2D<NodeValue: Clone> {
     size: Size2D,
     kernel_size: Size2D,
+    neighborhood: Neighborhood,
+    chunk_size: Option<Size2D>,
+    stagger: Stagger,
 
     last_collapsed: RefCell<Option<Index2D>>,
+    config: Option<Box<TileMap2DConfig<NodeValue>>>,
 
     nodes: Vecgrid<Node<Index2D, NodeValue>>,
 }
@@ -39,22 +267,198 @@ pub struct TileMap2D<NodeValue: Clone> {
 impl<NodeValue: Clone> TileMap2D<NodeValue> {
     /// Create a new [TileMap2D]. `kernel_size` must be uneven in both widht and height. `possible_values` must not be empty.
     pub fn new(size: Size2D, kernel_size: Size2D, possible_values: &[NodeValue]) -> Self {
+        assert!(!possible_values.is_empty(), "At least one value required!");
+        Self::from_values(size, kernel_size, possible_values.iter().cloned())
+            .expect("possible values are not empty")
+    }
+
+    /// like [TileMap2D::new], but each value has a weight. Values with a higher weight are chosen
+    /// more often and nodes are chosen by their [Node::weighted_entropy], so solvers do not need
+    /// to know about the weights. Weights must not be negative. Values with a weight of 0 or
+    /// without a weight are only chosen if a node has no value with a positive weight left.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use wave_collapse::tile2d::{Size2D, TileMap2D};
+    /// // grass is 8 times more common than water
+    /// let shape = TileMap2D::new_weighted(
+    ///     Size2D::square(10),
+    ///     Size2D::square(3),
+    ///     &[("grass", 8.0), ("water", 1.0)],
+    /// );
+    /// ```
+    pub fn new_weighted(
+        size: Size2D,
+        kernel_size: Size2D,
+        weighted_values: &[(NodeValue, f32)],
+    ) -> Self
+    where
+        NodeValue: Eq + Hash,
+    {
+        let values: Vec<NodeValue> = weighted_values.iter().map(|(v, _)| v.clone()).collect();
+        Self::new(size, kernel_size, &values).with_weights(weighted_values)
+    }
+
+    /// Sets the weight of each value, see [TileMap2D::new_weighted], e.g. for a map created with
+    /// [TileMap2D::from_values] or the next pass of a [crate::pipeline::Pipeline]. If a value
+    /// is listed more than once, its first weight is used.
+    pub fn with_weights(mut self, weighted_values: &[(NodeValue, f32)]) -> Self
+    where
+        NodeValue: Eq + Hash,
+    {
+        assert!(
+            weighted_values.iter().all(|(_, weight)| *weight >= 0.0),
+            "weights must not be negative"
+        );
+        let mut weights = HashMap::with_capacity(weighted_values.len());
+        for (value, weight) in weighted_values {
+            weights.entry(value.clone()).or_insert(*weight);
+        }
+        self.config_mut().weights = Some((weights, |weights, value| weights.get(value).copied()));
+        self
+    }
+
+    /// Changes the weight of `value`, see [TileMap2D::new_weighted], e.g. between the steps of a
+    /// [crate::wave_function::Collapser]. The weight must not be negative.
+    ///
+    /// returns `false` if `value` has no weight, e.g. because the map is not weighted.
+    pub fn set_weight(&mut self, value: &NodeValue, weight: f32) -> bool
+    where
+        NodeValue: Eq + Hash,
+    {
+        assert!(weight >= 0.0, "weights must not be negative");
+        let old = self
+            .config
+            .as_mut()
+            .and_then(|config| config.weights.as_mut())
+            .and_then(|(weights, _)| weights.get_mut(value));
+        match old {
+            Some(old) => {
+                *old = weight;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// like [TileMap2D::new], but the possible values can be any [IntoIterator], e.g. an iterator
+    /// chain that generates the rotations of each tile.
+    ///
+    /// returns [EngineError::EmptyInput] if `possible_values` is empty.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use wave_collapse::tile2d::{Size2D, TileMap2D};
+    /// let tiles = [0b0000_0011u8, 0b0000_0111];
+    /// let rotations = tiles
+    ///     .iter()
+    ///     .flat_map(|tile| (0..4).map(move |r| tile.rotate_left(2 * r)));
+    /// let shape = TileMap2D::from_values(Size2D::square(10), Size2D::square(3), rotations)
+    ///     .expect("there are tiles");
+    /// ```
+    pub fn from_values(
+        size: Size2D,
+        kernel_size: Size2D,
+        possible_values: impl IntoIterator<Item = NodeValue>,
+    ) -> Result<Self> {
+        assert!(kernel_size.width % 2 == 1, "Kernel width must be uneven");
+        assert!(kernel_size.height % 2 == 1, "Kernel height must be uneven");
+
+        let possible_values: Vec<NodeValue> = possible_values.into_iter().collect();
+        if possible_values.is_empty() {
+            return Err(EngineError::EmptyInput.into());
+        }
+
+        let mut data = Vec::new();
+        for y in 0..size.height {
+            for x in 0..size.width {
+                data.push(Node::new((x, y), possible_values.as_slice()));
+            }
+        }
+
+        Ok(Self::from_nodes(size, kernel_size, data))
+    }
+
+    /// Create a new [TileMap2D] from an authored `template`, which contains the value of each
+    /// node row by row. Nodes with a value are collapsed, the holes can be any of
+    /// `possible_values`.
+    ///
+    /// The holes are not validated against the authored nodes yet, use
+    /// [crate::wave_function::propagate_collapsed] before collapsing the map.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Cutoff};
+    /// # use wave_collapse::wave_function::WaveSolver;
+    /// use wave_collapse::collapse_wave;
+    /// use wave_collapse::wave_function::propagate_collapsed;
+    /// # struct TileSolver;
+    /// # impl WaveSolver<char, Kernel2D<Cutoff, char>> for TileSolver {
+    /// #     fn is_valid(&self, _value: &char, _kernel: &Kernel2D<Cutoff, char>) -> bool {
+    /// #         todo!()
+    /// #     }
+    /// # }
+    /// let template = [
+    ///     Some('#'), Some('#'), Some('#'),
+    ///     Some('#'), None,      Some('.'),
+    ///     Some('#'), None,      Some('.'),
+    /// ];
+    /// let shape =
+    ///     TileMap2D::from_template(Size2D::square(3), Size2D::square(3), &template, &['#', '.']);
+    /// let shape = propagate_collapsed::<_, _, _, Kernel2D<Cutoff, char>, _>(shape, &TileSolver)
+    ///     .expect("template is consistent");
+    /// let mut rng = rand::thread_rng();
+    /// let result = collapse_wave::<_, _, _, Kernel2D<Cutoff, char>, _>(shape, &TileSolver, &mut rng);
+    /// ```
+    pub fn from_template(
+        size: Size2D,
+        kernel_size: Size2D,
+        template: &[Option<NodeValue>],
+        possible_values: &[NodeValue],
+    ) -> Self {
         assert!(kernel_size.width % 2 == 1, "Kernel width must be uneven");
         assert!(kernel_size.height % 2 == 1, "Kernel height must be uneven");
         assert!(!possible_values.is_empty(), "At least one value required!");
+        assert_eq!(
+            template.len(),
+            (size.width * size.height) as usize,
+            "template must contain a value or hole for each node"
+        );
 
         let mut data = Vec::new();
         for y in 0..size.height {
             for x in 0..size.width {
-                data.push(Node::new((x, y), possible_values));
+                let node = match &template[(y * size.width + x) as usize] {
+                    Some(value) => {
+                        let node = Node::new((x, y), vec![value.clone()]);
+                        *node.is_collapsed.borrow_mut() = true;
+                        node
+                    }
+                    None => Node::new((x, y), possible_values),
+                };
+                data.push(node);
             }
         }
 
+        Self::from_nodes(size, kernel_size, data)
+    }
+
+    /// Create a new [TileMap2D] from `nodes`, which are ordered row by row. The id of each node
+    /// must match its position.
+    pub(crate) fn from_nodes(
+        size: Size2D,
+        kernel_size: Size2D,
+        nodes: Vec<Node<Index2D, NodeValue>>,
+    ) -> Self {
         TileMap2D {
             size,
             kernel_size,
+            neighborhood: Neighborhood::default(),
+            chunk_size: None,
+            stagger: Stagger::default(),
             last_collapsed: RefCell::new(None),
-            nodes: Vecgrid::from_column_major(data, size.width as usize, size.height as usize)
+            config: None,
+            nodes: Vecgrid::from_column_major(nodes, size.width as usize, size.height as usize)
                 .expect("data size should be valid"),
         }
     }
@@ -80,6 +484,49 @@ impl<NodeValue: Clone> TileMap2D<NodeValue> {
         }
     }
 
+    /// returns a digest of the collapsed values or [None] if not all nodes are collapsed.
+    /// The digest is calculated with a [Fnv1aHasher] so it can be used to compare results across
+    /// runs and crate versions.
+    pub fn collapsed_digest(&self) -> Option<u64>
+    where
+        NodeValue: Hash,
+    {
+        let collapsed = self.get_collapsed()?;
+
+        let mut hasher = Fnv1aHasher::default();
+        self.size.width.hash(&mut hasher);
+        self.size.height.hash(&mut hasher);
+        for value in collapsed.elements_row_major_iter() {
+            value.hash(&mut hasher);
+        }
+        Some(hasher.finish())
+    }
+
+    /// returns an [Iterator] over the collapsed values and their ids, row by row starting at the
+    /// top left, so the value at `(x, y)` is the `y * width + x`th item of a collapsed map.
+    /// Nodes that are not collapsed are skipped.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use wave_collapse::tile2d::{Size2D, TileMap2D};
+    /// # let shape = TileMap2D::new(Size2D::square(10), Size2D::square(3), &[0u32, 1]);
+    /// let mut buffer = vec![0; 100];
+    /// for ((x, y), value) in &shape {
+    ///     buffer[(y * 10 + x) as usize] = *value;
+    /// }
+    /// ```
+    pub fn iter(&self) -> CollapsedIter<'_, NodeValue> {
+        CollapsedIter {
+            tile_map: self,
+            ids: self.iter_node_ids(),
+        }
+    }
+
+    /// returns the node at `(x, y)` or [None] if it is outside of the map.
+    pub fn get(&self, x: u32, y: u32) -> Option<&Node<Index2D, NodeValue>> {
+        self.get_node(&(x, y))
+    }
+
     pub fn size(&self) -> &Size2D {
         &self.size
     }
@@ -87,9 +534,310 @@ impl<NodeValue: Clone> TileMap2D<NodeValue> {
     pub fn kernel_size(&self) -> &Size2D {
         &self.kernel_size
     }
+
+    /// Sets the [Neighborhood] used by the kernels of this [TileMap2D]. Solvers that only
+    /// define rules between orthogonal neighbors should use [Neighborhood::Orthogonal], to avoid
+    /// propagating changes to diagonal neighbors.
+    pub fn with_neighborhood(mut self, neighborhood: Neighborhood) -> Self {
+        self.neighborhood = neighborhood;
+        self
+    }
+
+    pub fn neighborhood(&self) -> &Neighborhood {
+        &self.neighborhood
+    }
+
+    /// Splits the map into chunks of `chunk_size`. Kernels using
+    /// [wrapping_mode::ChunkWrapping] wrap around within the chunk of their center node, which
+    /// produces chunks that can be repeated seamlessly. `chunk_size` must divide the map size.
+    pub fn with_chunk_size(mut self, chunk_size: Size2D) -> Self {
+        assert!(
+            chunk_size.width > 0 && self.size.width % chunk_size.width == 0,
+            "Chunk width must divide the map width"
+        );
+        assert!(
+            chunk_size.height > 0 && self.size.height % chunk_size.height == 0,
+            "Chunk height must divide the map height"
+        );
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    /// returns the chunk size. If no chunk size was set the whole map is a single chunk.
+    pub fn chunk_size(&self) -> Size2D {
+        self.chunk_size.unwrap_or(self.size)
+    }
+
+    /// Sets the [Stagger] used by kernels with [wrapping_mode::StaggeredWrapping].
+    /// If both shifts are set, the horizontal edges are resolved first.
+    pub fn with_stagger(mut self, stagger: Stagger) -> Self {
+        self.stagger = stagger;
+        self
+    }
+
+    pub fn stagger(&self) -> Stagger {
+        self.stagger
+    }
+
+    /// Copies the settings of `other`, which must have the same size as this map: the
+    /// [Neighborhood], chunk size, [Stagger], spread, weights, biases and the nodes whose
+    /// prunings are logged. The state of a collapse, e.g. the pruning log, is not copied.
+    /// Use this to restore the settings of a map that was loaded from a
+    /// [crate::snapshot::TileMapSnapshot].
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use wave_collapse::tile2d::{Size2D, TileMap2D};
+    /// let shape = TileMap2D::new_weighted(
+    ///     Size2D::square(10),
+    ///     Size2D::square(3),
+    ///     &[("grass", 8.0), ("water", 1.0)],
+    /// )
+    /// .with_spread(8);
+    /// let loaded = TileMap2D::from_snapshot(&shape.snapshot()).with_settings_of(&shape);
+    /// ```
+    pub fn with_settings_of(mut self, other: &TileMap2D<NodeValue>) -> Self {
+        assert_eq!(
+            self.size, other.size,
+            "settings must be copied from a map of the same size"
+        );
+        self.neighborhood = other.neighborhood.clone();
+        self.chunk_size = other.chunk_size;
+        self.stagger = other.stagger;
+        self.with_config(other.config())
+    }
+
+    /// returns a copy of the optional settings of this map without the state of a collapse, see
+    /// [TileMap2DConfig::settings].
+    pub(crate) fn config(&self) -> Option<Box<TileMap2DConfig<NodeValue>>> {
+        self.config
+            .as_ref()
+            .map(|config| Box::new(config.settings()))
+    }
+
+    /// Replaces the optional settings of this map, e.g. with the [TileMap2D::config] of the map
+    /// this one is built from.
+    pub(crate) fn with_config(mut self, config: Option<Box<TileMap2DConfig<NodeValue>>>) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// like [TileMap2D::config], but for the part of this map from `origin` with `size`, see
+    /// [TileMap2DConfig::crop].
+    pub(crate) fn cropped_config(
+        &self,
+        origin: Index2D,
+        size: Size2D,
+    ) -> Option<Box<TileMap2DConfig<NodeValue>>> {
+        self.config
+            .as_ref()
+            .map(|config| Box::new(config.crop(self.size, origin, size)))
+    }
+
+    fn config_mut(&mut self) -> &mut TileMap2DConfig<NodeValue> {
+        self.config.get_or_insert_with(Default::default)
+    }
+
+    /// Sets the kernel size to `radius_x` nodes on both sides of the center horizontally and
+    /// `radius_y` nodes vertically, e.g. to look 3 nodes ahead horizontally but only 1
+    /// vertically in a platformer. Solvers that declare [WaveSolver::kernel_radii] override this,
+    /// see [TileMap2D::validate_solver].
+    pub fn with_kernel_radius(mut self, radius_x: u32, radius_y: u32) -> Self {
+        self.kernel_size = Size2D::new(2 * radius_x + 1, 2 * radius_y + 1);
+        self
+    }
+
+    /// Checks that the kernels used by `solver` fit into this map. These are the
+    /// [WaveSolver::kernel_radii] of `solver` if it declares them, otherwise the kernel size of
+    /// this map, see [TileMap2D::with_kernel_radius].
+    ///
+    /// returns [EngineError::KernelLargerThanShape] if a kernel is larger than the map.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Cutoff};
+    /// # use wave_collapse::wave_function::WaveSolver;
+    /// # struct PlatformSolver;
+    /// # impl WaveSolver<char, Kernel2D<Cutoff, char>> for PlatformSolver {
+    /// #     fn is_valid(&self, _value: &char, _kernel: &Kernel2D<Cutoff, char>) -> bool {
+    /// #         todo!()
+    /// #     }
+    /// #     fn kernel_radii(&self) -> Option<Vec<u32>> {
+    /// #         Some(vec![3, 1])
+    /// #     }
+    /// # }
+    /// let shape = TileMap2D::new(Size2D::new(64, 16), Size2D::square(1), &['#', '.', '='])
+    ///     .with_kernel_radius(3, 1);
+    /// shape
+    ///     .validate_solver::<Kernel2D<Cutoff, char>, _>(&PlatformSolver)
+    ///     .expect("kernels fit into the map");
+    /// ```
+    pub fn validate_solver<Kernel, Solver>(&self, solver: &Solver) -> Result<()>
+    where
+        Solver: WaveSolver<NodeValue, Kernel>,
+    {
+        let kernel_size = match solver.kernel_radii() {
+            Some(radii) => {
+                let x = radii.first().copied().unwrap_or_default();
+                let y = radii.get(1).copied().unwrap_or(x);
+                Size2D::new(2 * x + 1, 2 * y + 1)
+            }
+            None => self.kernel_size,
+        };
+        if kernel_size.width > self.size.width || kernel_size.height > self.size.height {
+            let format_size = |size: Size2D| format!("{}x{}", size.width, size.height);
+            return Err(EngineError::KernelLargerThanShape {
+                kernel: format_size(kernel_size),
+                shape: format_size(self.size),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Breaks ties between nodes with the lowest entropy by spatial spread. Instead of choosing
+    /// one of them uniformly, each node is chosen with a weight of its squared distance to the
+    /// closest of the last `recent` collapsed nodes. The collapse spreads over the map like blue
+    /// noise instead of burrowing into one corner, which makes intermediate states more uniform,
+    /// e.g. for animations. A `recent` of 0 disables this.
+    ///
+    /// This still makes a single call to the rng per selection, see
+    /// [crate::rng::EntropySource::choose_node], but the chosen nodes differ from the default.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use wave_collapse::tile2d::{Size2D, TileMap2D};
+    /// let shape =
+    ///     TileMap2D::new(Size2D::square(64), Size2D::square(3), &[0u32, 1, 2]).with_spread(16);
+    /// ```
+    pub fn with_spread(mut self, recent: usize) -> Self {
+        let config = self.config_mut();
+        config.spread = recent;
+        config.recent.get_mut().clear();
+        self
+    }
+
+    /// returns the number of recently collapsed nodes used to break ties, see
+    /// [TileMap2D::with_spread].
+    pub fn spread(&self) -> usize {
+        self.config.as_ref().map_or(0, |config| config.spread)
+    }
+
+    /// Scales the weight of each value in `class` by the bias of its node, e.g. to paint more
+    /// forest in one region and more rock in another. `mask` contains the bias of each node row
+    /// by row, e.g. the brightness of a grayscale image. A bias of 2 makes the values of the class
+    /// twice as likely. A bias of 0 only chooses them if no value with a positive weight is left
+    /// in the node, e.g. because the solver removed all others. Biases must not be negative.
+    ///
+    /// The biases only change which values are chosen, so they are respected statistically, but
+    /// the solver can still force a value anywhere. The bias multiplies the weight of the value,
+    /// see [TileMap2D::new_weighted], and multiple biases multiply each other.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use wave_collapse::tile2d::{Size2D, TileMap2D};
+    /// let size = Size2D::square(64);
+    /// // more forest on the left, none on the right
+    /// let forest: Vec<f32> = (0..size.height)
+    ///     .flat_map(|_| (0..size.width).map(|x| 2.0 * (1.0 - x as f32 / 63.0)))
+    ///     .collect();
+    /// let shape = TileMap2D::new(size, Size2D::square(3), &["grass", "forest", "rock"])
+    ///     .with_bias(forest, |tile| *tile == "forest");
+    /// ```
+    pub fn with_bias(mut self, mask: Vec<f32>, class: fn(&NodeValue) -> bool) -> Self {
+        assert_eq!(
+            mask.len(),
+            (self.size.width * self.size.height) as usize,
+            "mask must contain a bias for each node"
+        );
+        assert!(
+            mask.iter().all(|bias| *bias >= 0.0),
+            "biases must not be negative"
+        );
+        self.config_mut().biases.push((mask, class));
+        self
+    }
+
+    /// Logs why values are removed from the `nodes`, so a tileset can be debugged by asking why
+    /// a node can never have a value, see [TileMap2D::pruning_log]. Logging all nodes of a
+    /// large map is slow, so only select the nodes of interest.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Cutoff};
+    /// # use wave_collapse::wave_function::WaveSolver;
+    /// use wave_collapse::collapse_wave;
+    /// # struct TestSolver;
+    /// # impl WaveSolver<&str, Kernel2D<Cutoff, &str>> for TestSolver {
+    /// #     fn is_valid(&self, _value: &&str, _kernel: &Kernel2D<Cutoff, &str>) -> bool {
+    /// #         todo!()
+    /// #     }
+    /// # }
+    /// let shape = TileMap2D::new(Size2D::square(10), Size2D::square(3), &["wall", "door"])
+    ///     .with_pruning_log([(4, 0)]);
+    /// let mut rng = rand::thread_rng();
+    /// let mut collapse =
+    ///     collapse_wave::<_, _, _, Kernel2D<Cutoff, &str>, _>(shape, &TestSolver, &mut rng);
+    /// let shape = Iterator::last(&mut &mut collapse).expect("there is at least one step");
+    /// if let Some(pruning) = shape.why_pruned((4, 0), &"door") {
+    ///     println!("no door in step {} because of {:?}", pruning.step, pruning.causes);
+    /// }
+    /// ```
+    pub fn with_pruning_log(mut self, nodes: impl IntoIterator<Item = Index2D>) -> Self {
+        self.config_mut().traced.extend(nodes);
+        self
+    }
+
+    /// returns why values were removed from the node `id`, oldest first. This is empty unless
+    /// the node was selected with [TileMap2D::with_pruning_log]. Values that were restored,
+    /// e.g. by backtracking, are still part of the log.
+    pub fn pruning_log(&self, id: Index2D) -> Vec<Pruning<Index2D, NodeValue>> {
+        self.config
+            .as_ref()
+            .and_then(|config| config.prunings.borrow().get(&id).cloned())
+            .unwrap_or_default()
+    }
+
+    /// returns why `value` was last removed from the node `id`, see [TileMap2D::pruning_log].
+    pub fn why_pruned(&self, id: Index2D, value: &NodeValue) -> Option<Pruning<Index2D, NodeValue>>
+    where
+        NodeValue: PartialEq,
+    {
+        self.pruning_log(id)
+            .into_iter()
+            .rev()
+            .find(|pruning| pruning.value == *value)
+    }
 }
 
-impl<NodeValue> WaveShape<Index2D, NodeValue> for TileMap2D<NodeValue>
+impl<NodeValue> TileMap2D<Arc<NodeValue>> {
+    /// like [TileMap2D::new], but each value of `palette` is stored behind an [Arc] and shared by
+    /// all nodes. Cloning a shared value only increments the reference count, so `NodeValue`
+    /// does not need to implement [Clone]. Use this for large values. The map can be sent to
+    /// another thread if `NodeValue` is [Send] and [Sync].
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use wave_collapse::tile2d::{Size2D, TileMap2D};
+    /// #[derive(Debug, PartialEq)]
+    /// struct Tile {
+    ///     name: String,
+    ///     mesh: Vec<[f32; 3]>,
+    /// }
+    /// # let (grass, water): (Tile, Tile) = todo!();
+    /// let shape = TileMap2D::from_palette(Size2D::square(100), Size2D::square(3), [grass, water]);
+    /// ```
+    pub fn from_palette(
+        size: Size2D,
+        kernel_size: Size2D,
+        palette: impl IntoIterator<Item = NodeValue>,
+    ) -> Self {
+        let palette: Vec<_> = palette.into_iter().map(Arc::new).collect();
+        Self::new(size, kernel_size, &palette)
+    }
+}
+
+impl<NodeValue> ReadShape<Index2D, NodeValue> for TileMap2D<NodeValue>
 where
     NodeValue: Clone,
 {
@@ -110,18 +858,280 @@ where
         vec.into_iter()
     }
 
+    fn get_last_collapsed_id(&self) -> Option<Index2D> {
+        *self.last_collapsed.borrow()
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.size.width == 0 || self.size.height == 0 {
+            return Err(EngineError::EmptyShape.into());
+        }
+        if self.kernel_size.width > self.size.width || self.kernel_size.height > self.size.height {
+            let format_size = |size: Size2D| format!("{}x{}", size.width, size.height);
+            return Err(EngineError::KernelLargerThanShape {
+                kernel: format_size(self.kernel_size),
+                shape: format_size(self.size),
+            }
+            .into());
+        }
+        crate::validate::validate_nodes(self)
+    }
+
+    fn is_weighted(&self) -> bool {
+        self.config
+            .as_ref()
+            .is_some_and(|config| config.weights.is_some() || !config.biases.is_empty())
+    }
+
+    fn value_weight(&self, value: &NodeValue) -> f32 {
+        match self
+            .config
+            .as_ref()
+            .and_then(|config| config.weights.as_ref())
+        {
+            Some((weights, lookup)) => lookup(weights, value).unwrap_or(0.0),
+            None => 1.0,
+        }
+    }
+
+    fn node_value_weight(&self, id: &Index2D, value: &NodeValue) -> f32 {
+        let Some(config) = &self.config else {
+            return self.value_weight(value);
+        };
+        let index = (id.1 * self.size.width + id.0) as usize;
+        config
+            .biases
+            .iter()
+            .filter(|(_, class)| class(value))
+            .fold(self.value_weight(value), |weight, (mask, _)| {
+                weight * mask[index]
+            })
+    }
+}
+
+impl<NodeValue> WaveShape<Index2D, NodeValue> for TileMap2D<NodeValue>
+where
+    NodeValue: Clone,
+{
+    fn choose_random_with_lowest_entropy_where(
+        &self,
+        rng: &mut impl EntropySource,
+        filter: impl Fn(&Node<Index2D, NodeValue>) -> bool,
+    ) -> Option<&Node<Index2D, NodeValue>> {
+        let bucket = lowest_entropy_candidates(self, filter);
+        let recent = match &self.config {
+            Some(config) if !config.recent.borrow().is_empty() => config.recent.borrow(),
+            _ => {
+                return rng
+                    .choose_node(bucket.len(), None)
+                    .map(|index| bucket[index])
+            }
+        };
+
+        let weights: Vec<f32> = bucket
+            .iter()
+            .map(|node| {
+                recent
+                    .iter()
+                    .map(|&(x, y)| {
+                        let dx = x as f32 - node.id.0 as f32;
+                        let dy = y as f32 - node.id.1 as f32;
+                        dx * dx + dy * dy
+                    })
+                    .fold(f32::INFINITY, f32::min)
+            })
+            .collect();
+        rng.choose_node(bucket.len(), Some(&weights))
+            .map(|index| bucket[index])
+    }
+
     fn set_last_collapsed_id(&self, node_id: Index2D) {
         let _ = self.last_collapsed.borrow_mut().insert(node_id);
+        let Some(config) = &self.config else {
+            return;
+        };
+        if config.spread > 0 {
+            let mut recent = config.recent.borrow_mut();
+            if recent.len() == config.spread {
+                recent.pop_front();
+            }
+            recent.push_back(node_id);
+        }
     }
 
-    fn get_last_collapsed_id(&self) -> Option<Index2D> {
-        *self.last_collapsed.borrow()
+    fn is_pruning_traced(&self, node_id: &Index2D) -> bool {
+        self.config
+            .as_ref()
+            .is_some_and(|config| config.traced.contains(node_id))
+    }
+
+    fn record_pruning(&self, node_id: Index2D, pruning: Pruning<Index2D, NodeValue>) {
+        let Some(config) = &self.config else {
+            return;
+        };
+        config
+            .prunings
+            .borrow_mut()
+            .entry(node_id)
+            .or_default()
+            .push(pruning);
+    }
+}
+
+/// Indexing a [TileMap2D] returns the node at an [Index2D] and panics if the index is outside of
+/// the map. Use [TileMap2D::get] to handle that case.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::tile2d::{Size2D, TileMap2D};
+/// let shape = TileMap2D::new(Size2D::square(10), Size2D::square(3), &[0u32, 1]);
+/// let value = shape[(3, 4)].collapsed();
+/// ```
+impl<NodeValue: Clone> Index<Index2D> for TileMap2D<NodeValue> {
+    type Output = Node<Index2D, NodeValue>;
+
+    fn index(&self, (x, y): Index2D) -> &Self::Output {
+        self.get(x, y).unwrap_or_else(|| {
+            panic!(
+                "index ({x}, {y}) is outside of a map of size {}x{}",
+                self.size.width, self.size.height
+            )
+        })
+    }
+}
+
+impl<'a, NodeValue: Clone> IntoIterator for &'a TileMap2D<NodeValue> {
+    type Item = (Index2D, Ref<'a, NodeValue>);
+    type IntoIter = CollapsedIter<'a, NodeValue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An [Iterator] over the collapsed values of a [TileMap2D], see [TileMap2D::iter].
+pub struct CollapsedIter<'a, NodeValue: Clone> {
+    tile_map: &'a TileMap2D<NodeValue>,
+    ids: NodeIdIter<Index2D>,
+}
+
+impl<'a, NodeValue: Clone> Iterator for CollapsedIter<'a, NodeValue> {
+    type Item = (Index2D, Ref<'a, NodeValue>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tile_map = self.tile_map;
+        self.ids
+            .by_ref()
+            .find_map(|id| Some((id, tile_map[id].collapsed_ref()?)))
+    }
+}
+
+pub use crate::wrapping_mode;
+
+use crate::wrapping_mode::Wrap;
+
+impl Wrap {
+    /// returns the id of the node at the offset `(x, y)` from `center`.
+    fn resolve<NodeValue: Clone>(
+        self,
+        tile_map: &TileMap2D<NodeValue>,
+        center: Index2D,
+        x: i64,
+        y: i64,
+    ) -> Option<Index2D> {
+        let size = tile_map.size;
+        let x = center.0 as i64 + x;
+        let y = center.1 as i64 + y;
+
+        match self {
+            Wrap::Cutoff => {
+                if (0..size.width as i64).contains(&x) && (0..size.height as i64).contains(&y) {
+                    Some((x as u32, y as u32))
+                } else {
+                    None
+                }
+            }
+            Wrap::Wrapping => Some((
+                x.rem_euclid(size.width as i64) as u32,
+                y.rem_euclid(size.height as i64) as u32,
+            )),
+            Wrap::Chunk => {
+                let chunk = tile_map.chunk_size();
+                let origin_x = (center.0 / chunk.width * chunk.width) as i64;
+                let origin_y = (center.1 / chunk.height * chunk.height) as i64;
+                Some((
+                    (origin_x + (x - origin_x).rem_euclid(chunk.width as i64)) as u32,
+                    (origin_y + (y - origin_y).rem_euclid(chunk.height as i64)) as u32,
+                ))
+            }
+            Wrap::Staggered => {
+                let (width, height) = (size.width as i64, size.height as i64);
+                let stagger = tile_map.stagger;
+
+                let y = y + x.div_euclid(width) * stagger.horizontal;
+                let x = x.rem_euclid(width);
+                let x = (x + y.div_euclid(height) * stagger.vertical).rem_euclid(width);
+                let y = y.rem_euclid(height);
+                Some((x as u32, y as u32))
+            }
+        }
     }
 }
 
-pub mod wrapping_mode {
-    pub struct Wrapping;
-    pub struct Cutoff;
+/// returns a hash of the possible values of the nodes at `offsets`, see [KernelFingerprint].
+/// The hash does not depend on the order of the possible values.
+fn fingerprint<'a, NodeValue: Clone + Hash + 'a>(
+    offsets: impl IntoIterator<Item = (i64, i64)>,
+    get: impl Fn(i64, i64) -> Option<&'a Node<Index2D, NodeValue>>,
+) -> u64 {
+    let mut hasher = Fnv1aHasher::default();
+    for (x, y) in offsets {
+        if (x, y) == (0, 0) {
+            continue;
+        }
+        (x, y).hash(&mut hasher);
+        match get(x, y) {
+            Some(node) => {
+                let values = node.possible_values();
+                let sum = values.iter().fold(0u64, |sum, value| {
+                    let mut value_hasher = Fnv1aHasher::default();
+                    value.hash(&mut value_hasher);
+                    sum.wrapping_add(value_hasher.finish())
+                });
+                values.len().hash(&mut hasher);
+                sum.hash(&mut hasher);
+            }
+            None => u64::MAX.hash(&mut hasher),
+        }
+    }
+    hasher.finish()
+}
+
+/// The state of the node at an offset from the center of a [Kernel2D], see [Kernel2D::get].
+pub enum NeighborState<'a, NodeValue: Clone> {
+    /// the node is part of the kernel.
+    Node(&'a Node<Index2D, NodeValue>),
+    /// the offset is part of the kernel, but the node is outside of the map. This only happens
+    /// with [wrapping_mode::Cutoff], at the edge of the map.
+    OutOfBounds,
+    /// the offset is not part of the kernel, see [Kernel2D::contains], so it does not constrain
+    /// the center.
+    OutsideKernel,
+}
+
+impl<'a, NodeValue: Clone> NeighborState<'a, NodeValue> {
+    /// returns the node, or [None] if there is no node at the offset.
+    pub fn node(&self) -> Option<&'a Node<Index2D, NodeValue>> {
+        match self {
+            NeighborState::Node(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    /// returns `true` if the node is outside of the map, see [NeighborState::OutOfBounds].
+    pub fn is_out_of_bounds(&self) -> bool {
+        matches!(self, NeighborState::OutOfBounds)
+    }
 }
 
 pub struct Kernel2D<WrappingMode, NodeValueDescription: Clone> {
@@ -129,6 +1139,8 @@ pub struct Kernel2D<WrappingMode, NodeValueDescription: Clone> {
     node_id: Index2D,
     pub radius_x: i64,
     pub radius_y: i64,
+    wrap: Wrap,
+    changed: Option<Vec<Index2D>>,
     _wrapping_mode: PhantomData<WrappingMode>,
 }
 
@@ -136,6 +1148,7 @@ impl<WrappingMode, NodeValueDescription: Clone> Kernel2D<WrappingMode, NodeValue
     fn new(
         shape: Rc<TileMap2D<NodeValueDescription>>,
         node: &Node<Index2D, NodeValueDescription>,
+        wrap: Wrap,
     ) -> Self {
         let radius_y = ((shape.kernel_size.height - 1) / 2) as i64;
         let radius_x = ((shape.kernel_size.width - 1) / 2) as i64;
@@ -145,89 +1158,483 @@ impl<WrappingMode, NodeValueDescription: Clone> Kernel2D<WrappingMode, NodeValue
             node_id: node.id,
             radius_x,
             radius_y,
+            wrap,
+            changed: None,
             _wrapping_mode: PhantomData::default(),
         }
     }
 
-    pub fn get(&self, x: i64, y: i64) -> Option<&Node<Index2D, NodeValueDescription>> {
-        if x.abs() > self.radius_x || y.abs() > self.radius_y {
-            return None;
+    /// returns the id of the node at the offset `(x, y)` from the center, taking the wrapping
+    /// mode into account.
+    fn resolve(&self, x: i64, y: i64) -> Option<Index2D> {
+        self.wrap.resolve(&self.tile_map, self.node_id, x, y)
+    }
+
+    /// sets the radius of each axis, see [WaveKernel::set_radii].
+    fn set_radii_xy(&mut self, radii: &[u32]) {
+        if let Some(&x) = radii.first() {
+            self.radius_x = x as i64;
+            self.radius_y = radii.get(1).copied().unwrap_or(x) as i64;
         }
+    }
 
-        let index = (
-            (self.node_id.0 as i64 + x) as u32,
-            (self.node_id.1 as i64 + y) as u32,
-        );
+    /// returns the ids of all nodes in this kernel.
+    fn resolve_all(&self, include_center: bool) -> NodeIdIter<Index2D> {
+        let vec: Vec<_> = self
+            .tile_map
+            .neighborhood
+            .offsets(self.radius_x, self.radius_y)
+            .into_iter()
+            .filter(|offset| include_center || *offset != (0, 0))
+            .filter_map(|(x, y)| self.resolve(x, y))
+            .filter(|id| include_center || *id != self.node_id)
+            .collect();
+        vec.into_iter()
+    }
+
+    /// returns all nodes in this kernel except for the center, with their offset from the
+    /// center.
+    fn resolve_directed(
+        &self,
+    ) -> DirectedNodeIter<'_, (i64, i64), Index2D, NodeValueDescription, Domain<NodeValueDescription>>
+    {
+        let vec: Vec<_> = self
+            .tile_map
+            .neighborhood
+            .offsets(self.radius_x, self.radius_y)
+            .into_iter()
+            .filter(|offset| *offset != (0, 0))
+            .filter_map(|(x, y)| self.resolve(x, y).map(|id| ((x, y), id)))
+            .filter(|(_, id)| *id != self.node_id)
+            .filter_map(|(offset, id)| self.tile_map.get_node(&id).map(|node| (offset, node)))
+            .collect();
+        vec.into_iter()
+    }
+
+    /// returns `true` if the node at the offset `(x, y)` from the center is part of this kernel.
+    pub fn contains(&self, x: i64, y: i64) -> bool {
+        match &self.tile_map.neighborhood {
+            Neighborhood::Offsets(_) => self.tile_map.neighborhood.contains(x, y),
+            neighborhood => {
+                x.abs() <= self.radius_x && y.abs() <= self.radius_y && neighborhood.contains(x, y)
+            }
+        }
+    }
+
+    /// returns the [NeighborState] of the node at the offset `(x, y)` from the center. This tells
+    /// a node outside of the map apart from an offset outside of the kernel, e.g. to only allow
+    /// some values at the edge of a map with [wrapping_mode::Cutoff]. Use [NeighborState::node]
+    /// if both mean the same to a solver.
+    pub fn get(&self, x: i64, y: i64) -> NeighborState<'_, NodeValueDescription> {
+        if !self.contains(x, y) {
+            return NeighborState::OutsideKernel;
+        }
+
+        match self
+            .resolve(x, y)
+            .and_then(|id| self.tile_map.get_node(&id))
+        {
+            Some(node) => NeighborState::Node(node),
+            None => NeighborState::OutOfBounds,
+        }
+    }
+
+    /// returns the direct neighbor of the center node in `direction`. See [Kernel2D::get].
+    pub fn get_dir(&self, direction: Direction) -> NeighborState<'_, NodeValueDescription> {
+        let (x, y) = direction.offset();
+        self.get(x, y)
+    }
+
+    /// returns `false` if the node at the offset `(x, y)` did not change since the center was
+    /// last validated. All possible values of the center are still valid against such a node,
+    /// so solvers can skip checking it. Nodes outside of the kernel never change.
+    pub fn is_changed(&self, x: i64, y: i64) -> bool {
+        match &self.changed {
+            None => true,
+            Some(changed) => {
+                self.contains(x, y) && self.resolve(x, y).is_some_and(|id| changed.contains(&id))
+            }
+        }
+    }
 
-        self.tile_map.get_node(&index)
+    /// like [Kernel2D::is_changed] for the direct neighbor in `direction`.
+    pub fn is_changed_dir(&self, direction: Direction) -> bool {
+        let (x, y) = direction.offset();
+        self.is_changed(x, y)
     }
 }
 
-impl<NodeValueDescription: Clone>
+impl<WrappingMode, NodeValueDescription> KernelFingerprint
+    for Kernel2D<WrappingMode, NodeValueDescription>
+where
+    NodeValueDescription: Clone + Hash,
+{
+    fn fingerprint(&self) -> u64 {
+        let offsets = self
+            .tile_map
+            .neighborhood
+            .offsets(self.radius_x, self.radius_y);
+        fingerprint(offsets, |x, y| self.get(x, y).node())
+    }
+}
+
+impl<Mode: wrapping_mode::WrappingMode, NodeValueDescription: Clone>
     WaveKernel<Index2D, NodeValueDescription, TileMap2D<NodeValueDescription>>
-    for Kernel2D<wrapping_mode::Cutoff, NodeValueDescription>
+    for Kernel2D<Mode, NodeValueDescription>
 {
+    type Direction = (i64, i64);
+
     fn new(
         shape: Rc<TileMap2D<NodeValueDescription>>,
         node: &Node<Index2D, NodeValueDescription>,
     ) -> Self {
-        Kernel2D::new(shape, node)
+        Kernel2D::new(shape, node, Mode::WRAP)
+    }
+
+    fn center_id(&self) -> Index2D {
+        self.node_id
+    }
+
+    fn center(&self) -> &Node<Index2D, NodeValueDescription> {
+        self.tile_map
+            .get_node(&self.node_id)
+            .expect("the kernel is created from a valid node")
     }
 
     fn iter_node_ids(&self) -> NodeIdIter<Index2D> {
-        use std::cmp::{max, min};
+        self.resolve_all(true)
+    }
 
-        let x_min = max(self.node_id.0 as i64 - self.radius_x, 0);
-        let x_max = min(
-            self.node_id.0 as i64 + self.radius_x,
-            self.tile_map.size.width as i64 - 1,
-        );
-        let y_min = max(self.node_id.1 as i64 - self.radius_y, 0);
-        let y_max = min(
-            self.node_id.1 as i64 + self.radius_y,
-            self.tile_map.size.height as i64 - 1,
-        );
+    fn iter_node_ids_without_center(&self) -> NodeIdIter<Index2D> {
+        self.resolve_all(false)
+    }
 
-        let vec: Vec<_> = gen_iter!({
-            for y in y_min..=y_max {
-                for x in x_min..=x_max {
-                    yield (x as u32, y as u32);
-                }
-            }
-        })
-        .collect();
-        vec.into_iter()
+    fn iter_directed(
+        &self,
+    ) -> DirectedNodeIter<'_, (i64, i64), Index2D, NodeValueDescription, Domain<NodeValueDescription>>
+    {
+        self.resolve_directed()
+    }
+
+    fn set_changed_ids(&mut self, changed: Option<Vec<Index2D>>) {
+        self.changed = changed;
+    }
+
+    fn set_radius(&mut self, radius: u32) {
+        self.radius_x = radius as i64;
+        self.radius_y = radius as i64;
+    }
+
+    fn set_radii(&mut self, radii: &[u32]) {
+        self.set_radii_xy(radii);
     }
 }
 
-impl<NodeValueDescription: Clone>
-    WaveKernel<Index2D, NodeValueDescription, TileMap2D<NodeValueDescription>>
-    for Kernel2D<wrapping_mode::Wrapping, NodeValueDescription>
+/// Creates a [TileMap2D] of `size` where every node can be any of `values`, collapses the nodes
+/// in `pinned` and checks if `value` is valid at `center` according to `solver`.
+///
+/// This is used to check a [WaveSolver] against a hand crafted neighborhood.
+pub(crate) fn probe_solver<NodeValue, Solver>(
+    solver: &Solver,
+    values: &[NodeValue],
+    size: Size2D,
+    kernel_size: Size2D,
+    center: Index2D,
+    value: &NodeValue,
+    pinned: &[(Index2D, NodeValue)],
+) -> bool
+where
+    NodeValue: Clone,
+    Solver: WaveSolver<NodeValue, Kernel2D<wrapping_mode::Cutoff, NodeValue>>,
 {
-    fn new(
-        shape: Rc<TileMap2D<NodeValueDescription>>,
-        node: &Node<Index2D, NodeValueDescription>,
+    let shape = Rc::new(TileMap2D::new(size, kernel_size, values));
+
+    for (id, pinned_value) in pinned {
+        let node = shape.get_node(id).expect("pinned node is within the shape");
+        node.with_values_mut(|values| *values = Domain::new(vec![pinned_value.clone()]));
+        *node.is_collapsed.borrow_mut() = true;
+    }
+
+    let center_node = shape.get_node(&center).expect("center is within the shape");
+    center_node.with_values_mut(|values| *values = Domain::new(vec![value.clone()]));
+
+    let kernel = Kernel2D::new(shape.clone(), center_node, Wrap::Cutoff);
+
+    solver.is_valid(value, &kernel)
+}
+
+/// Checks if `value` is valid according to `solver` when the only constrained node in its kernel
+/// is `neighbor` at `offset`.
+///
+/// The map is large enough that the kernels of both the center and `neighbor` are fully inside
+/// of it, so no node of either kernel is [NeighborState::OutOfBounds].
+pub(crate) fn probe_neighbor<NodeValue, Solver>(
+    solver: &Solver,
+    tiles: &[NodeValue],
+    kernel_size: Size2D,
+    value: &NodeValue,
+    neighbor: &NodeValue,
+    offset: (i64, i64),
+) -> bool
+where
+    NodeValue: Clone,
+    Solver: WaveSolver<NodeValue, Kernel2D<wrapping_mode::Cutoff, NodeValue>>,
+{
+    let size = Size2D::new(
+        kernel_size.width + offset.0.unsigned_abs() as u32,
+        kernel_size.height + offset.1.unsigned_abs() as u32,
+    );
+    let radius_x = (kernel_size.width - 1) / 2;
+    let radius_y = (kernel_size.height - 1) / 2;
+    let center: Index2D = (
+        radius_x + (-offset.0).max(0) as u32,
+        radius_y + (-offset.1).max(0) as u32,
+    );
+    let neighbor_id: Index2D = (
+        (center.0 as i64 + offset.0) as u32,
+        (center.1 as i64 + offset.1) as u32,
+    );
+
+    probe_solver(
+        solver,
+        tiles,
+        size,
+        kernel_size,
+        center,
+        value,
+        &[(neighbor_id, neighbor.clone())],
+    )
+}
+
+/// Creates a [TileMap2D] of `size` from `nodes`, which are ordered row by row, and returns the
+/// [Kernel2D] of its center node, see [crate::testing::sample_kernel].
+pub(crate) fn kernel_from_nodes<NodeValue: Clone>(
+    size: Size2D,
+    nodes: Vec<Node<Index2D, NodeValue>>,
+) -> Kernel2D<wrapping_mode::Cutoff, NodeValue> {
+    let shape = Rc::new(TileMap2D::from_nodes(size, size, nodes));
+    let center = shape
+        .get_node(&(size.width / 2, size.height / 2))
+        .expect("center is within the shape");
+    Kernel2D::new(shape.clone(), center, Wrap::Cutoff)
+}
+
+/// Which values are allowed at the edge of a map, where the neighbor of a node in a [Direction]
+/// is [NeighborState::OutOfBounds]. This only applies to kernels with [wrapping_mode::Cutoff].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CutoffBehaviour<NodeValue: Eq + Hash> {
+    /// any value is allowed at the edge.
+    Ignored,
+    /// only the given values are allowed at the edge, e.g. all tiles that are closed towards
+    /// the edge.
+    Only(HashSet<NodeValue>),
+}
+
+impl<NodeValue: Eq + Hash> Default for CutoffBehaviour<NodeValue> {
+    fn default() -> Self {
+        CutoffBehaviour::Ignored
+    }
+}
+
+impl<NodeValue: Eq + Hash> CutoffBehaviour<NodeValue> {
+    /// returns `true` if `value` is allowed at the edge.
+    pub fn allows(&self, value: &NodeValue) -> bool {
+        match self {
+            CutoffBehaviour::Ignored => true,
+            CutoffBehaviour::Only(allowed) => allowed.contains(value),
+        }
+    }
+}
+
+/// A [WaveSolver] that allows two values next to each other if there is a rule for them in
+/// that [Direction].
+///
+/// Directions without any rules do not constrain a value, so rules for the
+/// [Direction::ORTHOGONAL] directions are enough, even if the kernel contains diagonal
+/// neighbors. At the edge of the map each direction uses its own [CutoffBehaviour].
+///
+/// # Example
+/// ```no_run
+/// use std::collections::HashSet;
+/// use wave_collapse::tile2d::{AdjacencySolver2D, CutoffBehaviour, Direction};
+///
+/// let solver = AdjacencySolver2D::new()
+///     .with_rule("grass", "grass", Direction::Right)
+///     .with_rule("grass", "grass", Direction::Bottom)
+///     .with_rule("wall", "grass", Direction::Top)
+///     .with_rule("wall", "wall", Direction::Right)
+///     .with_cutoff(
+///         Direction::Bottom,
+///         CutoffBehaviour::Only(HashSet::from(["wall"])),
+///     );
+/// ```
+#[derive(Clone, Debug)]
+pub struct AdjacencySolver2D<NodeValue: Eq + Hash> {
+    /// the values allowed next to each value in a direction.
+    allowed: HashMap<Direction, HashMap<NodeValue, HashSet<NodeValue>>>,
+    cutoff: HashMap<Direction, CutoffBehaviour<NodeValue>>,
+}
+
+impl<NodeValue: Eq + Hash> Default for AdjacencySolver2D<NodeValue> {
+    fn default() -> Self {
+        AdjacencySolver2D {
+            allowed: HashMap::new(),
+            cutoff: HashMap::new(),
+        }
+    }
+}
+
+impl<NodeValue: Clone + Eq + Hash> AdjacencySolver2D<NodeValue> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows `neighbor` next to `value` in `direction`, which also allows `value` next to
+    /// `neighbor` in the opposite direction.
+    pub fn with_rule(
+        mut self,
+        value: NodeValue,
+        neighbor: NodeValue,
+        direction: Direction,
     ) -> Self {
-        Kernel2D::new(shape, node)
+        self.allowed
+            .entry(direction)
+            .or_default()
+            .entry(value.clone())
+            .or_default()
+            .insert(neighbor.clone());
+        self.allowed
+            .entry(direction.opposite())
+            .or_default()
+            .entry(neighbor)
+            .or_default()
+            .insert(value);
+        self
     }
 
-    fn iter_node_ids(&self) -> NodeIdIter<Index2D> {
-        let x_min = self.node_id.0 as i64 - self.radius_x;
-        let x_max = self.node_id.0 as i64 + self.radius_x;
-        let y_min = self.node_id.1 as i64 - self.radius_y;
-        let y_max = self.node_id.1 as i64 + self.radius_y;
+    /// Sets the [CutoffBehaviour] for nodes without a neighbor in `direction`.
+    pub fn with_cutoff(
+        mut self,
+        direction: Direction,
+        behaviour: CutoffBehaviour<NodeValue>,
+    ) -> Self {
+        self.cutoff.insert(direction, behaviour);
+        self
+    }
 
-        let vec: Vec<_> = gen_iter!({
-            for y in y_min..=y_max {
-                for x in x_min..=x_max {
-                    yield (
-                        x.rem_euclid(self.tile_map.size.width as i64) as u32,
-                        y.rem_euclid(self.tile_map.size.height as i64) as u32,
-                    );
-                }
+    /// returns `true` if `neighbor` is allowed next to `value` in `direction`.
+    pub fn allows(&self, value: &NodeValue, neighbor: &NodeValue, direction: Direction) -> bool {
+        self.allowed
+            .get(&direction)
+            .and_then(|allowed| allowed.get(value))
+            .is_some_and(|allowed| allowed.contains(neighbor))
+    }
+
+    /// returns the [CutoffBehaviour] for nodes without a neighbor in `direction`.
+    pub fn cutoff(&self, direction: Direction) -> Option<&CutoffBehaviour<NodeValue>> {
+        self.cutoff.get(&direction)
+    }
+}
+
+impl<WrappingMode, NodeValue> WaveSolver<NodeValue, Kernel2D<WrappingMode, NodeValue>>
+    for AdjacencySolver2D<NodeValue>
+where
+    NodeValue: Clone + Eq + Hash,
+{
+    fn is_valid(&self, value: &NodeValue, kernel: &Kernel2D<WrappingMode, NodeValue>) -> bool {
+        Direction::ALL.iter().all(|&direction| {
+            let (x, y) = direction.offset();
+            // values are still valid against neighbors that did not change
+            if !kernel.is_changed(x, y) {
+                return true;
+            }
+
+            match kernel.get(x, y) {
+                NeighborState::Node(node) => match self.allowed.get(&direction) {
+                    Some(_) => node.any_value(|neighbor| self.allows(value, neighbor, direction)),
+                    None => true,
+                },
+                NeighborState::OutOfBounds => match self.cutoff(direction) {
+                    Some(cutoff) => cutoff.allows(value),
+                    None => true,
+                },
+                NeighborState::OutsideKernel => true,
             }
         })
-        .collect();
-        vec.into_iter()
+    }
+
+    /// only the direct neighbors are checked.
+    fn kernel_radius(&self) -> Option<u32> {
+        Some(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Size2D, Stagger, TileMap2D, Wrap};
+
+    fn tile_map(width: u32, height: u32) -> TileMap2D<u8> {
+        TileMap2D::new(Size2D::new(width, height), Size2D::square(3), &[0, 1])
+    }
+
+    #[test]
+    fn cutoff_resolves_nodes_outside_of_the_map_to_none() {
+        let map = tile_map(4, 3);
+
+        assert_eq!(Wrap::Cutoff.resolve(&map, (0, 0), -1, 0), None);
+        assert_eq!(Wrap::Cutoff.resolve(&map, (3, 2), 1, 1), None);
+        assert_eq!(Wrap::Cutoff.resolve(&map, (3, 2), -1, -1), Some((2, 1)));
+    }
+
+    #[test]
+    fn chunk_wraps_within_the_chunk_of_the_center() {
+        let map = tile_map(4, 4).with_chunk_size(Size2D::square(2));
+
+        // corners of the map
+        assert_eq!(Wrap::Chunk.resolve(&map, (0, 0), -1, -1), Some((1, 1)));
+        assert_eq!(Wrap::Chunk.resolve(&map, (3, 3), 1, 1), Some((2, 2)));
+        assert_eq!(Wrap::Chunk.resolve(&map, (3, 0), 1, -1), Some((2, 1)));
+        // borders between chunks
+        assert_eq!(Wrap::Chunk.resolve(&map, (2, 1), -1, 0), Some((3, 1)));
+        assert_eq!(Wrap::Chunk.resolve(&map, (1, 1), 1, 0), Some((0, 1)));
+        assert_eq!(Wrap::Chunk.resolve(&map, (1, 2), 0, -1), Some((1, 3)));
+        // inside of a chunk
+        assert_eq!(Wrap::Chunk.resolve(&map, (2, 2), 1, 1), Some((3, 3)));
+    }
+
+    #[test]
+    fn staggered_shifts_nodes_that_cross_an_edge() {
+        let map = tile_map(4, 3).with_stagger(Stagger {
+            horizontal: 1,
+            vertical: 2,
+        });
+
+        // crossing the right edge moves to the next row, from the last row to the first
+        assert_eq!(Wrap::Staggered.resolve(&map, (3, 0), 1, 0), Some((0, 1)));
+        assert_eq!(Wrap::Staggered.resolve(&map, (3, 1), 1, 0), Some((0, 2)));
+        assert_eq!(Wrap::Staggered.resolve(&map, (3, 2), 1, 0), Some((2, 0)));
+        // crossing the left edge moves to the previous row
+        assert_eq!(Wrap::Staggered.resolve(&map, (0, 1), -1, 0), Some((3, 0)));
+        assert_eq!(Wrap::Staggered.resolve(&map, (0, 0), -1, 0), Some((1, 2)));
+        // crossing the top or bottom edge shifts along the x axis
+        assert_eq!(Wrap::Staggered.resolve(&map, (1, 0), 0, -1), Some((3, 2)));
+        assert_eq!(Wrap::Staggered.resolve(&map, (1, 2), 0, 1), Some((3, 0)));
+        // corners cross both edges, the horizontal edge first
+        assert_eq!(Wrap::Staggered.resolve(&map, (3, 2), 1, 1), Some((2, 1)));
+        assert_eq!(Wrap::Staggered.resolve(&map, (0, 0), -1, -1), Some((1, 1)));
+        // inside of the map
+        assert_eq!(Wrap::Staggered.resolve(&map, (1, 1), 1, 1), Some((2, 2)));
+    }
+
+    #[test]
+    fn staggered_without_stagger_wraps() {
+        let map = tile_map(4, 3);
+
+        for (center, x, y) in [((0, 0), -1, -1), ((3, 2), 1, 1), ((3, 1), 1, 0)] {
+            assert_eq!(
+                Wrap::Staggered.resolve(&map, center, x, y),
+                Wrap::Wrapping.resolve(&map, center, x, y),
+            );
+        }
     }
 }
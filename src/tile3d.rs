@@ -0,0 +1,556 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::domain::Domain;
+use crate::error::{EngineError, Result};
+use crate::node::{DirectedNodeIter, Node, NodeIdIter};
+use crate::wave_function::{ReadShape, WaveKernel, WaveShape, WaveSolver};
+
+pub use crate::wrapping_mode;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Size3D {
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+}
+
+pub type Index3D = (u32, u32, u32);
+
+impl Size3D {
+    pub fn new(width: u32, height: u32, depth: u32) -> Size3D {
+        Size3D {
+            width,
+            height,
+            depth,
+        }
+    }
+
+    pub fn cube(size: u32) -> Size3D {
+        Self::new(size, size, size)
+    }
+}
+
+/// Selects which nodes within the kernel size are part of a [Kernel3D].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Neighborhood3D {
+    /// all nodes within the kernel size, e.g. the 26-neighborhood for a kernel size of 3.
+    #[default]
+    Full,
+    /// nodes that differ from the center in at most 2 axes, e.g. the 18-neighborhood
+    /// (faces and edges) for a kernel size of 3.
+    Edges,
+    /// nodes that differ from the center in only 1 axis, e.g. the 6-neighborhood (faces)
+    /// for a kernel size of 3.
+    Faces,
+}
+
+impl Neighborhood3D {
+    /// returns `true` if the node at the offset `(x, y, z)` from the center is part of the
+    /// neighborhood.
+    pub fn contains(&self, x: i64, y: i64, z: i64) -> bool {
+        let changed_axes = [x, y, z].iter().filter(|c| **c != 0).count();
+        match self {
+            Neighborhood3D::Full => true,
+            Neighborhood3D::Edges => changed_axes <= 2,
+            Neighborhood3D::Faces => changed_axes <= 1,
+        }
+    }
+}
+
+pub struct TileMap3D<NodeValue: Clone> {
+    size: Size3D,
+    kernel_size: Size3D,
+    neighborhood: Neighborhood3D,
+
+    last_collapsed: RefCell<Option<Index3D>>,
+
+    nodes: Vec<Node<Index3D, NodeValue>>,
+}
+
+impl<NodeValue: Clone> TileMap3D<NodeValue> {
+    /// Create a new [TileMap3D]. `kernel_size` must be uneven in all dimensions. `possible_values` must not be empty.
+    pub fn new(size: Size3D, kernel_size: Size3D, possible_values: &[NodeValue]) -> Self {
+        assert!(kernel_size.width % 2 == 1, "Kernel width must be uneven");
+        assert!(kernel_size.height % 2 == 1, "Kernel height must be uneven");
+        assert!(kernel_size.depth % 2 == 1, "Kernel depth must be uneven");
+        assert!(!possible_values.is_empty(), "At least one value required!");
+
+        let mut nodes = Vec::new();
+        for z in 0..size.depth {
+            for y in 0..size.height {
+                for x in 0..size.width {
+                    nodes.push(Node::new((x, y, z), possible_values));
+                }
+            }
+        }
+
+        TileMap3D {
+            size,
+            kernel_size,
+            neighborhood: Neighborhood3D::default(),
+            last_collapsed: RefCell::new(None),
+            nodes,
+        }
+    }
+
+    /// returns the collapsed values ordered by `z`, then `y`, then `x` or [None] if not all
+    /// nodes are collapsed.
+    pub fn get_collapsed(&self) -> Option<Vec<NodeValue>> {
+        self.nodes.iter().map(|node| node.collapsed()).collect()
+    }
+
+    pub fn size(&self) -> &Size3D {
+        &self.size
+    }
+
+    pub fn kernel_size(&self) -> &Size3D {
+        &self.kernel_size
+    }
+
+    /// Sets the [Neighborhood3D] used by the kernels of this [TileMap3D]. Most voxel tilesets
+    /// only constrain face adjacency and should use [Neighborhood3D::Faces].
+    pub fn with_neighborhood(mut self, neighborhood: Neighborhood3D) -> Self {
+        self.neighborhood = neighborhood;
+        self
+    }
+
+    pub fn neighborhood(&self) -> Neighborhood3D {
+        self.neighborhood
+    }
+}
+
+impl<NodeValue> ReadShape<Index3D, NodeValue> for TileMap3D<NodeValue>
+where
+    NodeValue: Clone,
+{
+    fn get_node(&self, id: &Index3D) -> Option<&Node<Index3D, NodeValue>> {
+        let (x, y, z) = *id;
+        if x >= self.size.width || y >= self.size.height || z >= self.size.depth {
+            return None;
+        }
+        let index = (z as usize * self.size.height as usize + y as usize)
+            * self.size.width as usize
+            + x as usize;
+        self.nodes.get(index)
+    }
+
+    fn iter_node_ids(&self) -> NodeIdIter<Index3D> {
+        let vec: Vec<_> = self.nodes.iter().map(|node| node.id).collect();
+        vec.into_iter()
+    }
+
+    fn get_last_collapsed_id(&self) -> Option<Index3D> {
+        *self.last_collapsed.borrow()
+    }
+
+    fn validate(&self) -> Result<()> {
+        let (size, kernel) = (self.size, self.kernel_size);
+        if size.width == 0 || size.height == 0 || size.depth == 0 {
+            return Err(EngineError::EmptyShape.into());
+        }
+        if kernel.width > size.width || kernel.height > size.height || kernel.depth > size.depth {
+            let format_size =
+                |size: Size3D| format!("{}x{}x{}", size.width, size.height, size.depth);
+            return Err(EngineError::KernelLargerThanShape {
+                kernel: format_size(kernel),
+                shape: format_size(size),
+            }
+            .into());
+        }
+        crate::validate::validate_nodes(self)
+    }
+}
+
+impl<NodeValue> WaveShape<Index3D, NodeValue> for TileMap3D<NodeValue>
+where
+    NodeValue: Clone,
+{
+    fn set_last_collapsed_id(&self, node_id: Index3D) {
+        let _ = self.last_collapsed.borrow_mut().insert(node_id);
+    }
+}
+
+pub struct Kernel3D<WrappingMode, NodeValueDescription: Clone> {
+    tile_map: Rc<TileMap3D<NodeValueDescription>>,
+    node_id: Index3D,
+    pub radius_x: i64,
+    pub radius_y: i64,
+    pub radius_z: i64,
+    wrapping: bool,
+    _wrapping_mode: PhantomData<WrappingMode>,
+}
+
+impl<WrappingMode, NodeValueDescription: Clone> Kernel3D<WrappingMode, NodeValueDescription> {
+    fn new(
+        shape: Rc<TileMap3D<NodeValueDescription>>,
+        node: &Node<Index3D, NodeValueDescription>,
+        wrapping: bool,
+    ) -> Self {
+        let radius_x = ((shape.kernel_size.width - 1) / 2) as i64;
+        let radius_y = ((shape.kernel_size.height - 1) / 2) as i64;
+        let radius_z = ((shape.kernel_size.depth - 1) / 2) as i64;
+
+        Kernel3D {
+            tile_map: shape,
+            node_id: node.id,
+            radius_x,
+            radius_y,
+            radius_z,
+            wrapping,
+            _wrapping_mode: PhantomData::default(),
+        }
+    }
+
+    /// returns `true` if the node at the offset `(x, y, z)` from the center is part of this kernel.
+    pub fn contains(&self, x: i64, y: i64, z: i64) -> bool {
+        x.abs() <= self.radius_x
+            && y.abs() <= self.radius_y
+            && z.abs() <= self.radius_z
+            && self.tile_map.neighborhood.contains(x, y, z)
+    }
+
+    pub fn get(&self, x: i64, y: i64, z: i64) -> Option<&Node<Index3D, NodeValueDescription>> {
+        if !self.contains(x, y, z) {
+            return None;
+        }
+
+        let size = self.tile_map.size;
+        let (x, y, z) = (
+            self.node_id.0 as i64 + x,
+            self.node_id.1 as i64 + y,
+            self.node_id.2 as i64 + z,
+        );
+        let index = if self.wrapping {
+            (
+                x.rem_euclid(size.width as i64),
+                y.rem_euclid(size.height as i64),
+                z.rem_euclid(size.depth as i64),
+            )
+        } else {
+            (x, y, z)
+        };
+        if index.0 < 0 || index.1 < 0 || index.2 < 0 {
+            return None;
+        }
+
+        self.tile_map
+            .get_node(&(index.0 as u32, index.1 as u32, index.2 as u32))
+    }
+
+    /// returns all offsets that are part of this kernel.
+    fn offsets(&self, include_center: bool) -> Vec<(i64, i64, i64)> {
+        let mut offsets = Vec::new();
+        for z in -self.radius_z..=self.radius_z {
+            for y in -self.radius_y..=self.radius_y {
+                for x in -self.radius_x..=self.radius_x {
+                    if (include_center || (x, y, z) != (0, 0, 0))
+                        && self.tile_map.neighborhood.contains(x, y, z)
+                    {
+                        offsets.push((x, y, z));
+                    }
+                }
+            }
+        }
+        offsets
+    }
+
+    /// returns all nodes in this kernel except for the center, with their offset from the
+    /// center.
+    fn directed_nodes(
+        &self,
+    ) -> DirectedNodeIter<
+        '_,
+        (i64, i64, i64),
+        Index3D,
+        NodeValueDescription,
+        Domain<NodeValueDescription>,
+    > {
+        let vec: Vec<_> = self
+            .offsets(false)
+            .into_iter()
+            .filter_map(|(x, y, z)| self.get(x, y, z).map(|node| ((x, y, z), node)))
+            .filter(|(_, node)| node.id != self.node_id)
+            .collect();
+        vec.into_iter()
+    }
+}
+
+impl<NodeValueDescription: Clone>
+    WaveKernel<Index3D, NodeValueDescription, TileMap3D<NodeValueDescription>>
+    for Kernel3D<wrapping_mode::Cutoff, NodeValueDescription>
+{
+    type Direction = (i64, i64, i64);
+
+    fn new(
+        shape: Rc<TileMap3D<NodeValueDescription>>,
+        node: &Node<Index3D, NodeValueDescription>,
+    ) -> Self {
+        Kernel3D::new(shape, node, false)
+    }
+
+    fn center_id(&self) -> Index3D {
+        self.node_id
+    }
+
+    fn center(&self) -> &Node<Index3D, NodeValueDescription> {
+        self.tile_map
+            .get_node(&self.node_id)
+            .expect("the kernel is created from a valid node")
+    }
+
+    fn set_radius(&mut self, radius: u32) {
+        self.radius_x = radius as i64;
+        self.radius_y = radius as i64;
+        self.radius_z = radius as i64;
+    }
+
+    fn set_radii(&mut self, radii: &[u32]) {
+        if let Some(&x) = radii.first() {
+            self.radius_x = x as i64;
+            self.radius_y = radii.get(1).copied().unwrap_or(x) as i64;
+            self.radius_z = radii.get(2).copied().unwrap_or(x) as i64;
+        }
+    }
+
+    fn iter_node_ids(&self) -> NodeIdIter<Index3D> {
+        self.cutoff_node_ids(true)
+    }
+
+    fn iter_node_ids_without_center(&self) -> NodeIdIter<Index3D> {
+        self.cutoff_node_ids(false)
+    }
+
+    fn iter_directed(
+        &self,
+    ) -> DirectedNodeIter<
+        '_,
+        (i64, i64, i64),
+        Index3D,
+        NodeValueDescription,
+        Domain<NodeValueDescription>,
+    > {
+        self.directed_nodes()
+    }
+}
+
+impl<NodeValueDescription: Clone> Kernel3D<wrapping_mode::Cutoff, NodeValueDescription> {
+    fn cutoff_node_ids(&self, include_center: bool) -> NodeIdIter<Index3D> {
+        let size = self.tile_map.size;
+
+        let vec: Vec<_> = self
+            .offsets(include_center)
+            .into_iter()
+            .map(|(x, y, z)| {
+                (
+                    self.node_id.0 as i64 + x,
+                    self.node_id.1 as i64 + y,
+                    self.node_id.2 as i64 + z,
+                )
+            })
+            .filter(|(x, y, z)| {
+                (0..size.width as i64).contains(x)
+                    && (0..size.height as i64).contains(y)
+                    && (0..size.depth as i64).contains(z)
+            })
+            .map(|(x, y, z)| (x as u32, y as u32, z as u32))
+            .collect();
+        vec.into_iter()
+    }
+}
+
+impl<NodeValueDescription: Clone>
+    WaveKernel<Index3D, NodeValueDescription, TileMap3D<NodeValueDescription>>
+    for Kernel3D<wrapping_mode::Wrapping, NodeValueDescription>
+{
+    type Direction = (i64, i64, i64);
+
+    fn new(
+        shape: Rc<TileMap3D<NodeValueDescription>>,
+        node: &Node<Index3D, NodeValueDescription>,
+    ) -> Self {
+        Kernel3D::new(shape, node, true)
+    }
+
+    fn center_id(&self) -> Index3D {
+        self.node_id
+    }
+
+    fn center(&self) -> &Node<Index3D, NodeValueDescription> {
+        self.tile_map
+            .get_node(&self.node_id)
+            .expect("the kernel is created from a valid node")
+    }
+
+    fn set_radius(&mut self, radius: u32) {
+        self.radius_x = radius as i64;
+        self.radius_y = radius as i64;
+        self.radius_z = radius as i64;
+    }
+
+    fn set_radii(&mut self, radii: &[u32]) {
+        if let Some(&x) = radii.first() {
+            self.radius_x = x as i64;
+            self.radius_y = radii.get(1).copied().unwrap_or(x) as i64;
+            self.radius_z = radii.get(2).copied().unwrap_or(x) as i64;
+        }
+    }
+
+    fn iter_node_ids(&self) -> NodeIdIter<Index3D> {
+        self.wrapping_node_ids(true)
+    }
+
+    fn iter_node_ids_without_center(&self) -> NodeIdIter<Index3D> {
+        self.wrapping_node_ids(false)
+    }
+
+    fn iter_directed(
+        &self,
+    ) -> DirectedNodeIter<
+        '_,
+        (i64, i64, i64),
+        Index3D,
+        NodeValueDescription,
+        Domain<NodeValueDescription>,
+    > {
+        self.directed_nodes()
+    }
+}
+
+impl<NodeValueDescription: Clone> Kernel3D<wrapping_mode::Wrapping, NodeValueDescription> {
+    fn wrapping_node_ids(&self, include_center: bool) -> NodeIdIter<Index3D> {
+        let size = self.tile_map.size;
+
+        let vec: Vec<_> = self
+            .offsets(include_center)
+            .into_iter()
+            .map(|(x, y, z)| {
+                (
+                    (self.node_id.0 as i64 + x).rem_euclid(size.width as i64) as u32,
+                    (self.node_id.1 as i64 + y).rem_euclid(size.height as i64) as u32,
+                    (self.node_id.2 as i64 + z).rem_euclid(size.depth as i64) as u32,
+                )
+            })
+            .filter(|id| include_center || *id != self.node_id)
+            .collect();
+        vec.into_iter()
+    }
+}
+
+/// A [WaveSolver] for [TileMap3D]s, where horizontal and vertical adjacency rules are defined
+/// independently, e.g. so that floors can stack on top of each other without being allowed
+/// next to each other.
+///
+/// `y` is the vertical axis and, like [crate::tile2d::Direction::Top], decreasing `y` is up.
+/// Only the 6 face neighbors are checked. Missing neighbors do not constrain a value.
+///
+/// # Example
+/// ```no_run
+/// use wave_collapse::tile3d::AdjacencySolver3D;
+///
+/// let solver = AdjacencySolver3D::new()
+///     .with_horizontal("air", "air")
+///     .with_horizontal("floor", "wall")
+///     .with_vertical("floor", "air")
+///     .with_vertical("floor", "floor")
+///     .with_vertical("air", "air");
+/// ```
+#[derive(Debug, Clone)]
+pub struct AdjacencySolver3D<NodeValue> {
+    /// the values allowed next to each value along the `x` or `z` axis.
+    horizontal: HashMap<NodeValue, HashSet<NodeValue>>,
+    /// the values allowed on top of each value.
+    above: HashMap<NodeValue, HashSet<NodeValue>>,
+    /// the values allowed below each value.
+    below: HashMap<NodeValue, HashSet<NodeValue>>,
+}
+
+impl<NodeValue> Default for AdjacencySolver3D<NodeValue> {
+    fn default() -> Self {
+        AdjacencySolver3D {
+            horizontal: HashMap::new(),
+            above: HashMap::new(),
+            below: HashMap::new(),
+        }
+    }
+}
+
+impl<NodeValue: Clone + Eq + Hash> AdjacencySolver3D<NodeValue> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows `a` and `b` next to each other along the `x` or `z` axis, in either order.
+    pub fn with_horizontal(mut self, a: NodeValue, b: NodeValue) -> Self {
+        self.horizontal
+            .entry(a.clone())
+            .or_default()
+            .insert(b.clone());
+        self.horizontal.entry(b).or_default().insert(a);
+        self
+    }
+
+    /// Allows `above` directly on top of `below`. This does not allow `below` on top of `above`.
+    pub fn with_vertical(mut self, below: NodeValue, above: NodeValue) -> Self {
+        self.above
+            .entry(below.clone())
+            .or_default()
+            .insert(above.clone());
+        self.below.entry(above).or_default().insert(below);
+        self
+    }
+
+    /// returns `true` if `a` and `b` are allowed next to each other horizontally.
+    pub fn allows_horizontal(&self, a: &NodeValue, b: &NodeValue) -> bool {
+        Self::allows(&self.horizontal, a, b)
+    }
+
+    /// returns `true` if `above` is allowed directly on top of `below`.
+    pub fn allows_vertical(&self, below: &NodeValue, above: &NodeValue) -> bool {
+        Self::allows(&self.above, below, above)
+    }
+
+    fn allows(
+        rules: &HashMap<NodeValue, HashSet<NodeValue>>,
+        a: &NodeValue,
+        b: &NodeValue,
+    ) -> bool {
+        rules.get(a).is_some_and(|allowed| allowed.contains(b))
+    }
+
+    /// returns `true` if the neighbor of `kernel` at the offset `(x, y, z)` has a possible value
+    /// that is allowed next to `value` by `rules`.
+    fn is_supported<WrappingMode>(
+        rules: &HashMap<NodeValue, HashSet<NodeValue>>,
+        value: &NodeValue,
+        kernel: &Kernel3D<WrappingMode, NodeValue>,
+        (x, y, z): (i64, i64, i64),
+    ) -> bool {
+        match kernel.get(x, y, z) {
+            Some(node) => node.any_value(|neighbor| Self::allows(rules, value, neighbor)),
+            None => true,
+        }
+    }
+}
+
+impl<WrappingMode, NodeValue> WaveSolver<NodeValue, Kernel3D<WrappingMode, NodeValue>>
+    for AdjacencySolver3D<NodeValue>
+where
+    NodeValue: Clone + Eq + Hash,
+{
+    fn is_valid(&self, value: &NodeValue, kernel: &Kernel3D<WrappingMode, NodeValue>) -> bool {
+        [(1, 0, 0), (-1, 0, 0), (0, 0, 1), (0, 0, -1)]
+            .into_iter()
+            .all(|offset| Self::is_supported(&self.horizontal, value, kernel, offset))
+            && Self::is_supported(&self.above, value, kernel, (0, -1, 0))
+            && Self::is_supported(&self.below, value, kernel, (0, 1, 0))
+    }
+
+    /// only the direct neighbors are checked.
+    fn kernel_radius(&self) -> Option<u32> {
+        Some(1)
+    }
+}
@@ -0,0 +1,80 @@
+//! Checks that help to find bugs in custom [WaveShape] and [WaveKernel] implementations and
+//! invalid input, see [ReadShape::validate].
+
+use std::{collections::HashSet, fmt::Debug, hash::Hash, rc::Rc};
+
+use crate::error::{EngineError, Result};
+use crate::wave_function::{ReadShape, WaveKernel, WaveShape};
+
+/// Verifies that every id returned by [ReadShape::iter_node_ids] is unique and resolves to a
+/// [crate::node::Node] with the same id via [ReadShape::get_node].
+pub fn debug_validate<NodeId, NodeValue, Shape>(shape: &Shape) -> Result<()>
+where
+    NodeId: Eq + Hash + Debug,
+    NodeValue: Clone,
+    Shape: ReadShape<NodeId, NodeValue> + ?Sized,
+{
+    let mut ids = HashSet::new();
+
+    for id in shape.iter_node_ids() {
+        match shape.get_node(&id) {
+            Some(node) if node.id == id => {}
+            _ => return Err(EngineError::UnresolvableNodeId(format!("{id:?}")).into()),
+        }
+
+        if ids.contains(&id) {
+            return Err(EngineError::DuplicateNodeId(format!("{id:?}")).into());
+        }
+        ids.insert(id);
+    }
+
+    Ok(())
+}
+
+/// The default implementation of [ReadShape::validate], so shapes that override it can still
+/// use it for their nodes.
+///
+/// returns [EngineError::EmptyShape] if `shape` has no nodes and [EngineError::EmptyDomain] for
+/// the first node without possible values.
+pub fn validate_nodes<NodeId, NodeValue, Shape>(shape: &Shape) -> Result<()>
+where
+    NodeId: Debug,
+    NodeValue: Clone,
+    Shape: ReadShape<NodeId, NodeValue> + ?Sized,
+{
+    let mut nodes = shape.iter_nodes().peekable();
+    if nodes.peek().is_none() {
+        return Err(EngineError::EmptyShape.into());
+    }
+    match nodes.find(|node| node.is_overspecified()) {
+        Some(node) => Err(EngineError::EmptyDomain(format!("{:?}", node.id)).into()),
+        None => Ok(()),
+    }
+}
+
+/// Runs [debug_validate] and verifies that the kernel of every node only contains ids that
+/// belong to `shape`.
+pub fn debug_validate_kernels<NodeId, NodeValue, Shape, Kernel>(shape: &Rc<Shape>) -> Result<()>
+where
+    NodeId: Eq + Hash + Debug,
+    NodeValue: Clone,
+    Shape: WaveShape<NodeId, NodeValue>,
+    Kernel: WaveKernel<NodeId, NodeValue, Shape>,
+{
+    debug_validate(shape.as_ref())?;
+
+    let ids: HashSet<NodeId> = shape.iter_node_ids().collect();
+
+    for node in shape.iter_nodes() {
+        let kernel = Kernel::new(shape.clone(), node);
+        if let Some(id) = kernel.iter_node_ids().find(|id| !ids.contains(id)) {
+            return Err(EngineError::InvalidKernelNodeId {
+                center: format!("{:?}", node.id),
+                id: format!("{id:?}"),
+            }
+            .into());
+        }
+    }
+
+    Ok(())
+}
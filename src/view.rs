@@ -0,0 +1,162 @@
+//! Views of the values and entropies of a [TileMap2D] for rendering.
+//!
+//! The nodes of a shape store their values in [std::cell::RefCell]s, which are changed by the
+//! collapse. A renderer that keeps a [std::cell::Ref] of a node, e.g. from
+//! [crate::node::Node::collapsed_ref], across a step of the collapse causes a `BorrowMutError`
+//! panic. A [ShapeView] copies the collapsed values and entropies of all nodes at once, so the
+//! renderer only works with plain slices and never holds a borrow of the shape.
+
+use crate::tile2d::{Index2D, Size2D, TileMap2D};
+
+/// The collapsed values and entropies of the nodes of a [TileMap2D], row by row, so the node
+/// `(x, y)` is at `y * width + x`.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Cutoff};
+/// # use wave_collapse::wave_function::WaveSolver;
+/// use wave_collapse::collapse_wave;
+/// use wave_collapse::view::ShapeView;
+/// # struct TestSolver;
+/// # impl WaveSolver<char, Kernel2D<Cutoff, char>> for TestSolver {
+/// #     fn is_valid(&self, _value: &char, _kernel: &Kernel2D<Cutoff, char>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// let shape = TileMap2D::new(Size2D::square(40), Size2D::square(3), &['.', '#']);
+/// let mut view = ShapeView::new(&shape);
+/// let mut rng = rand::thread_rng();
+/// let mut collapse =
+///     collapse_wave::<_, _, _, Kernel2D<Cutoff, char>, _>(shape, &TestSolver, &mut rng);
+/// while let Some(shape) = Iterator::next(&mut &mut collapse) {
+///     // reuses the buffers of the previous step
+///     view.refresh(&shape);
+///     for row in view.rows() {
+///         let line: String = row
+///             .iter()
+///             .map(|(value, entropy)| match value {
+///                 Some(tile) => *tile,
+///                 None if entropy == 0 => '!',
+///                 None => '?',
+///             })
+///             .collect();
+///         println!("{line}");
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapeView<NodeValue> {
+    size: Size2D,
+    values: Vec<Option<NodeValue>>,
+    entropies: Vec<u32>,
+}
+
+impl<NodeValue: Clone> ShapeView<NodeValue> {
+    /// Creates a view of the current state of `shape`.
+    pub fn new(shape: &TileMap2D<NodeValue>) -> Self {
+        let mut view = ShapeView {
+            size: *shape.size(),
+            values: Vec::new(),
+            entropies: Vec::new(),
+        };
+        view.refresh(shape);
+        view
+    }
+
+    /// Updates the view to the current state of `shape`. The buffers of the view are reused, so
+    /// refreshing a view of a shape of the same size does not allocate.
+    pub fn refresh(&mut self, shape: &TileMap2D<NodeValue>) {
+        self.size = *shape.size();
+        self.values.clear();
+        self.entropies.clear();
+        for y in 0..self.size.height {
+            for x in 0..self.size.width {
+                let node = &shape[(x, y)];
+                self.values.push(node.collapsed());
+                self.entropies.push(node.entropy());
+            }
+        }
+    }
+
+    /// returns the size of the viewed shape.
+    pub fn size(&self) -> Size2D {
+        self.size
+    }
+
+    /// returns the collapsed value of each node, or [None] for nodes that are not collapsed.
+    pub fn values(&self) -> &[Option<NodeValue>] {
+        &self.values
+    }
+
+    /// returns the number of possible values of each node, see [crate::node::Node::entropy].
+    pub fn entropies(&self) -> &[u32] {
+        &self.entropies
+    }
+
+    /// returns the collapsed value of the node `id`, or [None] if it is not collapsed or outside
+    /// of the shape.
+    pub fn get(&self, id: Index2D) -> Option<&NodeValue> {
+        self.index(id).and_then(|index| self.values[index].as_ref())
+    }
+
+    /// returns the entropy of the node `id`, or [None] if it is outside of the shape.
+    pub fn entropy(&self, id: Index2D) -> Option<u32> {
+        self.index(id).map(|index| self.entropies[index])
+    }
+
+    /// returns the row `y`, or [None] if it is outside of the shape.
+    pub fn row(&self, y: u32) -> Option<RowView<'_, NodeValue>> {
+        if y >= self.size.height {
+            return None;
+        }
+        let start = (y * self.size.width) as usize;
+        let end = start + self.size.width as usize;
+        Some(RowView {
+            y,
+            values: &self.values[start..end],
+            entropies: &self.entropies[start..end],
+        })
+    }
+
+    /// returns all rows, top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = RowView<'_, NodeValue>> {
+        (0..self.size.height).filter_map(|y| self.row(y))
+    }
+
+    fn index(&self, id: Index2D) -> Option<usize> {
+        if id.0 >= self.size.width || id.1 >= self.size.height {
+            return None;
+        }
+        Some((id.1 * self.size.width + id.0) as usize)
+    }
+}
+
+/// A row of a [ShapeView].
+#[derive(Debug)]
+pub struct RowView<'a, NodeValue> {
+    /// the y coordinate of the row.
+    pub y: u32,
+    /// the collapsed value of each node in the row, left to right.
+    pub values: &'a [Option<NodeValue>],
+    /// the entropy of each node in the row, left to right.
+    pub entropies: &'a [u32],
+}
+
+impl<'a, NodeValue> RowView<'a, NodeValue> {
+    /// returns the collapsed value and entropy of each node in the row, left to right.
+    pub fn iter(&self) -> impl Iterator<Item = (Option<&'a NodeValue>, u32)> + 'a {
+        let (values, entropies) = (self.values, self.entropies);
+        values
+            .iter()
+            .map(Option::as_ref)
+            .zip(entropies.iter().copied())
+    }
+}
+
+impl<'a, NodeValue> Clone for RowView<'a, NodeValue> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, NodeValue> Copy for RowView<'a, NodeValue> {}
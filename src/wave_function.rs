@@ -1,21 +1,68 @@
-use crate::node::{Node, NodeIdIter, NodeIter};
-use rand::{seq::SliceRandom, Rng};
-use std::{cmp::Reverse, fmt::Debug, hash::Hash, ops::Generator, rc::Rc};
+use crate::domain::{Domain, DomainStorage};
+use crate::node::{DirectedNodeIter, Node, NodeIdIter, NodeIter};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    hash::Hash,
+    marker::PhantomData,
+    mem::size_of,
+    ops::Generator,
+    rc::Rc,
+    time::Instant,
+};
 
 use gen_iter::{gen_iter_return, GenIterReturn};
 
-use crate::binary_heap_set::BinaryHeapSet;
-use crate::error::{Result, WaveCollapseError};
+use crate::error::{
+    EngineError, ErrorContext, PinConflict, PropagationError, Result, WaveCollapseError,
+};
+use crate::gen_iter_return_result::GenIterReturnResult;
+use crate::indexed_priority_queue::IndexedPriorityQueue;
+use crate::outcome::{CollapseOutcome, CollapseStats};
+use crate::rng::{substream, EntropySource};
+use crate::telemetry::{StepTelemetry, TelemetrySink};
 
 /// This represents a set of rules that define how to colapse a given wave function.
 pub trait WaveSolver<NodeValue, Kernel> {
     /// This function should return true, if a `value` is valid within a kernel
     fn is_valid(&self, value: &NodeValue, kernel: &Kernel) -> bool;
+
+    /// returns the radius of the kernel this solver needs, or [None] to use the kernel size of
+    /// the shape. The collapse passes this to [WaveKernel::set_radii], so the same shape can be
+    /// used with solvers that need different kernel sizes.
+    fn kernel_radius(&self) -> Option<u32> {
+        None
+    }
+
+    /// like [WaveSolver::kernel_radius], but with a radius per axis, in the order of the axes of
+    /// the shape, e.g. `[x, y]`. A single radius is used for all axes. This is passed to
+    /// [WaveKernel::set_radii], e.g. to look 3 nodes horizontally but only 1 vertically.
+    /// The default implementation uses [WaveSolver::kernel_radius] for all axes.
+    fn kernel_radii(&self) -> Option<Vec<u32>> {
+        self.kernel_radius().map(|radius| vec![radius])
+    }
+
+    /// called once with the shape before it is collapsed, e.g. to build lookup tables sized to
+    /// the values of the shape, instead of recomputing them in each [WaveSolver::is_valid] call.
+    /// This is only called by [collapse_prepared]. The default implementation does nothing.
+    fn prepare<NodeId, Shape>(&mut self, _shape: &Shape)
+    where
+        NodeValue: Clone,
+        Shape: ReadShape<NodeId, NodeValue> + ?Sized,
+    {
+    }
 }
 
-/// A wave shape defines the dimension/size/shape of the wave function. It also provides functions
-/// to create a kernel for a given node and iterate all nodes.
-pub trait WaveShape<NodeId, NodeValue: Clone> {
+/// The read-only part of a [WaveShape]. It defines the dimension/size/shape of the wave function
+/// and provides functions to iterate and inspect all nodes.
+///
+/// This is object safe, so renderers and analyzers can accept a `&dyn ReadShape`. The
+/// [ReadShape::Storage] has to be named in that case, e.g.
+/// `&dyn ReadShape<Index2D, u32, Storage = Domain<u32>>`.
+pub trait ReadShape<NodeId, NodeValue: Clone> {
+    /// the [DomainStorage] used by the nodes of this shape.
+    type Storage: DomainStorage<NodeValue> = Domain<NodeValue>;
+
     /// returns an `Iterator` over all ids of the nodes in the wave function.
     fn iter_node_ids(&self) -> NodeIdIter<NodeId>;
 
@@ -24,11 +71,11 @@ pub trait WaveShape<NodeId, NodeValue: Clone> {
         NodeIter::new(self.iter_node_ids(), self)
     }
 
-    fn get_node(&self, id: &NodeId) -> Option<&Node<NodeId, NodeValue>>;
+    fn get_node(&self, id: &NodeId) -> Option<&Node<NodeId, NodeValue, Self::Storage>>;
 
     /// returns `true` if all nodes in the [WaveShape] are collapsed. The default implementation uses
     /// ```no_run
-    /// # use wave_collapse::wave_function::WaveShape;
+    /// # use wave_collapse::wave_function::ReadShape;
     /// # use wave_collapse::tile2d::{TileMap2D, Size2D};
     /// # let tiles: Vec<u32> = vec![];
     /// # let shape = TileMap2D::new(Size2D::square(10), Size2D::square(3), &tiles);
@@ -41,7 +88,7 @@ pub trait WaveShape<NodeId, NodeValue: Clone> {
     /// returns `true` if any node in the [WaveShape] is overspecified, meaning that there are no valid
     /// values for it left.
     /// ```no_run
-    /// # use wave_collapse::wave_function::WaveShape;
+    /// # use wave_collapse::wave_function::ReadShape;
     /// # use wave_collapse::tile2d::{TileMap2D, Size2D};
     /// # let tiles: Vec<u32> = vec![];
     /// # let shape = TileMap2D::new(Size2D::square(10), Size2D::square(3), &tiles);
@@ -51,43 +98,213 @@ pub trait WaveShape<NodeId, NodeValue: Clone> {
         self.iter_nodes().any(|node| node.is_overspecified())
     }
 
+    /// returns `true` if the values of this shape have weights, see [ReadShape::value_weight].
+    fn is_weighted(&self) -> bool {
+        false
+    }
+
+    /// returns the weight of `value`. Values with a higher weight are chosen more often and
+    /// nodes are chosen by their [Node::weighted_entropy]. A value with a weight of 0 is only
+    /// chosen if no value of the node has a positive weight. This is only used if
+    /// [ReadShape::is_weighted] returns `true`.
+    fn value_weight(&self, _value: &NodeValue) -> f32 {
+        1.0
+    }
+
+    /// returns the weight of `value` in the node `id`, e.g. to make a value more common in some
+    /// regions of the shape. This is used instead of [ReadShape::value_weight] when a node is
+    /// collapsed or its [Node::weighted_entropy] is computed. The default implementation returns
+    /// [ReadShape::value_weight].
+    fn node_value_weight(&self, _id: &NodeId, value: &NodeValue) -> f32 {
+        self.value_weight(value)
+    }
+
+    /// returns an estimate of the memory in bytes used by the nodes of this shape, e.g. to
+    /// enforce memory budgets or to compare [ReadShape::Storage]s. Heap memory owned by the
+    /// values themselves is not included. The default implementation sums
+    /// [Node::memory_usage] over all nodes.
+    fn memory_usage(&self) -> usize {
+        self.iter_nodes().map(|node| node.memory_usage()).sum()
+    }
+
+    /// returns the id of the [Node] set by [WaveShape::set_last_collapsed_id].
+    /// It is valid for an implementation to always return [None].
+    fn get_last_collapsed_id(&self) -> Option<NodeId>;
+
+    /// Checks that this shape can be collapsed, before a collapse is started, so tools can show
+    /// a precise message.
+    ///
+    /// returns [EngineError::EmptyShape] if the shape has no nodes and
+    /// [EngineError::EmptyDomain] for the first node without possible values, see
+    /// [crate::validate::validate_nodes]. Shapes with a size, like [crate::tile2d::TileMap2D],
+    /// also return [EngineError::KernelLargerThanShape].
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use wave_collapse::tile2d::{Size2D, TileMap2D};
+    /// use wave_collapse::wave_function::ReadShape;
+    /// let shape = TileMap2D::new(Size2D::square(2), Size2D::square(3), &[0u32, 1]);
+    /// if let Err(error) = shape.validate() {
+    ///     eprintln!("invalid map: {error}");
+    /// }
+    /// ```
+    fn validate(&self) -> Result<()>
+    where
+        NodeId: Debug,
+    {
+        crate::validate::validate_nodes(self)
+    }
+}
+
+/// A wave shape defines the dimension/size/shape of the wave function. It also provides functions
+/// to create a kernel for a given node and iterate all nodes.
+/// On top of [ReadShape] this contains everything the algorithm needs to collapse the shape.
+pub trait WaveShape<NodeId, NodeValue: Clone>: ReadShape<NodeId, NodeValue> {
     /// returns a random node where the [Node] has the lowest possible entropy and is not collapsed or
     /// overspecified.
     /// If no node is found [None] is returend.
     fn choose_random_with_lowest_entropy(
         &self,
-        rng: &mut impl Rng,
-    ) -> Option<&Node<NodeId, NodeValue>> {
-        let mut bucket = Vec::new();
-        let mut entropy = u32::MAX;
-        for node in self.iter_nodes() {
-            if node.is_collapsed() || node.is_overspecified() {
-                continue;
-            }
-
-            let node_entropy = node.entropy();
-            #[allow(clippy::comparison_chain)]
-            if node_entropy < entropy {
-                entropy = node_entropy;
-                bucket.clear();
-                bucket.push(node);
-            } else if node_entropy == entropy {
-                bucket.push(node);
-            }
-        }
+        rng: &mut impl EntropySource,
+    ) -> Option<&Node<NodeId, NodeValue, Self::Storage>> {
+        self.choose_random_with_lowest_entropy_where(rng, |_| true)
+    }
 
-        bucket.choose(rng).copied()
+    /// like `choose_random_with_lowest_entropy`, but only nodes for which `filter` returns `true`
+    /// are considered.
+    fn choose_random_with_lowest_entropy_where(
+        &self,
+        rng: &mut impl EntropySource,
+        filter: impl Fn(&Node<NodeId, NodeValue, Self::Storage>) -> bool,
+    ) -> Option<&Node<NodeId, NodeValue, Self::Storage>> {
+        let bucket = lowest_entropy_candidates(self, filter);
+        rng.choose_node(bucket.len(), None)
+            .map(|index| bucket[index])
     }
 
     /// called by [collapse_wave] so that it is possible to track progress.
     /// It is valid for an implementation to ignore this and always return [None] fron `get_last_collapsed`
     fn set_last_collapsed_id(&self, node_id: NodeId);
 
-    /// returns the id of the [Node] set by `set_last_collapsed_id`.
-    /// It is valid for an implementation to always return [None].
-    fn get_last_collapsed_id(&self) -> Option<NodeId>;
+    /// returns `true` if [WaveShape::record_pruning] should be called for the values removed
+    /// from the node `node_id`. The default implementation returns `false`.
+    fn is_pruning_traced(&self, _node_id: &NodeId) -> bool {
+        false
+    }
+
+    /// called for each value removed from a node for which [WaveShape::is_pruning_traced]
+    /// returns `true`, e.g. to find out why a node can never have a value while debugging a
+    /// tileset. The default implementation ignores it.
+    fn record_pruning(&self, _node_id: NodeId, _pruning: Pruning<NodeId, NodeValue>) {}
+
+    /// called after the possible values of the node `node_id` changed in `step`, e.g. to record
+    /// the changes as they happen, see [crate::event_sourced::EventSourcedShape]. Removed values
+    /// are stamped with `step`. The default implementation ignores it.
+    fn record_change(&self, _node_id: NodeId, _step: u32) {}
+}
+
+/// Why a value was removed from a node, see [WaveShape::record_pruning].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pruning<NodeId, NodeValue> {
+    /// the removed value.
+    pub value: NodeValue,
+    /// the step of the collapse the value was removed in.
+    pub step: u32,
+    /// the neighbors whose changes made the value invalid. The value was invalid against the
+    /// kernel after these nodes changed. This is empty if the value was removed because
+    /// the node itself was collapsed to a different value.
+    pub causes: Vec<NodeId>,
 }
 
+/// implements [ReadShape] and [WaveShape] for a pointer type by forwarding to the shape it points
+/// to.
+macro_rules! forward_shape {
+    ($($pointer:ty),*) => {$(
+        impl<NodeId, NodeValue, Shape> ReadShape<NodeId, NodeValue> for $pointer
+        where
+            NodeValue: Clone,
+            Shape: ReadShape<NodeId, NodeValue> + ?Sized,
+        {
+            type Storage = Shape::Storage;
+
+            fn iter_node_ids(&self) -> NodeIdIter<NodeId> {
+                (**self).iter_node_ids()
+            }
+
+            fn get_node(&self, id: &NodeId) -> Option<&Node<NodeId, NodeValue, Self::Storage>> {
+                (**self).get_node(id)
+            }
+
+            fn is_collapsed(&self) -> bool {
+                (**self).is_collapsed()
+            }
+
+            fn is_overspecified(&self) -> bool {
+                (**self).is_overspecified()
+            }
+
+            fn is_weighted(&self) -> bool {
+                (**self).is_weighted()
+            }
+
+            fn value_weight(&self, value: &NodeValue) -> f32 {
+                (**self).value_weight(value)
+            }
+
+            fn node_value_weight(&self, id: &NodeId, value: &NodeValue) -> f32 {
+                (**self).node_value_weight(id, value)
+            }
+
+            fn memory_usage(&self) -> usize {
+                (**self).memory_usage()
+            }
+
+            fn get_last_collapsed_id(&self) -> Option<NodeId> {
+                (**self).get_last_collapsed_id()
+            }
+
+            fn validate(&self) -> Result<()>
+            where
+                NodeId: Debug,
+            {
+                (**self).validate()
+            }
+        }
+
+        impl<NodeId, NodeValue, Shape> WaveShape<NodeId, NodeValue> for $pointer
+        where
+            NodeValue: Clone,
+            Shape: WaveShape<NodeId, NodeValue> + ?Sized,
+        {
+            fn choose_random_with_lowest_entropy_where(
+                &self,
+                rng: &mut impl EntropySource,
+                filter: impl Fn(&Node<NodeId, NodeValue, Self::Storage>) -> bool,
+            ) -> Option<&Node<NodeId, NodeValue, Self::Storage>> {
+                (**self).choose_random_with_lowest_entropy_where(rng, filter)
+            }
+
+            fn set_last_collapsed_id(&self, node_id: NodeId) {
+                (**self).set_last_collapsed_id(node_id)
+            }
+
+            fn is_pruning_traced(&self, node_id: &NodeId) -> bool {
+                (**self).is_pruning_traced(node_id)
+            }
+
+            fn record_pruning(&self, node_id: NodeId, pruning: Pruning<NodeId, NodeValue>) {
+                (**self).record_pruning(node_id, pruning)
+            }
+
+            fn record_change(&self, node_id: NodeId, step: u32) {
+                (**self).record_change(node_id, step)
+            }
+        }
+    )*};
+}
+
+forward_shape!(&Shape, Rc<Shape>, Box<Shape>);
+
 /// A wave kernel is a structure that represents all nodes that can affect the [Node] that is
 /// used to create the kernel, e.g. in a tile map that would be all nodes sorounding the center node.
 pub trait WaveKernel<
@@ -96,17 +313,91 @@ pub trait WaveKernel<
     Shape: WaveShape<NodeId, NodeValueDescription>,
 >
 {
+    /// the direction from the center to a node of the kernel, e.g. an offset. Solvers that
+    /// only depend on this, like adjacency or socket solvers, can be written once for all
+    /// kernels with the same direction type.
+    type Direction;
+
     /// Creates a kernel for the given [Node] and [WaveShape]. A kernel needs to contain all nodes that can
     /// influcence the current nodes valid states.
-    fn new(shape: Rc<Shape>, node: &Node<NodeId, NodeValueDescription>) -> Self;
+    fn new(shape: Rc<Shape>, node: &Node<NodeId, NodeValueDescription, Shape::Storage>) -> Self;
+
+    /// returns the id of the [Node] that was used to create this kernel.
+    fn center_id(&self) -> NodeId;
+
+    /// returns the [Node] that was used to create this kernel, so solvers can inspect it
+    /// without looking it up in the shape.
+    fn center(&self) -> &Node<NodeId, NodeValueDescription, Shape::Storage>;
 
     /// returns an [Iterator] over all ids of the [Node]s in the [WaveKernel].
     fn iter_node_ids(&self) -> NodeIdIter<NodeId>;
 
+    /// returns an [Iterator] over all ids of the [Node]s in the [WaveKernel] except for the
+    /// center. The default implementation filters the result of `iter_node_ids`.
+    fn iter_node_ids_without_center(&self) -> NodeIdIter<NodeId>
+    where
+        NodeId: PartialEq,
+    {
+        let center = self.center_id();
+        let ids: Vec<_> = self.iter_node_ids().filter(|id| *id != center).collect();
+        ids.into_iter()
+    }
+
     /// returns an [Iterator] over all nodes in the [WaveKernel].
     fn iter_nodes(&self) -> NodeIter<NodeId, NodeValueDescription, Self> {
         NodeIter::new(self.iter_node_ids(), self)
     }
+
+    /// returns an [Iterator] over all nodes in the [WaveKernel] except for the center, together
+    /// with their [WaveKernel::Direction] from the center. A node can be returned more than
+    /// once with different directions, e.g. if the kernel wraps around a small shape.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::collections::HashSet;
+    /// use wave_collapse::wave_function::{WaveKernel, WaveShape};
+    ///
+    /// /// returns `true` if there is a rule for `value` and a possible value of each neighbor in
+    /// /// the direction of that neighbor. Works for every kernel, e.g. with offsets as directions.
+    /// fn is_valid<NodeId, Shape, Kernel>(
+    ///     rules: &HashSet<(u32, u32, Kernel::Direction)>,
+    ///     value: &u32,
+    ///     kernel: &Kernel,
+    /// ) -> bool
+    /// where
+    ///     Shape: WaveShape<NodeId, u32>,
+    ///     Kernel: WaveKernel<NodeId, u32, Shape>,
+    ///     Kernel::Direction: Eq + std::hash::Hash + Clone,
+    /// {
+    ///     kernel.iter_directed().all(|(direction, node)| {
+    ///         node.any_value(|other| rules.contains(&(*value, *other, direction.clone())))
+    ///     })
+    /// }
+    /// ```
+    fn iter_directed(
+        &self,
+    ) -> DirectedNodeIter<'_, Self::Direction, NodeId, NodeValueDescription, Shape::Storage>;
+
+    /// called by [collapse_wave] before the kernel is passed to a [WaveSolver] with the ids of the
+    /// nodes that changed since the center was last validated, or [None] if the center was never
+    /// validated. All values of the center were valid against the unchanged nodes, so a kernel
+    /// can expose this to let solvers skip those checks.
+    /// The default implementation ignores this.
+    fn set_changed_ids(&mut self, _changed: Option<Vec<NodeId>>) {}
+
+    /// called by [WaveKernel::set_radii] with a single radius for all axes. The kernel should
+    /// only contain the nodes within `radius` of the center afterwards.
+    /// The default implementation ignores this, e.g. for kernels with a fixed size.
+    fn set_radius(&mut self, _radius: u32) {}
+
+    /// called by [collapse_wave] after the kernel is created, if the [WaveSolver] declares
+    /// [WaveSolver::kernel_radii]. `radii` contains a radius per axis or a single radius for all
+    /// axes. The default implementation passes the largest radius to [WaveKernel::set_radius].
+    fn set_radii(&mut self, radii: &[u32]) {
+        if let Some(&radius) = radii.iter().max() {
+            self.set_radius(radius);
+        }
+    }
 }
 
 /// collapses the `shape` so that each [Node] in the [WaveShape] has only value.
@@ -114,8 +405,8 @@ pub trait WaveKernel<
 /// The result is an [Iterator]. Each iteration collapses a single [Node] in the [WaveShape] and
 /// yields the itermediate [WaveShape].
 /// When the [Iterator] yields [None], the result value in the [GenIterReturn] is the [Result]
-/// of the collapse. In the success case the collapsed [WaveShape] or a [WaveCollapseError]
-/// if something went wrong.
+/// of the collapse. In the success case a [CollapseOutcome] with the collapsed [WaveShape] or a
+/// [WaveCollapseError] if something went wrong.
 ///
 /// * `result.next()`: a reference to the [WaveShape]. The state of this [WaveShape] is not
 ///         stable and will change with each iteration. Each iteration returns a reference to
@@ -123,6 +414,8 @@ pub trait WaveKernel<
 /// * `result.calculate_result()`: Automatically advances the [Iterator] until it yields [None]
 ///         and than returns the [Result] of the wave function collapse. See [crate::GenIterReturnResult]
 ///
+/// The collapse is reproducible for a seeded `rng`, see [crate::rng].
+///
 /// # Example
 /// ```no_run
 /// use wave_collapse::{collapse_wave, GenIterReturnResult};
@@ -147,97 +440,1589 @@ pub trait WaveKernel<
 ///     // print_tile_map(&shape);
 /// }
 /// match result_iter.calc_result() {
-///     Ok(shape) => todo!(), // print_tile_map(&shape)
+///     Ok(outcome) => todo!(), // print_tile_map(&outcome.shape)
 ///     Err(error) => eprintln!("Failed to collapse wave: {error:?}"),
 /// }
 /// ```
 pub fn collapse_wave<'solver, Shape, NodeId, NodeValue, Kernel, Solver>(
     shape: Shape,
     solver: &'solver Solver,
-    rng: &'solver mut impl Rng,
-) -> GenIterReturn<impl Generator<Yield = Rc<Shape>, Return = Result<Rc<Shape>>> + 'solver>
+    rng: &'solver mut impl EntropySource,
+) -> GenIterReturn<
+    impl Generator<Yield = Rc<Shape>, Return = Result<CollapseOutcome<Shape>>> + 'solver,
+>
 where
     NodeId: Copy + Eq + Hash + Debug,
     NodeValue: Clone + PartialEq + Debug,
     Shape: WaveShape<NodeId, NodeValue> + 'solver,
     Kernel: WaveKernel<NodeId, NodeValue, Shape>,
     Solver: WaveSolver<NodeValue, Kernel>,
+{
+    collapse_wave_where(shape, solver, rng, |_| true)
+}
+
+/// like [collapse_wave], but only the nodes for which `filter` returns `true` are chosen to be
+/// collapsed. The result is returned as soon as all of those nodes are collapsed. The other nodes
+/// are still constrained by propagation, but might not be collapsed.
+pub fn collapse_wave_where<'solver, Shape, NodeId, NodeValue, Kernel, Solver, Filter>(
+    shape: Shape,
+    solver: &'solver Solver,
+    rng: &'solver mut impl EntropySource,
+    filter: Filter,
+) -> GenIterReturn<
+    impl Generator<Yield = Rc<Shape>, Return = Result<CollapseOutcome<Shape>>> + 'solver,
+>
+where
+    NodeId: Copy + Eq + Hash + Debug,
+    NodeValue: Clone + PartialEq + Debug,
+    Shape: WaveShape<NodeId, NodeValue> + 'solver,
+    Kernel: WaveKernel<NodeId, NodeValue, Shape>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+    Filter: Fn(&NodeId) -> bool + 'solver,
+{
+    collapse_wave_inner(shape, solver, rng, filter, ())
+}
+
+/// like [collapse_wave], but the [StepTelemetry] of each step is passed to `telemetry`.
+/// See [crate::telemetry::TelemetryWriter] to write the telemetry as CSV or JSON lines.
+pub fn collapse_wave_with_telemetry<'solver, Shape, NodeId, NodeValue, Kernel, Solver, Sink>(
+    shape: Shape,
+    solver: &'solver Solver,
+    rng: &'solver mut impl EntropySource,
+    telemetry: Sink,
+) -> GenIterReturn<
+    impl Generator<Yield = Rc<Shape>, Return = Result<CollapseOutcome<Shape>>> + 'solver,
+>
+where
+    NodeId: Copy + Eq + Hash + Debug,
+    NodeValue: Clone + PartialEq + Debug,
+    Shape: WaveShape<NodeId, NodeValue> + 'solver,
+    Kernel: WaveKernel<NodeId, NodeValue, Shape>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+    Sink: TelemetrySink<NodeId> + 'solver,
+{
+    collapse_wave_inner(shape, solver, rng, |_| true, telemetry)
+}
+
+/// Collapses shapes created by `create_shape` until one collapses without a contradiction.
+/// After `max_retries` failed retries the last [PropagationError::InvalidSuperposition] is
+/// returned. Other errors are returned immediately.
+///
+/// Unlike [collapse_wave] this does not yield intermediate shapes. The [CollapseStats] contain
+/// the number of contradictions and retries and the time spent on all attempts.
+pub fn collapse_wave_retrying<Shape, NodeId, NodeValue, Kernel, Solver, CreateShape>(
+    create_shape: CreateShape,
+    solver: &Solver,
+    rng: &mut impl EntropySource,
+    max_retries: u32,
+) -> Result<CollapseOutcome<Shape>>
+where
+    NodeId: Copy + Eq + Hash + Debug,
+    NodeValue: Clone + PartialEq + Debug,
+    Shape: WaveShape<NodeId, NodeValue>,
+    Kernel: WaveKernel<NodeId, NodeValue, Shape>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+    CreateShape: Fn() -> Shape,
+{
+    let start = Instant::now();
+    let mut contradictions = 0;
+    let mut attempts: u32 = 0;
+
+    loop {
+        attempts += 1;
+        let result = collapse_wave::<_, _, _, Kernel, _>(create_shape(), solver, rng).calc_result();
+        match result {
+            Ok(mut outcome) => {
+                outcome.stats.contradictions = contradictions;
+                // every attempt after the first is a retry
+                outcome.stats.retries = attempts - 1;
+                outcome.stats.elapsed = start.elapsed();
+                return Ok(outcome);
+            }
+            Err(WaveCollapseError::Propagation(..)) if contradictions < max_retries => {
+                contradictions += 1;
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// Options for [collapse_fully].
+#[derive(Debug, Clone)]
+pub struct CollapseConfig<R> {
+    rng: R,
+    seed: Option<u64>,
+    substreams: bool,
+    queue_strategy: QueueStrategy,
+    lookahead: Option<usize>,
+}
+
+impl<R: EntropySource> CollapseConfig<R> {
+    pub fn new(rng: R) -> Self {
+        CollapseConfig {
+            rng,
+            seed: None,
+            substreams: false,
+            queue_strategy: QueueStrategy::default(),
+            lookahead: None,
+        }
+    }
+
+    /// Sets the seed that is reported in the [CollapseStats]. This does not seed the rng.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Derives every decision from `seed` and the id of the node it is about, see
+    /// [crate::rng::substream], instead of taking them from the rng one after another.
+    ///
+    /// That way pinning a node or collapsing the nodes in a different order only changes the
+    /// nodes that are actually affected, instead of the whole map. Among the nodes with the
+    /// lowest entropy, the node with the lowest hash of `seed` and its id is collapsed next.
+    pub fn with_substreams(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self.substreams = true;
+        self
+    }
+
+    /// Sets the [QueueStrategy] used during propagation.
+    pub fn with_queue_strategy(mut self, queue_strategy: QueueStrategy) -> Self {
+        self.queue_strategy = queue_strategy;
+        self
+    }
+
+    /// like [CollapseConfig::with_queue_strategy], but changes an existing config, e.g. through
+    /// [Collapser::configure_mut].
+    pub fn set_queue_strategy(&mut self, queue_strategy: QueueStrategy) {
+        self.queue_strategy = queue_strategy;
+    }
+
+    /// Chooses the next node with a one-step lookahead. Up to `candidates` of the nodes with the
+    /// lowest entropy are scored by how many values their neighbors would lose if they were
+    /// collapsed, and the node with the highest impact is collapsed next. This reduces late
+    /// contradictions on tight tilesets, at the cost of validating the neighbors of each
+    /// candidate for each of its values.
+    ///
+    /// The first candidate is chosen by [WaveShape::choose_random_with_lowest_entropy], so the
+    /// weights of the shape are respected and there is still a single call to the rng per
+    /// selection. The other candidates are the nodes with the same entropy that follow it. The
+    /// lookahead is ignored if [CollapseConfig::with_substreams] is used. `candidates` must not
+    /// be 0.
+    ///
+    /// Only collapses that take a [CollapseConfig], i.e. [collapse_fully], [collapse_prepared]
+    /// and [Collapser], use the lookahead. [collapse_wave] and the functions built on it always
+    /// collapse the node chosen by the shape.
+    pub fn with_lookahead(mut self, candidates: usize) -> Self {
+        assert!(candidates > 0, "lookahead needs at least one candidate");
+        self.lookahead = Some(candidates);
+        self
+    }
+
+    /// like [CollapseConfig::with_lookahead], but changes an existing config, e.g. through
+    /// [Collapser::configure_mut]. [None] disables the lookahead.
+    pub fn set_lookahead(&mut self, candidates: Option<usize>) {
+        assert!(
+            candidates != Some(0),
+            "lookahead needs at least one candidate"
+        );
+        self.lookahead = candidates;
+    }
+}
+
+#[cfg(feature = "deterministic")]
+impl CollapseConfig<crate::rng::DeterministicRng> {
+    /// Creates a config with a [crate::rng::DeterministicRng] seeded with `seed`.
+    pub fn seeded(seed: u64) -> Self {
+        CollapseConfig::new(crate::rng::DeterministicRng::new(seed)).with_seed(seed)
+    }
+}
+
+/// like [collapse_wave], but runs the collapse to completion without yielding intermediate
+/// shapes. Use this if only the collapsed shape is of interest.
+///
+/// The result is the same as the result of [collapse_wave] with the same rng.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::wave_function::WaveSolver;
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Wrapping};
+/// use wave_collapse::wave_function::{collapse_fully, CollapseConfig};
+/// # struct TestSolver;
+/// # impl WaveSolver<u32, Kernel2D<Wrapping, u32>> for TestSolver {
+/// #     fn is_valid(&self, _value: &u32, _kernel: &Kernel2D<Wrapping, u32>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// let tiles: Vec<u32> = vec![0, 1, 2];
+/// let shape = TileMap2D::new(Size2D::square(100), Size2D::square(3), &tiles);
+/// let config = CollapseConfig::new(rand::thread_rng());
+/// let outcome =
+///     collapse_fully::<_, _, _, Kernel2D<Wrapping, u32>, _, _>(shape, &TestSolver, config);
+/// ```
+pub fn collapse_fully<Shape, NodeId, NodeValue, Kernel, Solver, R>(
+    shape: Shape,
+    solver: &Solver,
+    config: CollapseConfig<R>,
+) -> Result<CollapseOutcome<Shape>>
+where
+    NodeId: Copy + Eq + Hash + Debug,
+    NodeValue: Clone + PartialEq + Debug,
+    Shape: WaveShape<NodeId, NodeValue>,
+    Kernel: WaveKernel<NodeId, NodeValue, Shape>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+    R: EntropySource,
+{
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("collapse_fully").entered();
+
+    Collapser::<_, _, _, Kernel, _, _>::new(shape, solver, config)?.finish()
+}
+
+/// like [collapse_fully], but calls [WaveSolver::prepare] with `shape` first. The solver is
+/// borrowed mutably for the preparation only.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Wrapping};
+/// use std::collections::HashMap;
+/// use wave_collapse::wave_function::{
+///     collapse_prepared, CollapseConfig, ReadShape, WaveKernel, WaveSolver,
+/// };
+///
+/// /// looks up the allowed neighbors of each value in a table indexed by palette position
+/// struct TableSolver {
+///     index: HashMap<u32, usize>,
+///     allowed: Vec<Vec<bool>>,
+/// }
+///
+/// impl WaveSolver<u32, Kernel2D<Wrapping, u32>> for TableSolver {
+///     fn is_valid(&self, value: &u32, kernel: &Kernel2D<Wrapping, u32>) -> bool {
+///         let row = &self.allowed[self.index[value]];
+///         kernel
+///             .iter_directed()
+///             .all(|(_, node)| node.any_value(|other| row[self.index[other]]))
+///     }
+///
+///     fn prepare<NodeId, Shape>(&mut self, shape: &Shape)
+///     where
+///         Shape: ReadShape<NodeId, u32> + ?Sized,
+///     {
+///         let palette = shape.iter_nodes().next().unwrap().possible_values().to_vec();
+///         self.index = palette.iter().enumerate().map(|(i, v)| (*v, i)).collect();
+///         self.allowed = palette
+///             .iter()
+///             .map(|a| palette.iter().map(|b| a.abs_diff(*b) <= 1).collect())
+///             .collect();
+///     }
+/// }
+///
+/// let shape = TileMap2D::new(Size2D::square(100), Size2D::square(3), &[0u32, 1, 2]);
+/// let mut solver = TableSolver { index: HashMap::new(), allowed: Vec::new() };
+/// let config = CollapseConfig::new(rand::thread_rng());
+/// let outcome =
+///     collapse_prepared::<_, _, _, Kernel2D<Wrapping, u32>, _, _>(shape, &mut solver, config);
+/// ```
+pub fn collapse_prepared<Shape, NodeId, NodeValue, Kernel, Solver, R>(
+    shape: Shape,
+    solver: &mut Solver,
+    config: CollapseConfig<R>,
+) -> Result<CollapseOutcome<Shape>>
+where
+    NodeId: Copy + Eq + Hash + Debug,
+    NodeValue: Clone + PartialEq + Debug,
+    Shape: WaveShape<NodeId, NodeValue>,
+    Kernel: WaveKernel<NodeId, NodeValue, Shape>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+    R: EntropySource,
+{
+    solver.prepare::<NodeId, Shape>(&shape);
+    collapse_fully::<_, _, _, Kernel, _, _>(shape, solver, config)
+}
+
+/// A collapse that is run one step at a time, like [collapse_fully]. Between steps the config
+/// can be changed with [Collapser::configure_mut] and the shape with [Collapser::shape_mut],
+/// e.g. from the sliders of an interactive tool. Changes take effect with the next step.
+///
+/// Running all steps without changes gives the same result as [collapse_fully] with the same
+/// config.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::wave_function::WaveSolver;
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Wrapping};
+/// use wave_collapse::wave_function::{CollapseConfig, Collapser, QueueStrategy};
+/// # struct TestSolver;
+/// # impl WaveSolver<&str, Kernel2D<Wrapping, &str>> for TestSolver {
+/// #     fn is_valid(&self, _value: &&str, _kernel: &Kernel2D<Wrapping, &str>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// # let water_slider = || 2.0;
+/// let shape = TileMap2D::new_weighted(
+///     Size2D::square(100),
+///     Size2D::square(3),
+///     &[("grass", 8.0), ("water", 1.0)],
+/// );
+/// let config = CollapseConfig::new(rand::thread_rng());
+/// let mut collapser =
+///     Collapser::<_, _, _, Kernel2D<Wrapping, &str>, _, _>::new(shape, &TestSolver, config)?;
+/// while collapser.step()? {
+///     if let Some(shape) = collapser.shape_mut() {
+///         shape.set_weight(&"water", water_slider());
+///     }
+///     collapser
+///         .configure_mut()
+///         .set_queue_strategy(QueueStrategy::default());
+/// }
+/// let outcome = collapser.finish()?;
+/// # Ok::<(), wave_collapse::error::WaveCollapseError>(())
+/// ```
+pub struct Collapser<'solver, Shape, NodeId, NodeValue, Kernel, Solver, R> {
+    shape: Rc<Shape>,
+    solver: &'solver Solver,
+    config: CollapseConfig<R>,
+    generation: u32,
+    validated: HashSet<NodeId>,
+    contradiction: Option<ErrorContext>,
+    shape_memory: usize,
+    peak_memory: usize,
+    start: Instant,
+    _types: PhantomData<fn() -> (NodeValue, Kernel)>,
+}
+
+impl<'solver, Shape, NodeId, NodeValue, Kernel, Solver, R>
+    Collapser<'solver, Shape, NodeId, NodeValue, Kernel, Solver, R>
+where
+    NodeId: Copy + Eq + Hash + Debug,
+    NodeValue: Clone + PartialEq + Debug,
+    Shape: WaveShape<NodeId, NodeValue>,
+    Kernel: WaveKernel<NodeId, NodeValue, Shape>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+    R: EntropySource,
+{
+    /// Creates a collapse of `shape` that has not run any steps yet.
+    ///
+    /// returns [EngineError::EmptyInput] if `shape` has no nodes.
+    pub fn new(shape: Shape, solver: &'solver Solver, config: CollapseConfig<R>) -> Result<Self> {
+        let start = Instant::now();
+        let shape = Rc::new(shape);
+
+        if shape.iter_nodes().count() == 0 {
+            return Err(EngineError::EmptyInput.into());
+        }
+
+        #[cfg(debug_assertions)]
+        crate::validate::debug_validate(shape.as_ref())?;
+
+        let shape_memory = shape.memory_usage();
+        Ok(Collapser {
+            shape,
+            solver,
+            config,
+            generation: 0,
+            validated: HashSet::new(),
+            contradiction: None,
+            shape_memory,
+            peak_memory: shape_memory,
+            start,
+            _types: PhantomData,
+        })
+    }
+
+    /// returns the config used by the following steps, so it can be changed between steps.
+    pub fn configure_mut(&mut self) -> &mut CollapseConfig<R> {
+        &mut self.config
+    }
+
+    /// returns the shape that is collapsed.
+    pub fn shape(&self) -> &Rc<Shape> {
+        &self.shape
+    }
+
+    /// returns the shape, so it can be changed between steps, e.g. with
+    /// [crate::tile2d::TileMap2D::set_weight], or [None] if the shape returned by
+    /// [Collapser::shape] is still referenced elsewhere.
+    ///
+    /// Values removed with [Node::with_values_mut] are propagated by the next [Collapser::step].
+    pub fn shape_mut(&mut self) -> Option<&mut Shape> {
+        Rc::get_mut(&mut self.shape)
+    }
+
+    /// returns the number of steps that were run.
+    pub fn steps(&self) -> u32 {
+        self.generation
+    }
+
+    /// Collapses one node and propagates the change.
+    ///
+    /// returns `false` if the shape was already collapsed, or an error if the shape is left
+    /// overspecified.
+    pub fn step(&mut self) -> Result<bool> {
+        let generation = self.generation;
+        if self.shape.is_collapsed() {
+            return Ok(false);
+        }
+
+        let dirty = propagate_dirty::<_, _, _, Kernel, _>(
+            &self.shape,
+            self.solver,
+            generation,
+            &mut self.validated,
+            self.config.queue_strategy,
+        );
+        if dirty.contradiction.is_some() {
+            self.contradiction = dirty.contradiction;
+        }
+
+        #[cfg(feature = "test-util")]
+        if let Some(error) = crate::testing::inject::take_injected_failure(generation.into()) {
+            return Err(error.at_step(generation));
+        }
+        if self.shape.is_overspecified() {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(step = generation, "contradiction");
+            return Err(contradiction_error(
+                Some(generation),
+                self.contradiction.take(),
+            ));
+        }
+
+        let config = &mut self.config;
+        let node = match (config.seed, config.lookahead) {
+            (Some(seed), _) if config.substreams => choose_by_substream(self.shape.as_ref(), seed),
+            (_, Some(candidates)) => choose_by_lookahead::<_, _, _, Kernel, _>(
+                &self.shape,
+                self.solver,
+                &mut config.rng,
+                candidates,
+            ),
+            _ => self
+                .shape
+                .choose_random_with_lowest_entropy(&mut config.rng),
+        }
+        .expect("This should never be none, because shape is not collapsed or overspecified");
+        #[cfg(feature = "tracing")]
+        tracing::trace!(step = generation, node = ?node.id, entropy = node.entropy(), "selected node");
+
+        match config.seed {
+            Some(seed) if config.substreams => collapse_node(
+                self.shape.as_ref(),
+                node,
+                generation,
+                &mut substream(seed, &node.id),
+            ),
+            _ => collapse_node(self.shape.as_ref(), node, generation, &mut config.rng),
+        }
+        self.shape.set_last_collapsed_id(node.id);
+        #[cfg(feature = "tracing")]
+        tracing::trace!(step = generation, node = ?node.id, value = ?node.collapsed(), "collapsed node");
+
+        let propagation = propagate::<_, _, _, Kernel, _>(
+            &self.shape,
+            self.solver,
+            node.id,
+            generation,
+            &mut self.validated,
+            config.queue_strategy,
+        );
+        self.contradiction = propagation.contradiction;
+        self.peak_memory = self.peak_memory.max(collapse_memory(
+            self.shape_memory,
+            &self.validated,
+            propagation.queue_length,
+        ));
+
+        self.generation += 1;
+        Ok(true)
+    }
+
+    /// Runs the remaining steps.
+    ///
+    /// returns the collapsed shape, or an error if the shape is left overspecified.
+    pub fn finish(mut self) -> Result<CollapseOutcome<Shape>> {
+        while self.step()? {}
+
+        Ok(CollapseOutcome {
+            shape: self.shape,
+            stats: CollapseStats {
+                seed: self.config.seed,
+                iterations: self.generation,
+                elapsed: self.start.elapsed(),
+                peak_memory: self.peak_memory,
+                ..Default::default()
+            },
+        })
+    }
+}
+
+fn collapse_wave_inner<'solver, Shape, NodeId, NodeValue, Kernel, Solver, Filter, Sink>(
+    shape: Shape,
+    solver: &'solver Solver,
+    rng: &'solver mut impl EntropySource,
+    filter: Filter,
+    mut telemetry: Sink,
+) -> GenIterReturn<
+    impl Generator<Yield = Rc<Shape>, Return = Result<CollapseOutcome<Shape>>> + 'solver,
+>
+where
+    NodeId: Copy + Eq + Hash + Debug,
+    NodeValue: Clone + PartialEq + Debug,
+    Shape: WaveShape<NodeId, NodeValue> + 'solver,
+    Kernel: WaveKernel<NodeId, NodeValue, Shape>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+    Filter: Fn(&NodeId) -> bool + 'solver,
+    Sink: TelemetrySink<NodeId> + 'solver,
 {
     let result_iter = gen_iter_return!(move {
+        let start = Instant::now();
 
         let shape = Rc::new(shape);
 
         if shape.iter_nodes().count() == 0 {
-            return Err(WaveCollapseError::EmptyInput);
+            return Err(EngineError::EmptyInput.into());
         }
 
+        #[cfg(debug_assertions)]
+        crate::validate::debug_validate(shape.as_ref())?;
+
+        // removed values are stamped with the generation, which is incremented for every
+        // collapsed node
+        let mut generation: u32 = 0;
+
+        // nodes that were validated at least once. All other nodes need to be validated against
+        // their entire kernel.
+        let mut validated = HashSet::new();
+
+        // where the last propagation left a node without values
+        let mut contradiction = None;
+
+        let shape_memory = shape.memory_usage();
+        let mut peak_memory = shape_memory;
+
+        // the sum of the entropy of all nodes, only tracked if telemetry is enabled
+        let mut total_entropy: u64 = if telemetry.is_enabled() {
+            shape.iter_nodes().map(|node| node.entropy() as u64).sum()
+        } else {
+            0
+        };
+
         loop {
-            if shape.is_collapsed() {
-                return Ok(shape.clone());
+            if shape.iter_nodes().filter(|node| filter(&node.id)).all(|node| node.is_collapsed()) {
+                return Ok(CollapseOutcome {
+                    shape: shape.clone(),
+                    stats: CollapseStats {
+                        iterations: generation,
+                        elapsed: start.elapsed(),
+                        peak_memory,
+                        ..Default::default()
+                    },
+                });
+            }
+            #[cfg(feature = "test-util")]
+            if let Some(error) = crate::testing::inject::take_injected_failure(generation.into()) {
+                return Err(error.at_step(generation));
+            }
+
+            // nodes changed by constraints or through a yielded shape
+            let dirty = propagate_dirty::<_, _, _, Kernel, _>(
+                &shape,
+                solver,
+                generation,
+                &mut validated,
+                QueueStrategy::default(),
+            );
+            if dirty.contradiction.is_some() {
+                contradiction = dirty.contradiction;
             }
             if shape.is_overspecified() {
-                return Err(WaveCollapseError::InvalidSuperposition);
+                #[cfg(feature = "tracing")]
+                tracing::debug!(step = generation, "contradiction");
+                return Err(contradiction_error(Some(generation), contradiction));
             }
 
-            let first_node = shape.choose_random_with_lowest_entropy(rng)
+            let first_node = shape.choose_random_with_lowest_entropy_where(rng, |node| filter(&node.id))
                 .expect("This should never be none, because shape is not collapsed or overspecified");
+            #[cfg(feature = "tracing")]
+            tracing::trace!(step = generation, node = ?first_node.id, entropy = first_node.entropy(), "selected node");
+
 
+            let mut pruned = first_node.entropy() as u64 - 1 + dirty.pruned;
 
             // randomly choose a value from and assign it to the first node
-            collapse_node(first_node, rng);
+            collapse_node(shape.as_ref(), first_node, generation, rng);
             shape.set_last_collapsed_id(first_node.id);
+            #[cfg(feature = "tracing")]
+            tracing::trace!(step = generation, node = ?first_node.id, value = ?first_node.collapsed(), "collapsed node");
+
+            let propagation = propagate::<_, _, _, Kernel, _>(
+                &shape,
+                solver,
+                first_node.id,
+                generation,
+                &mut validated,
+                QueueStrategy::default(),
+            );
+            pruned += propagation.pruned;
+            contradiction = propagation.contradiction;
+            let memory = collapse_memory(shape_memory, &validated, propagation.queue_length);
+            peak_memory = peak_memory.max(memory);
+
+            if telemetry.is_enabled() {
+                telemetry.record(&StepTelemetry {
+                    step: generation,
+                    node_id: first_node.id,
+                    entropy_before: total_entropy,
+                    entropy_after: total_entropy - pruned,
+                    pruned,
+                    queue_length: propagation.queue_length,
+                });
+                total_entropy -= pruned;
+            }
+
+            generation += 1;
+
+            // yield the current state of the calculation. That way we can inspect every iteration easily.
+            // also this might be interesting for animation or debugging
+            yield shape.clone();
+        }
+    });
+
+    result_iter
+}
+
+/// Adds `values` back to the possible values of the node `node_id` and propagates the change,
+/// e.g. to repair a contradiction or after a constraint was relaxed by an interactive edit.
+/// Values that are already possible are ignored. The node is no longer collapsed, even if it is
+/// left with a single value.
+///
+/// The added values and the values of all nodes reached by the propagation are validated
+/// against their entire kernel. Values removed by the propagation are stamped with `generation`,
+/// which must not be smaller than the generation of any previous removal, e.g. the
+/// [CollapseStats::iterations] of the collapse that produced `shape`.
+///
+/// returns the number of added values or [PropagationError::InvalidSuperposition] if a node is
+/// left without possible values.
+pub fn expand_domain<Shape, NodeId, NodeValue, Kernel, Solver>(
+    shape: &Rc<Shape>,
+    solver: &Solver,
+    node_id: NodeId,
+    values: impl IntoIterator<Item = NodeValue>,
+    generation: u32,
+) -> Result<usize>
+where
+    NodeId: Copy + Eq + Hash + Debug,
+    NodeValue: Clone + PartialEq + Debug,
+    Shape: WaveShape<NodeId, NodeValue>,
+    Kernel: WaveKernel<NodeId, NodeValue, Shape>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+{
+    let node = shape
+        .get_node(&node_id)
+        .ok_or_else(|| EngineError::UnresolvableNodeId(format!("{node_id:?}")))?;
+
+    let added = node.update_values(|possible_values| {
+        values
+            .into_iter()
+            .map(|value| possible_values.insert(value))
+            .filter(|added| *added)
+            .count()
+    });
+    if added == 0 {
+        return Ok(0);
+    }
+    shape.record_change(node_id, generation);
+
+    let propagation = propagate::<_, _, _, Kernel, _>(
+        shape,
+        solver,
+        node_id,
+        generation,
+        &mut HashSet::new(),
+        QueueStrategy::default(),
+    );
+
+    if shape.is_overspecified() {
+        return Err(contradiction_error(
+            Some(generation),
+            propagation.contradiction,
+        ));
+    }
+    Ok(added)
+}
 
-            let mut open_list = BinaryHeapSet::new();
-            open_list.push(Reverse(first_node));
+/// Propagates the values of all collapsed nodes of `shape`, e.g. the authored nodes of a
+/// template or nodes that were pinned before the collapse. [collapse_wave] only propagates the
+/// nodes it collapses itself, so without this the other nodes are not validated against the
+/// collapsed nodes until the collapse reaches them.
+///
+/// returns the propagated `shape` or [PropagationError::InvalidSuperposition] if a node is left
+/// without possible values.
+pub fn propagate_collapsed<Shape, NodeId, NodeValue, Kernel, Solver>(
+    shape: Shape,
+    solver: &Solver,
+) -> Result<Shape>
+where
+    NodeId: Copy + Eq + Hash + Debug,
+    NodeValue: Clone + PartialEq + Debug,
+    Shape: WaveShape<NodeId, NodeValue>,
+    Kernel: WaveKernel<NodeId, NodeValue, Shape>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+{
+    let shape = Rc::new(shape);
 
-            while let Some(node) = open_list.pop() {
-                let node = node.0;
+    let mut validated = HashSet::new();
+    let collapsed: Vec<_> = shape
+        .iter_nodes()
+        .filter(|node| node.is_collapsed())
+        .map(|node| node.id)
+        .collect();
+    let mut contradiction = None;
+    for id in collapsed {
+        let propagation = propagate::<_, _, _, Kernel, _>(
+            &shape,
+            solver,
+            id,
+            0,
+            &mut validated,
+            QueueStrategy::default(),
+        );
+        contradiction = contradiction.or(propagation.contradiction);
+    }
 
-                let kernel = Kernel::new(shape.clone(), node);
+    if shape.is_overspecified() {
+        return Err(contradiction_error(None, contradiction));
+    }
+    Ok(Rc::try_unwrap(shape).unwrap_or_else(|_| panic!("kernels do not outlive the propagation")))
+}
 
-                let mut values = node.possible_values.borrow_mut();
-                let possibilities_before = values.len();
-                if !node.is_collapsed() {
-                    values.retain(|v| solver.is_valid(v, &kernel));
+/// Removes the values that became invalid because the node `node_id` changed, e.g. because a
+/// custom loop collapsed it or removed some of its values, and propagates the changes until no
+/// more values are removed. This is the propagation used by [collapse_wave], so custom outer
+/// loops, like a search or an interactive tool, can reuse it.
+///
+/// Removed values are stamped with `generation`, so they can be restored with [Node::restore].
+/// A contradiction is not an error here, it is reported in the [PropagationReport] and the
+/// node without values is left in `shape`.
+///
+/// # Example
+/// ```no_run
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Cutoff};
+/// # use wave_collapse::wave_function::WaveSolver;
+/// use std::rc::Rc;
+/// use wave_collapse::wave_function::{propagate_from, ReadShape};
+/// # struct TestSolver;
+/// # impl WaveSolver<char, Kernel2D<Cutoff, char>> for TestSolver {
+/// #     fn is_valid(&self, _value: &char, _kernel: &Kernel2D<Cutoff, char>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// let shape = Rc::new(TileMap2D::new(Size2D::square(10), Size2D::square(3), &['#', '.']));
+///
+/// // the user removed '#' from a node in an editor
+/// let generation = 1;
+/// let node = &shape[(4, 4)];
+/// node.with_values_mut(|values| values.retain(generation, |value| *value != '#'));
+///
+/// let report = propagate_from::<_, _, _, Kernel2D<Cutoff, char>, _>(
+///     &shape,
+///     &TestSolver,
+///     (4, 4),
+///     generation,
+/// );
+/// if report.is_contradiction() {
+///     // undo the edit
+///     shape.iter_nodes().for_each(|node| node.restore(generation));
+/// }
+/// ```
+pub fn propagate_from<Shape, NodeId, NodeValue, Kernel, Solver>(
+    shape: &Rc<Shape>,
+    solver: &Solver,
+    node_id: NodeId,
+    generation: u32,
+) -> PropagationReport
+where
+    NodeId: Copy + Eq + Hash + Debug,
+    NodeValue: Clone + PartialEq + Debug,
+    Shape: WaveShape<NodeId, NodeValue>,
+    Kernel: WaveKernel<NodeId, NodeValue, Shape>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+{
+    propagate::<_, _, _, Kernel, _>(
+        shape,
+        solver,
+        node_id,
+        generation,
+        &mut HashSet::new(),
+        QueueStrategy::default(),
+    )
+}
+
+/// Collapses each node of `pins` to its value and propagates the changes. Either all pins are
+/// applied or, if they are inconsistent, `shape` is left unchanged.
+///
+/// The pins are validated against each other and the rest of `shape` with `solver`. Values
+/// removed by this are stamped with `generation`, which must be larger than the generation of
+/// any previous removal, e.g. the [CollapseStats::iterations] of the collapse that produced
+/// `shape`.
+///
+/// returns a [PinConflict] with a minimal set of inconsistent pins. Finding that set requires
+/// multiple propagations, so it is only done if the pins are inconsistent.
+///
+/// # Example
+/// ```no_run
+/// # use std::rc::Rc;
+/// # use wave_collapse::tile2d::{Kernel2D, Size2D, TileMap2D, wrapping_mode::Cutoff};
+/// # use wave_collapse::wave_function::WaveSolver;
+/// use wave_collapse::wave_function::pin_all;
+/// # struct TestSolver;
+/// # impl WaveSolver<char, Kernel2D<Cutoff, char>> for TestSolver {
+/// #     fn is_valid(&self, _value: &char, _kernel: &Kernel2D<Cutoff, char>) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// let shape = Rc::new(TileMap2D::new(Size2D::square(10), Size2D::square(3), &['#', '.', '~']));
+/// let pins = [((0, 0), '#'), ((5, 5), '~'), ((5, 6), '#')];
+/// if let Err(conflict) = pin_all::<_, _, _, Kernel2D<Cutoff, char>, _>(&shape, &TestSolver, &pins, 0)
+/// {
+///     for index in conflict.pins {
+///         eprintln!("conflicting pin {:?}", pins[index]);
+///     }
+/// }
+/// ```
+pub fn pin_all<Shape, NodeId, NodeValue, Kernel, Solver>(
+    shape: &Rc<Shape>,
+    solver: &Solver,
+    pins: &[(NodeId, NodeValue)],
+    generation: u32,
+) -> std::result::Result<(), PinConflict>
+where
+    NodeId: Copy + Eq + Hash + Debug,
+    NodeValue: Clone + PartialEq + Debug,
+    Shape: WaveShape<NodeId, NodeValue, Storage = Domain<NodeValue>>,
+    Kernel: WaveKernel<NodeId, NodeValue, Shape>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+{
+    // pins that conflict on their own or with another pin of the same node
+    for (index, (id, value)) in pins.iter().enumerate() {
+        if !shape.get_node(id).is_some_and(|node| node.contains(value)) {
+            return Err(PinConflict { pins: vec![index] });
+        }
+        if let Some(other) = pins[..index]
+            .iter()
+            .position(|(other_id, other_value)| other_id == id && other_value != value)
+        {
+            return Err(PinConflict {
+                pins: vec![other, index],
+            });
+        }
+    }
+
+    let all: Vec<usize> = (0..pins.len()).collect();
+    let pinned = apply_pins::<_, _, _, Kernel, _>(shape, solver, pins, &all, generation);
+    if pinned.consistent {
+        return Ok(());
+    }
+    pinned.rollback(shape, generation);
+
+    let is_consistent = |indices: &[usize]| {
+        let pinned = apply_pins::<_, _, _, Kernel, _>(shape, solver, pins, indices, generation);
+        pinned.rollback(shape, generation);
+        pinned.consistent
+    };
+
+    // adding pins only removes values, so the first inconsistent prefix is part of the conflict
+    let last = all.partition_point(|&last| is_consistent(&all[..=last]));
+    let mut conflict: Vec<usize> = (0..last).collect();
+    let mut index = 0;
+    while index < conflict.len() {
+        let mut without: Vec<usize> = conflict.clone();
+        without.remove(index);
+        without.push(last);
+        if is_consistent(&without) {
+            index += 1;
+        } else {
+            conflict.remove(index);
+        }
+    }
+    conflict.push(last);
+
+    Err(PinConflict { pins: conflict })
+}
+
+/// The pins applied by [apply_pins].
+struct AppliedPins<NodeId> {
+    /// `true` if no node is left without values and all pins are valid.
+    consistent: bool,
+    /// the nodes that were not collapsed before.
+    collapsed: Vec<NodeId>,
+}
+
+impl<NodeId> AppliedPins<NodeId> {
+    /// Restores `shape` to the state before the pins were applied.
+    fn rollback<Shape, NodeValue>(&self, shape: &Shape, generation: u32)
+    where
+        NodeId: Copy,
+        NodeValue: Clone,
+        Shape: WaveShape<NodeId, NodeValue, Storage = Domain<NodeValue>>,
+    {
+        for node in shape.iter_nodes() {
+            if node.update_values(|values| values.restore(generation)) > 0 {
+                *node.is_collapsed.borrow_mut() = false;
+                shape.record_change(node.id, generation);
+            }
+        }
+        for id in &self.collapsed {
+            if let Some(node) = shape.get_node(id) {
+                if node.is_collapsed.replace(false) {
+                    shape.record_change(*id, generation);
                 }
+            }
+        }
+    }
+}
 
-                if node.is_collapsed() || possibilities_before != values.len() {
-                    drop(values);
+/// Collapses the nodes of the pins at `indices` and propagates the changes. Collapsed nodes are
+/// not validated by [propagate], so each pin is validated against its kernel afterwards.
+fn apply_pins<Shape, NodeId, NodeValue, Kernel, Solver>(
+    shape: &Rc<Shape>,
+    solver: &Solver,
+    pins: &[(NodeId, NodeValue)],
+    indices: &[usize],
+    generation: u32,
+) -> AppliedPins<NodeId>
+where
+    NodeId: Copy + Eq + Hash + Debug,
+    NodeValue: Clone + PartialEq + Debug,
+    Shape: WaveShape<NodeId, NodeValue, Storage = Domain<NodeValue>>,
+    Kernel: WaveKernel<NodeId, NodeValue, Shape>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+{
+    let mut collapsed = Vec::new();
+    for &index in indices {
+        let (id, value) = &pins[index];
+        let node = shape.get_node(id).expect("pins are validated by pin_all");
+        if !node.is_collapsed() {
+            collapsed.push(*id);
+        }
+        node.update_values(|values| {
+            if let Some(index) = values.as_slice().iter().position(|other| other == value) {
+                values.collapse(generation, index);
+            }
+        });
+        *node.is_collapsed.borrow_mut() = true;
+        shape.record_change(*id, generation);
+    }
 
-                    for node in kernel
-                        .iter_node_ids()
-                        .filter(|id| *id != node.id)
-                        .map(|id|shape.get_node(&id)
-                            .unwrap_or_else(|| panic!("NodeIdIter is always valid. Id: {id:?}")))
-                        .filter(|node| !node.is_collapsed()) {
-                        open_list.push(Reverse(node));
-                    };
+    let mut validated = HashSet::new();
+    for &index in indices {
+        propagate::<_, _, _, Kernel, _>(
+            shape,
+            solver,
+            pins[index].0,
+            generation,
+            &mut validated,
+            QueueStrategy::default(),
+        );
+    }
 
+    let radii = solver.kernel_radii();
+    let consistent = !shape.is_overspecified()
+        && indices.iter().all(|&index| {
+            let (id, value) = &pins[index];
+            let node = shape.get_node(id).expect("pins are validated by pin_all");
+            let mut kernel = Kernel::new(shape.clone(), node);
+            if let Some(radii) = &radii {
+                kernel.set_radii(radii);
+            }
+            solver.is_valid(value, &kernel)
+        });
+
+    AppliedPins {
+        consistent,
+        collapsed,
+    }
+}
+
+/// returns an estimate of the memory used by a collapse of a shape that uses `shape_memory`,
+/// with the `validated` nodes and a propagation queue of `queue_length`.
+fn collapse_memory<NodeId>(
+    shape_memory: usize,
+    validated: &HashSet<NodeId>,
+    queue_length: usize,
+) -> usize {
+    // the open list stores each id in its heap and in its position map
+    shape_memory
+        + validated.capacity() * size_of::<NodeId>()
+        + queue_length * (size_of::<(NodeId, i64)>() + size_of::<(NodeId, usize)>())
+}
+
+/// The result of [propagate_from].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PropagationReport {
+    /// the number of removed values.
+    pub pruned: u64,
+    /// the maximum number of nodes waiting to be validated.
+    pub queue_length: usize,
+    /// describes the first node that was left without values, if any.
+    pub contradiction: Option<ErrorContext>,
+}
 
+impl PropagationReport {
+    /// returns `true` if a node was left without possible values.
+    pub fn is_contradiction(&self) -> bool {
+        self.contradiction.is_some()
+    }
+}
+
+/// returns a [PropagationError::InvalidSuperposition] with the `contradiction` found by
+/// [propagate] as context.
+fn contradiction_error(
+    step: Option<u32>,
+    contradiction: Option<ErrorContext>,
+) -> WaveCollapseError {
+    let context = ErrorContext {
+        step,
+        ..contradiction.unwrap_or_default()
+    };
+    WaveCollapseError::from(PropagationError::InvalidSuperposition).with_context(context)
+}
+
+/// Propagates the changes of all nodes that are dirty, see [Node::is_dirty].
+fn propagate_dirty<Shape, NodeId, NodeValue, Kernel, Solver>(
+    shape: &Rc<Shape>,
+    solver: &Solver,
+    generation: u32,
+    validated: &mut HashSet<NodeId>,
+    strategy: QueueStrategy,
+) -> PropagationReport
+where
+    NodeId: Copy + Eq + Hash + Debug,
+    NodeValue: Clone + PartialEq + Debug,
+    Shape: WaveShape<NodeId, NodeValue>,
+    Kernel: WaveKernel<NodeId, NodeValue, Shape>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+{
+    let dirty: Vec<_> = shape
+        .iter_nodes()
+        .filter(|node| node.is_dirty())
+        .map(|node| node.id)
+        .collect();
+
+    let mut report = PropagationReport::default();
+    for id in dirty {
+        let propagation =
+            propagate::<_, _, _, Kernel, _>(shape, solver, id, generation, validated, strategy);
+        report.pruned += propagation.pruned;
+        report.queue_length = report.queue_length.max(propagation.queue_length);
+        if report.contradiction.is_none() {
+            report.contradiction = propagation.contradiction;
+        }
+    }
+    report
+}
+
+/// Validates the nodes affected by a change of the node `start` until no more values are
+/// removed. Nodes that are not in `validated` are validated against their entire kernel.
+fn propagate<Shape, NodeId, NodeValue, Kernel, Solver>(
+    shape: &Rc<Shape>,
+    solver: &Solver,
+    start: NodeId,
+    generation: u32,
+    validated: &mut HashSet<NodeId>,
+    strategy: QueueStrategy,
+) -> PropagationReport
+where
+    NodeId: Copy + Eq + Hash + Debug,
+    NodeValue: Clone + PartialEq + Debug,
+    Shape: WaveShape<NodeId, NodeValue>,
+    Kernel: WaveKernel<NodeId, NodeValue, Shape>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+{
+    #[cfg(feature = "tracing")]
+    let _span = tracing::trace_span!("propagate", step = generation, start = ?start).entered();
+
+    let mut pruned = 0;
+    let mut contradiction = None;
+
+    let mut open_list = OpenList::new(strategy);
+    open_list.push(
+        start,
+        shape
+            .get_node(&start)
+            .map(|node| {
+                if node.take_dirty() {
+                    shape.record_change(start, generation);
                 }
+                node.entropy()
+            })
+            .unwrap_or_default(),
+    );
+    let mut queue_length = open_list.len();
+    let mut changed_ids: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+    let radii = solver.kernel_radii();
+
+    while let Some(node_id) = open_list.pop() {
+        let node = shape
+            .get_node(&node_id)
+            .unwrap_or_else(|| panic!("open list only contains valid ids. Id: {node_id:?}"));
+
+        let mut kernel = Kernel::new(shape.clone(), node);
+        if let Some(radii) = &radii {
+            kernel.set_radii(radii);
+        }
+        let changed = changed_ids.remove(&node_id);
+        let traced = shape.is_pruning_traced(&node_id);
+        let causes = if traced {
+            changed.clone().unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        kernel.set_changed_ids(if validated.contains(&node_id) {
+            changed
+        } else {
+            None
+        });
+
+        let mut removed = 0;
+        if !node.is_collapsed() {
+            let mut removed_values = Vec::new();
+            removed = node.update_values(|values| {
+                values.retain(generation, |v| {
+                    let valid = solver.is_valid(v, &kernel);
+                    if traced && !valid {
+                        removed_values.push(v.clone());
+                    }
+                    valid
+                })
+            });
+            pruned += removed as u64;
+            if removed > 0 {
+                shape.record_change(node_id, generation);
             }
+            for value in removed_values {
+                let pruning = Pruning {
+                    value,
+                    step: generation,
+                    causes: causes.clone(),
+                };
+                shape.record_pruning(node_id, pruning);
+            }
+            validated.insert(node_id);
 
-            // yield the current state of the calculation. That way we can inspect every iteration easily.
-            // also this might be interesting for animation or debugging
-            yield shape.clone();
+            if contradiction.is_none() && node.is_overspecified() {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(step = generation, node = ?node_id, "no possible values left");
+
+                let constraints = kernel
+                    .iter_node_ids_without_center()
+                    .filter_map(|id| shape.get_node(&id))
+                    .map(|neighbor| {
+                        format!("{:?}: {:?}", neighbor.id, &*neighbor.possible_values())
+                    })
+                    .collect();
+                contradiction = Some(ErrorContext {
+                    step: None,
+                    node_id: Some(format!("{node_id:?}")),
+                    constraints,
+                });
+            }
         }
-    });
 
-    result_iter
+        // the start node changed, even if it is not collapsed and no value was removed
+        if node_id == start || node.is_collapsed() || removed > 0 {
+            for node in kernel
+                .iter_node_ids_without_center()
+                .map(|id| {
+                    shape
+                        .get_node(&id)
+                        .unwrap_or_else(|| panic!("NodeIdIter is always valid. Id: {id:?}"))
+                })
+                .filter(|node| !node.is_collapsed())
+            {
+                open_list.push(node.id, node.entropy());
+                changed_ids.entry(node.id).or_default().push(node_id);
+            }
+            queue_length = queue_length.max(open_list.len());
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    tracing::trace!(pruned, queue_length, "propagation finished");
+
+    PropagationReport {
+        pruned,
+        queue_length,
+        contradiction,
+    }
+}
+
+/// The order in which nodes are validated during propagation. This affects the performance
+/// and which contradictions are discovered first, but not whether a contradiction exists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QueueStrategy {
+    /// validate the node with the lowest entropy first.
+    #[default]
+    Entropy,
+    /// validate nodes in the order they were changed, like a breadth first search.
+    Fifo,
+    /// validate the most recently changed node first, like a depth first search.
+    Lifo,
+}
+
+/// The queue of nodes that need to be validated during propagation. Each node is queued at
+/// most once.
+struct OpenList<NodeId> {
+    queue: IndexedPriorityQueue<NodeId, i64>,
+    strategy: QueueStrategy,
+    pushed: i64,
+}
+
+impl<NodeId: Clone + Eq + Hash> OpenList<NodeId> {
+    fn new(strategy: QueueStrategy) -> Self {
+        OpenList {
+            queue: IndexedPriorityQueue::new(),
+            strategy,
+            pushed: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    fn push(&mut self, node_id: NodeId, entropy: u32) {
+        self.pushed += 1;
+        match self.strategy {
+            QueueStrategy::Entropy => {
+                self.queue.push(node_id, i64::from(entropy));
+            }
+            QueueStrategy::Fifo => {
+                // keep the position of nodes that are already queued
+                if !self.queue.contains(&node_id) {
+                    self.queue.push(node_id, self.pushed);
+                }
+            }
+            QueueStrategy::Lifo => {
+                self.queue.push(node_id, -self.pushed);
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Option<NodeId> {
+        self.queue.pop().map(|(node_id, _)| node_id)
+    }
+}
+
+/// returns the node with the lowest entropy and the lowest [substream] hash of its id, that is
+/// not collapsed or overspecified.
+fn choose_by_substream<Shape, NodeId, NodeValue>(
+    shape: &Shape,
+    seed: u64,
+) -> Option<&Node<NodeId, NodeValue, Shape::Storage>>
+where
+    NodeId: Hash,
+    NodeValue: Clone,
+    Shape: WaveShape<NodeId, NodeValue>,
+{
+    shape
+        .iter_nodes()
+        .filter(|node| !node.is_collapsed() && !node.is_overspecified())
+        .min_by_key(|node| (node.entropy(), substream(seed, &node.id).next_u64()))
+}
+
+/// returns the node to collapse next, see [CollapseConfig::with_lookahead]. The candidates are
+/// consecutive nodes of the lowest entropy, starting at the node chosen by the shape.
+fn choose_by_lookahead<'shape, Shape, NodeId, NodeValue, Kernel, Solver>(
+    shape: &'shape Rc<Shape>,
+    solver: &Solver,
+    rng: &mut impl EntropySource,
+    candidates: usize,
+) -> Option<&'shape Node<NodeId, NodeValue, Shape::Storage>>
+where
+    NodeId: Copy + Eq + Hash + Debug,
+    NodeValue: Clone + PartialEq + Debug,
+    Shape: WaveShape<NodeId, NodeValue>,
+    Kernel: WaveKernel<NodeId, NodeValue, Shape>,
+    Solver: WaveSolver<NodeValue, Kernel>,
+{
+    let first = shape.choose_random_with_lowest_entropy(rng)?;
+    let bucket = lowest_entropy_candidates(shape.as_ref(), |_| true);
+    let Some(start) = bucket.iter().position(|node| node.id == first.id) else {
+        // the shape chose a node by its own rules
+        return Some(first);
+    };
+
+    let mut best = first;
+    let mut best_impact = lookahead_impact::<_, _, _, Kernel, _>(shape, solver, first);
+    for offset in 1..candidates.min(bucket.len()) {
+        let node = bucket[(start + offset) % bucket.len()];
+        let impact = lookahead_impact::<_, _, _, Kernel, _>(shape, solver, node);
+        if impact > best_impact {
+            best = node;
+            best_impact = impact;
+        }
+    }
+    Some(best)
 }
 
-fn collapse_node<NodeId, NodeValue>(node: &Node<NodeId, NodeValue>, rng: &mut impl Rng)
+/// returns the number of values the neighbors of `node` would lose if `node` was collapsed,
+/// summed over all possible values of `node`. The values of `node` are restored afterwards.
+fn lookahead_impact<Shape, NodeId, NodeValue, Kernel, Solver>(
+    shape: &Rc<Shape>,
+    solver: &Solver,
+    node: &Node<NodeId, NodeValue, Shape::Storage>,
+) -> usize
 where
-    NodeId: Debug,
+    NodeId: Copy + Eq + Hash + Debug,
     NodeValue: Clone + PartialEq + Debug,
+    Shape: WaveShape<NodeId, NodeValue>,
+    Kernel: WaveKernel<NodeId, NodeValue, Shape>,
+    Solver: WaveSolver<NodeValue, Kernel>,
 {
-    let mut node_values = node.possible_values.borrow_mut();
-    let collapsed_value = node_values
-        .choose(rng)
-        .expect("This should never be None, because the current shape is not overspecified.")
-        .clone();
-    node_values.clear();
-    node_values.push(collapsed_value);
+    let radii = solver.kernel_radii();
+    let new_kernel = |node: &Node<NodeId, NodeValue, Shape::Storage>| {
+        let mut kernel = Kernel::new(shape.clone(), node);
+        if let Some(radii) = &radii {
+            kernel.set_radii(radii);
+        }
+        kernel
+    };
+
+    let neighbors: Vec<_> = new_kernel(node)
+        .iter_node_ids_without_center()
+        .filter_map(|id| shape.get_node(&id))
+        .filter(|neighbor| !neighbor.is_collapsed())
+        .collect();
+
+    let saved = node.possible_values.borrow().clone();
+    let mut impact = 0;
+    for index in 0..saved.len() {
+        node.update_values(|values| {
+            *values = saved.clone();
+            values.collapse(0, index);
+        });
+
+        for &neighbor in &neighbors {
+            let kernel = new_kernel(neighbor);
+            impact += neighbor
+                .possible_values()
+                .iter()
+                .filter(|value| !solver.is_valid(value, &kernel))
+                .count();
+        }
+    }
+    node.update_values(|values| *values = saved);
+    impact
+}
+
+/// returns the nodes of `shape` with the lowest entropy, that are not collapsed or
+/// overspecified and pass `filter`, in the order of [ReadShape::iter_nodes]. These are the
+/// candidates of [WaveShape::choose_random_with_lowest_entropy_where].
+pub(crate) fn lowest_entropy_candidates<'a, NodeId, NodeValue, Shape>(
+    shape: &'a Shape,
+    filter: impl Fn(&Node<NodeId, NodeValue, Shape::Storage>) -> bool,
+) -> Vec<&'a Node<NodeId, NodeValue, Shape::Storage>>
+where
+    NodeValue: Clone,
+    Shape: ReadShape<NodeId, NodeValue> + ?Sized,
+{
+    let candidates = shape
+        .iter_nodes()
+        .filter(|&node| !node.is_collapsed() && !node.is_overspecified() && filter(node));
+    if shape.is_weighted() {
+        lowest_entropy(candidates, |node| {
+            node.weighted_entropy(|value| shape.node_value_weight(&node.id, value))
+        })
+    } else {
+        lowest_entropy(candidates, |node| node.entropy())
+    }
+}
+
+/// returns the nodes with the lowest `entropy`, in the order of `nodes`.
+fn lowest_entropy<'a, Item, Entropy: PartialOrd>(
+    nodes: impl Iterator<Item = &'a Item>,
+    entropy: impl Fn(&Item) -> Entropy,
+) -> Vec<&'a Item> {
+    let mut bucket = Vec::new();
+    let mut lowest = None;
+    for node in nodes {
+        let node_entropy = entropy(node);
+        match &lowest {
+            Some(lowest) if node_entropy > *lowest => continue,
+            Some(lowest) if node_entropy == *lowest => {}
+            _ => {
+                lowest = Some(node_entropy);
+                bucket.clear();
+            }
+        }
+        bucket.push(node);
+    }
+    bucket
+}
+
+/// Collapses `node` to one of its values. The value is chosen by the weights of `shape`, if it
+/// has any, see [ReadShape::node_value_weight]. If no value has a positive weight, all values
+/// are equally likely.
+fn collapse_node<Shape, NodeId, NodeValue>(
+    shape: &Shape,
+    node: &Node<NodeId, NodeValue, Shape::Storage>,
+    generation: u32,
+    rng: &mut impl EntropySource,
+) where
+    NodeId: Copy,
+    NodeValue: Clone,
+    Shape: WaveShape<NodeId, NodeValue> + ?Sized,
+{
+    let removed = node.update_values(|values| {
+        let weights: Option<Vec<f32>> = shape
+            .is_weighted()
+            .then(|| {
+                values
+                    .as_slice()
+                    .iter()
+                    .map(|value| shape.node_value_weight(&node.id, value))
+                    .collect::<Vec<_>>()
+            })
+            .filter(|weights| weights.iter().any(|weight| *weight > 0.0));
+        let index = rng
+            .choose_value(values.len(), weights.as_deref())
+            .expect("This should never be None, because the current shape is not overspecified.");
+        let removed: Vec<NodeValue> = if shape.is_pruning_traced(&node.id) {
+            let values = values.as_slice();
+            values[..index]
+                .iter()
+                .chain(&values[index + 1..])
+                .cloned()
+                .collect()
+        } else {
+            Vec::new()
+        };
+        values.collapse(generation, index);
+        removed
+    });
 
     *node.is_collapsed.borrow_mut() = true;
+    shape.record_change(node.id, generation);
+
+    for value in removed {
+        let pruning = Pruning {
+            value,
+            step: generation,
+            causes: Vec::new(),
+        };
+        shape.record_pruning(node.id, pruning);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OpenList, QueueStrategy};
+
+    fn pop_all(open_list: &mut OpenList<u32>) -> Vec<u32> {
+        std::iter::from_fn(|| open_list.pop()).collect()
+    }
+
+    #[test]
+    fn entropy_strategy_pops_the_lowest_entropy_first() {
+        let mut open_list = OpenList::new(QueueStrategy::Entropy);
+        open_list.push(1, 3);
+        open_list.push(2, 1);
+        open_list.push(3, 2);
+        // updated to the new entropy instead of queued twice
+        open_list.push(1, 0);
+
+        assert_eq!(open_list.len(), 3);
+        assert_eq!(pop_all(&mut open_list), [1, 2, 3]);
+    }
+
+    #[test]
+    fn fifo_strategy_keeps_the_position_of_queued_nodes() {
+        let mut open_list = OpenList::new(QueueStrategy::Fifo);
+        open_list.push(1, 3);
+        open_list.push(2, 1);
+        open_list.push(3, 2);
+        open_list.push(1, 0);
+
+        assert_eq!(open_list.len(), 3);
+        assert_eq!(pop_all(&mut open_list), [1, 2, 3]);
+    }
+
+    #[test]
+    fn lifo_strategy_moves_requeued_nodes_to_the_front() {
+        let mut open_list = OpenList::new(QueueStrategy::Lifo);
+        open_list.push(1, 3);
+        open_list.push(2, 1);
+        open_list.push(3, 2);
+        open_list.push(1, 0);
+
+        assert_eq!(open_list.len(), 3);
+        assert_eq!(pop_all(&mut open_list), [1, 3, 2]);
+    }
+
+    #[cfg(feature = "tile2d")]
+    #[test]
+    fn lookahead_impact_leaves_the_shape_unchanged() {
+        use super::{lookahead_impact, lowest_entropy_candidates, propagate, ReadShape};
+        use crate::tile2d::{
+            wrapping_mode::Cutoff, AdjacencySolver2D, Direction, Kernel2D, Size2D, TileMap2D,
+        };
+        use std::{collections::HashSet, rc::Rc};
+
+        const GRASS: u8 = 0;
+        const TREE: u8 = 1;
+        const ROCK: u8 = 2;
+
+        // trees and rocks are only next to grass
+        let mut solver = AdjacencySolver2D::new();
+        for direction in [Direction::Right, Direction::Bottom] {
+            for (value, neighbor) in [(TREE, GRASS), (ROCK, GRASS)] {
+                solver = solver.with_rule(value, neighbor, direction);
+                solver = solver.with_rule(neighbor, value, direction);
+            }
+            solver = solver.with_rule(GRASS, GRASS, direction);
+        }
+
+        let shape = Rc::new(TileMap2D::new(
+            Size2D::new(4, 3),
+            Size2D::square(3),
+            &[GRASS, TREE, ROCK],
+        ));
+        let tree = shape.get(1, 1).unwrap();
+        tree.update_values(|values| values.retain(0, |value| *value == TREE));
+        *tree.is_collapsed.borrow_mut() = true;
+        propagate::<_, _, _, Kernel2D<Cutoff, u8>, _>(
+            &shape,
+            &solver,
+            tree.id,
+            0,
+            &mut HashSet::new(),
+            QueueStrategy::default(),
+        );
+
+        let state = |shape: &TileMap2D<u8>| {
+            let nodes: Vec<_> = shape
+                .iter_nodes()
+                .map(|node| {
+                    let values = node.possible_values().to_vec();
+                    let flags = (node.is_collapsed(), node.is_dirty());
+                    (node.id, values, flags, node.entropy())
+                })
+                .collect();
+            let candidates: Vec<_> = lowest_entropy_candidates(shape, |_| true)
+                .iter()
+                .map(|node| node.id)
+                .collect();
+            (nodes, candidates)
+        };
+        let before = state(&shape);
+
+        let mut impact = 0;
+        for node in shape.iter_nodes().filter(|node| !node.is_collapsed()) {
+            impact += lookahead_impact::<_, _, _, Kernel2D<Cutoff, u8>, _>(&shape, &solver, node);
+        }
+
+        assert!(impact > 0);
+        assert_eq!(state(&shape), before);
+    }
 }
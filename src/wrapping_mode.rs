@@ -0,0 +1,57 @@
+//! Marker types that select how a kernel handles nodes outside of the shape.
+
+/// Nodes outside of the shape wrap around to the other side.
+pub struct Wrapping;
+/// Nodes outside of the shape are not part of the kernel.
+pub struct Cutoff;
+/// Nodes outside of the chunk of the center node wrap around to the other side of that chunk,
+/// which creates chunks that can be tiled.
+pub struct ChunkWrapping;
+/// Like [Wrapping], but crossing an edge of the shape also shifts the node along the other
+/// axis, e.g. to create brick or staggered tilings.
+pub struct StaggeredWrapping;
+
+mod private {
+    /// The runtime equivalent of a [WrappingMode](super::WrappingMode).
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Wrap {
+        Cutoff,
+        Wrapping,
+        Chunk,
+        Staggered,
+    }
+
+    pub trait Sealed {
+        const WRAP: Wrap;
+    }
+}
+
+pub(crate) use private::Wrap;
+
+/// Implemented by the marker types of this module, so that a kernel can be generic over its
+/// wrapping mode. This trait is sealed and can not be implemented outside of this crate.
+pub trait WrappingMode: private::Sealed {}
+
+impl private::Sealed for Wrapping {
+    const WRAP: Wrap = Wrap::Wrapping;
+}
+
+impl WrappingMode for Wrapping {}
+
+impl private::Sealed for Cutoff {
+    const WRAP: Wrap = Wrap::Cutoff;
+}
+
+impl WrappingMode for Cutoff {}
+
+impl private::Sealed for ChunkWrapping {
+    const WRAP: Wrap = Wrap::Chunk;
+}
+
+impl WrappingMode for ChunkWrapping {}
+
+impl private::Sealed for StaggeredWrapping {
+    const WRAP: Wrap = Wrap::Staggered;
+}
+
+impl WrappingMode for StaggeredWrapping {}
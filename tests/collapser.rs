@@ -0,0 +1,50 @@
+//! Changes of nodes between the steps of a [Collapser], see [Node::with_values_mut].
+//!
+//! [Node::with_values_mut]: wave_collapse::node::Node::with_values_mut
+#![cfg(all(feature = "tile2d", feature = "rand"))]
+
+use rand::{rngs::StdRng, SeedableRng};
+use wave_collapse::tile2d::{
+    wrapping_mode::Cutoff, AdjacencySolver2D, Direction, Kernel2D, Size2D, TileMap2D,
+};
+use wave_collapse::wave_function::{CollapseConfig, Collapser};
+
+const BLACK: u8 = 0;
+const WHITE: u8 = 1;
+
+/// neighbors always have different colors.
+fn checkerboard_solver() -> AdjacencySolver2D<u8> {
+    AdjacencySolver2D::new()
+        .with_rule(BLACK, WHITE, Direction::Right)
+        .with_rule(WHITE, BLACK, Direction::Right)
+        .with_rule(BLACK, WHITE, Direction::Bottom)
+        .with_rule(WHITE, BLACK, Direction::Bottom)
+}
+
+#[test]
+fn changed_nodes_are_propagated_by_the_next_step() {
+    let solver = checkerboard_solver();
+    for seed in 0..8 {
+        let shape = TileMap2D::new(Size2D::new(4, 3), Size2D::square(3), &[BLACK, WHITE]);
+        let config = CollapseConfig::new(StdRng::seed_from_u64(seed));
+        let mut collapser =
+            Collapser::<_, _, _, Kernel2D<Cutoff, u8>, _, _>::new(shape, &solver, config)
+                .expect("the shape is not empty");
+
+        let node = collapser.shape().get(0, 0).expect("the node is in the map");
+        node.with_values_mut(|values| values.retain(0, |value| *value == WHITE));
+        assert!(node.is_dirty());
+
+        collapser.step().expect("the change is consistent");
+        let node = collapser.shape().get(0, 0).expect("the node is in the map");
+        assert!(!node.is_dirty());
+        let neighbor = collapser.shape().get(1, 0).expect("the node is in the map");
+        assert_eq!(&*neighbor.possible_values(), &[BLACK]);
+
+        let outcome = collapser.finish().expect("the shape can be collapsed");
+        for ((x, y), value) in outcome.shape.iter() {
+            let expected = if (x + y) % 2 == 0 { WHITE } else { BLACK };
+            assert_eq!(*value, expected, "({x}, {y})");
+        }
+    }
+}
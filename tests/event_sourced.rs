@@ -0,0 +1,99 @@
+//! Replays the [DomainEvent]s of an [EventSourcedShape] during a collapse.
+//!
+//! [DomainEvent]: wave_collapse::event_sourced::DomainEvent
+#![cfg(all(feature = "tile2d", feature = "rand"))]
+
+use std::collections::HashMap;
+
+use rand::{rngs::StdRng, SeedableRng};
+use wave_collapse::collapse_wave;
+use wave_collapse::event_sourced::EventSourcedShape;
+use wave_collapse::tile2d::{
+    wrapping_mode::Cutoff, AdjacencySolver2D, Direction, Index2D, Kernel2D, Size2D, TileMap2D,
+};
+use wave_collapse::wave_function::ReadShape;
+
+const GRASS: u8 = 0;
+const TREE: u8 = 1;
+const ROCK: u8 = 2;
+
+/// trees and rocks are only next to grass.
+fn forest_solver() -> AdjacencySolver2D<u8> {
+    let mut solver = AdjacencySolver2D::new();
+    for direction in [Direction::Right, Direction::Bottom] {
+        solver = solver
+            .with_rule(GRASS, GRASS, direction)
+            .with_rule(GRASS, TREE, direction)
+            .with_rule(TREE, GRASS, direction)
+            .with_rule(GRASS, ROCK, direction)
+            .with_rule(ROCK, GRASS, direction);
+    }
+    solver
+}
+
+#[test]
+fn replayed_events_match_the_shape_after_every_step() {
+    let shape = TileMap2D::new(Size2D::new(8, 6), Size2D::square(3), &[GRASS, TREE, ROCK]);
+    let mut replica: HashMap<Index2D, (Vec<u8>, bool)> = shape
+        .iter_nodes()
+        .map(|node| (node.id, (node.possible_values().to_vec(), false)))
+        .collect();
+
+    let solver = forest_solver();
+    let mut rng = StdRng::seed_from_u64(5);
+    let mut collapse = collapse_wave::<_, _, _, Kernel2D<Cutoff, u8>, _>(
+        EventSourcedShape::new(shape),
+        &solver,
+        &mut rng,
+    );
+
+    let mut seen = 0;
+    let mut steps = 0;
+    while let Some(shape) = Iterator::next(&mut &mut collapse) {
+        let events = shape.events_since(seen);
+        assert!(!events.is_empty(), "every step changes a node");
+        for event in events.iter() {
+            assert_eq!(event.version, seen + 1);
+            assert_eq!(event.step, steps);
+            seen = event.version;
+            replica.insert(event.node_id, (event.values.clone(), event.collapsed));
+        }
+        drop(events);
+
+        assert_eq!(seen, shape.version());
+        for node in shape.iter_nodes() {
+            let (values, collapsed) = &replica[&node.id];
+            assert_eq!(values.as_slice(), &*node.possible_values(), "{:?}", node.id);
+            assert_eq!(*collapsed, node.is_collapsed(), "{:?}", node.id);
+        }
+        steps += 1;
+    }
+
+    assert!(steps > 1);
+    assert!(replica
+        .values()
+        .all(|(values, collapsed)| values.len() == 1 && *collapsed));
+}
+
+#[test]
+fn discarded_events_keep_the_node_versions() {
+    let shape = TileMap2D::new(Size2D::new(4, 4), Size2D::square(3), &[GRASS, TREE, ROCK]);
+    let solver = forest_solver();
+    let mut rng = StdRng::seed_from_u64(9);
+    let mut collapse = collapse_wave::<_, _, _, Kernel2D<Cutoff, u8>, _>(
+        EventSourcedShape::new(shape),
+        &solver,
+        &mut rng,
+    );
+
+    let shape = Iterator::next(&mut &mut collapse).expect("the shape is not collapsed");
+    let version = shape.version();
+    let node_id = shape.events_since(0)[0].node_id;
+    shape.discard_until(version);
+
+    assert!(shape.events_since(0).is_empty());
+    assert!(shape
+        .node_version(&node_id)
+        .is_some_and(|version| version > 0));
+    assert_eq!(shape.node_version(&(4, 4)), None);
+}
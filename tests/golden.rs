@@ -0,0 +1,36 @@
+//! Golden digests of small collapses with [CollapseConfig::seeded], see [wave_collapse::rng].
+//! A changed digest means the same seed no longer produces the same map, which is a breaking
+//! change. Run with `cargo test --features tile2d,deterministic`.
+//!
+//! [CollapseConfig::seeded]: wave_collapse::wave_function::CollapseConfig::seeded
+#![cfg(all(feature = "tile2d", feature = "deterministic"))]
+
+use wave_collapse::testing::assert_golden_deterministic;
+use wave_collapse::tile2d::{
+    wrapping_mode::Cutoff, AdjacencySolver2D, Direction, Kernel2D, Size2D, TileMap2D,
+};
+
+const GRASS: u8 = 0;
+const TREE: u8 = 1;
+
+/// trees are never next to each other.
+fn forest_solver() -> AdjacencySolver2D<u8> {
+    AdjacencySolver2D::new()
+        .with_rule(GRASS, GRASS, Direction::Right)
+        .with_rule(GRASS, TREE, Direction::Right)
+        .with_rule(TREE, GRASS, Direction::Right)
+        .with_rule(GRASS, GRASS, Direction::Bottom)
+        .with_rule(GRASS, TREE, Direction::Bottom)
+        .with_rule(TREE, GRASS, Direction::Bottom)
+}
+
+#[test]
+fn forest_digest_is_stable() {
+    let shape = TileMap2D::new(Size2D::new(6, 4), Size2D::square(3), &[GRASS, TREE]);
+    assert_golden_deterministic::<_, Kernel2D<Cutoff, u8>, _>(
+        shape,
+        &forest_solver(),
+        42,
+        0xfc5b_9380_3086_3e57,
+    );
+}
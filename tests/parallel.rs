@@ -0,0 +1,114 @@
+//! Collapses with [collapse_parallel] and [collapse_sub_maps]. The chunks and sub-maps are
+//! collapsed on their own, so the seams between them are checked against the solver.
+//!
+//! [collapse_parallel]: wave_collapse::parallel::collapse_parallel
+//! [collapse_sub_maps]: wave_collapse::parallel::collapse_sub_maps
+#![cfg(feature = "parallel")]
+
+use rand::{rngs::StdRng, SeedableRng};
+use vecgrid::Vecgrid;
+use wave_collapse::parallel::{collapse_parallel, collapse_sub_maps};
+use wave_collapse::tile2d::{AdjacencySolver2D, Direction, Size2D, TileMap2D};
+
+const GRASS: u8 = 0;
+const SAND: u8 = 1;
+const WATER: u8 = 2;
+const ROAD: u8 = 3;
+
+/// returns `true` if `a` and `b` can be next to each other: sand is between grass and water,
+/// roads are next to anything.
+fn allowed(a: u8, b: u8) -> bool {
+    a == ROAD || b == ROAD || a.abs_diff(b) <= 1
+}
+
+fn coast_solver() -> AdjacencySolver2D<u8> {
+    let values = [GRASS, SAND, WATER, ROAD];
+    let mut solver = AdjacencySolver2D::new();
+    for (a, b) in values.into_iter().flat_map(|a| values.map(|b| (a, b))) {
+        if allowed(a, b) {
+            solver = solver.with_rule(a, b, Direction::Right);
+            solver = solver.with_rule(a, b, Direction::Bottom);
+        }
+    }
+    solver
+}
+
+/// returns the values of `grid` row by row.
+fn rows(grid: &Vecgrid<u8>, size: Size2D) -> Vec<Vec<u8>> {
+    (0..size.height as usize)
+        .map(|y| {
+            (0..size.width as usize)
+                .map(|x| *grid.get(x, y).expect("the grid has the size of the map"))
+                .collect()
+        })
+        .collect()
+}
+
+fn assert_valid(rows: &[Vec<u8>]) {
+    for (y, row) in rows.iter().enumerate() {
+        for (x, &value) in row.iter().enumerate() {
+            if let Some(&right) = row.get(x + 1) {
+                assert!(allowed(value, right), "({x}, {y}) and its right neighbor");
+            }
+            if let Some(below) = rows.get(y + 1) {
+                assert!(
+                    allowed(value, below[x]),
+                    "({x}, {y}) and its bottom neighbor"
+                );
+            }
+        }
+    }
+}
+
+fn collapse_chunks(seed: u64) -> Vec<Vec<u8>> {
+    let size = Size2D::new(30, 20);
+    let shape = TileMap2D::new(size, Size2D::square(3), &[GRASS, SAND, WATER]);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let grid = collapse_parallel(shape, &coast_solver(), Size2D::new(8, 6), &mut rng)
+        .expect("the map can be collapsed");
+    rows(&grid, size)
+}
+
+/// a road every 7 rows and columns splits the map into blocks.
+fn collapse_blocks(seed: u64) -> Vec<Vec<u8>> {
+    let size = Size2D::new(30, 20);
+    let template: Vec<_> = (0..size.height)
+        .flat_map(|y| (0..size.width).map(move |x| (x % 7 == 0 || y % 7 == 0).then_some(ROAD)))
+        .collect();
+    let shape = TileMap2D::from_template(size, Size2D::square(3), &template, &[GRASS, SAND, WATER]);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let grid =
+        collapse_sub_maps(shape, &coast_solver(), &mut rng).expect("the map can be collapsed");
+    rows(&grid, size)
+}
+
+#[test]
+fn chunk_seams_are_valid() {
+    for seed in 0..4 {
+        assert_valid(&collapse_chunks(seed));
+    }
+}
+
+#[test]
+fn chunks_with_the_same_seed_are_equal() {
+    assert_eq!(collapse_chunks(11), collapse_chunks(11));
+}
+
+#[test]
+fn sub_map_seams_are_valid() {
+    for seed in 0..4 {
+        let rows = collapse_blocks(seed);
+        assert_valid(&rows);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, &value) in row.iter().enumerate() {
+                let is_road = x % 7 == 0 || y % 7 == 0;
+                assert_eq!(value == ROAD, is_road, "({x}, {y})");
+            }
+        }
+    }
+}
+
+#[test]
+fn sub_maps_with_the_same_seed_are_equal() {
+    assert_eq!(collapse_blocks(11), collapse_blocks(11));
+}
@@ -0,0 +1,102 @@
+//! Pins applied with [pin_all] and the conflicts it reports.
+//!
+//! [pin_all]: wave_collapse::wave_function::pin_all
+#![cfg(feature = "tile2d")]
+
+use std::rc::Rc;
+
+use wave_collapse::tile2d::{
+    wrapping_mode::Cutoff, AdjacencySolver2D, Direction, Index2D, Kernel2D, Size2D, TileMap2D,
+};
+use wave_collapse::wave_function::{pin_all, ReadShape};
+
+const GRASS: u8 = 0;
+const TREE: u8 = 1;
+
+/// trees are never next to each other.
+fn forest_solver() -> AdjacencySolver2D<u8> {
+    AdjacencySolver2D::new()
+        .with_rule(GRASS, GRASS, Direction::Right)
+        .with_rule(GRASS, TREE, Direction::Right)
+        .with_rule(TREE, GRASS, Direction::Right)
+        .with_rule(GRASS, GRASS, Direction::Bottom)
+        .with_rule(GRASS, TREE, Direction::Bottom)
+        .with_rule(TREE, GRASS, Direction::Bottom)
+}
+
+fn forest() -> Rc<TileMap2D<u8>> {
+    Rc::new(TileMap2D::new(
+        Size2D::new(5, 3),
+        Size2D::square(3),
+        &[GRASS, TREE],
+    ))
+}
+
+fn pin(shape: &Rc<TileMap2D<u8>>, pins: &[(Index2D, u8)]) -> Result<(), Vec<usize>> {
+    pin_all::<_, _, _, Kernel2D<Cutoff, u8>, _>(shape, &forest_solver(), pins, 0)
+        .map_err(|conflict| conflict.pins)
+}
+
+fn assert_unchanged(shape: &TileMap2D<u8>) {
+    for node in shape.iter_nodes() {
+        assert!(!node.is_collapsed(), "{:?}", node.id);
+        assert_eq!(node.possible_values().len(), 2, "{:?}", node.id);
+    }
+}
+
+#[test]
+fn consistent_pins_are_applied() {
+    let shape = forest();
+    let pins = [
+        ((0, 0), TREE),
+        ((2, 0), TREE),
+        ((1, 0), GRASS),
+        ((4, 2), TREE),
+    ];
+
+    assert_eq!(pin(&shape, &pins), Ok(()));
+    for (id, value) in pins {
+        assert_eq!(shape.get_node(&id).unwrap().collapsed(), Some(value));
+    }
+    assert_eq!(&*shape.get(0, 1).unwrap().possible_values(), &[GRASS]);
+    assert_eq!(shape.get(2, 2).unwrap().possible_values().len(), 2);
+}
+
+#[test]
+fn a_pin_that_can_not_be_applied_is_the_only_conflict() {
+    let shape = forest();
+    let pins = [((0, 0), TREE), ((4, 2), TREE), ((2, 1), 7)];
+
+    assert_eq!(pin(&shape, &pins), Err(vec![2]));
+    assert_unchanged(&shape);
+}
+
+#[test]
+fn conflicting_pin_is_reported_with_the_pins_it_conflicts_with() {
+    let shape = forest();
+    let pins = [
+        ((0, 0), TREE),
+        ((4, 2), GRASS),
+        ((1, 0), TREE),
+        ((3, 1), TREE),
+    ];
+
+    assert_eq!(pin(&shape, &pins), Err(vec![0, 2]));
+    assert_unchanged(&shape);
+}
+
+#[test]
+fn pins_that_only_conflict_with_each_other_are_reported() {
+    let shape = forest();
+    let pins = [
+        ((0, 0), TREE),
+        ((4, 2), TREE),
+        ((2, 1), TREE),
+        ((3, 0), GRASS),
+        ((2, 2), TREE),
+        ((0, 2), TREE),
+    ];
+
+    assert_eq!(pin(&shape, &pins), Err(vec![2, 4]));
+    assert_unchanged(&shape);
+}
@@ -0,0 +1,79 @@
+//! Collapses of weighted [TileMap2D]s, see [TileMap2D::new_weighted] and [TileMap2D::with_bias].
+#![cfg(all(feature = "tile2d", feature = "rand"))]
+
+use rand::{rngs::StdRng, SeedableRng};
+use wave_collapse::collapse_fully;
+use wave_collapse::tile2d::{
+    wrapping_mode::Cutoff, AdjacencySolver2D, Direction, Kernel2D, Size2D, TileMap2D,
+};
+use wave_collapse::wave_function::CollapseConfig;
+
+const BLACK: u8 = 0;
+const WHITE: u8 = 1;
+
+/// neighbors always have different colors.
+fn checkerboard_solver() -> AdjacencySolver2D<u8> {
+    AdjacencySolver2D::new()
+        .with_rule(BLACK, WHITE, Direction::Right)
+        .with_rule(WHITE, BLACK, Direction::Right)
+        .with_rule(BLACK, WHITE, Direction::Bottom)
+        .with_rule(WHITE, BLACK, Direction::Bottom)
+}
+
+/// returns the collapsed values of `shape` row by row.
+fn collapse(shape: TileMap2D<u8>, solver: &AdjacencySolver2D<u8>, seed: u64) -> Vec<u8> {
+    let config = CollapseConfig::new(StdRng::seed_from_u64(seed));
+    let outcome = collapse_fully::<_, _, _, Kernel2D<Cutoff, u8>, _, _>(shape, solver, config)
+        .expect("the shape can be collapsed");
+    outcome.shape.iter().map(|(_, value)| *value).collect()
+}
+
+#[test]
+fn values_without_weight_can_be_forced() {
+    for seed in 0..8 {
+        let shape = TileMap2D::new_weighted(
+            Size2D::new(5, 4),
+            Size2D::square(3),
+            &[(BLACK, 1.0), (WHITE, 0.0)],
+        );
+        let values = collapse(shape, &checkerboard_solver(), seed);
+
+        assert_eq!(values.len(), 20);
+        assert_eq!(values.iter().filter(|value| **value == WHITE).count(), 10);
+    }
+}
+
+#[test]
+fn all_weights_zero_collapses() {
+    let shape = TileMap2D::new_weighted(
+        Size2D::new(5, 4),
+        Size2D::square(3),
+        &[(BLACK, 0.0), (WHITE, 0.0)],
+    );
+    let values = collapse(shape, &checkerboard_solver(), 7);
+
+    assert_eq!(values.len(), 20);
+    assert_eq!(values.iter().filter(|value| **value == WHITE).count(), 10);
+}
+
+#[test]
+fn zero_bias_is_only_chosen_if_forced() {
+    let size = Size2D::new(5, 4);
+    let no_white = vec![0.0; (size.width * size.height) as usize];
+
+    // any neighbors are allowed, so white is never chosen
+    let solver = checkerboard_solver()
+        .with_rule(BLACK, BLACK, Direction::Right)
+        .with_rule(WHITE, WHITE, Direction::Right)
+        .with_rule(BLACK, BLACK, Direction::Bottom)
+        .with_rule(WHITE, WHITE, Direction::Bottom);
+    let shape = TileMap2D::new(size, Size2D::square(3), &[BLACK, WHITE])
+        .with_bias(no_white.clone(), |value| *value == WHITE);
+    assert_eq!(collapse(shape, &solver, 3), vec![BLACK; 20]);
+
+    // the checkerboard forces white next to every black
+    let shape = TileMap2D::new(size, Size2D::square(3), &[BLACK, WHITE])
+        .with_bias(no_white, |value| *value == WHITE);
+    let values = collapse(shape, &checkerboard_solver(), 3);
+    assert_eq!(values.iter().filter(|value| **value == WHITE).count(), 10);
+}